@@ -0,0 +1,501 @@
+/*!
+Reading of the metadata blocks of FLAC files and of the pages of Ogg streams.
+
+The FlacReader walks over the metadata blocks behind the `fLaC` signature (an ID3v2 tag in front of
+it is skipped) and stops in front of the first audio frame. STREAMINFO, VORBIS_COMMENT and PICTURE
+blocks can be decoded, the other blocks are read by Read or skipped.
+
+The OggReader returns the pages of an Ogg stream with their granule position, serial number and
+sequence number, the CRC of every page is verified. With resync enabled, garbage in front of a page
+and pages with a wrong CRC are skipped by searching the next `OggS` capture pattern.
+
+```rust
+use blockwise_reader::BlockWiseReader;
+use blockwise_reader::audio::{FlacReader, STREAMINFO, VORBIS_COMMENT};
+
+let mut data = b"fLaC\x00\x00\x00\x22".to_vec();
+data.extend_from_slice(b"\x10\x00\x10\x00\x00\x00\x00\x00\x00\x00\x0a\xc4\x42\xf0\x00\x00\xac\x44");
+data.extend_from_slice(&[0; 16]);
+data.extend_from_slice(b"\x84\x00\x00\x16\x03\0\0\0abc\x01\0\0\0\x07\0\0\0TITLE=x");
+data.extend_from_slice(b"\xff\xf8 frames");
+let mut bwr = BlockWiseReader::from_slice(&data);
+let mut flac = FlacReader::new(&mut bwr, 1024);
+assert_eq!(STREAMINFO, flac.next_block().unwrap().unwrap().block_type);
+let info = flac.stream_info().unwrap().unwrap();
+assert_eq!((44100, 2, 16, 44100), (info.sample_rate, info.channels, info.bits_per_sample, info.total_samples));
+let block = flac.next_block().unwrap().unwrap();
+assert_eq!((VORBIS_COMMENT, true), (block.block_type, block.is_last));
+let comment = flac.vorbis_comment().unwrap().unwrap();
+assert_eq!(vec![("TITLE".to_string(), "x".to_string())], comment.comments);
+assert!(flac.next_block().unwrap().is_none());
+assert_eq!(b"\xff\xf8 frames", bwr.get());
+```
+*/
+
+use crate::binary::{read_uint, Endian, Fields};
+use crate::{BlockWiseReader, Error, FindPos};
+use std::io::Read;
+
+pub const STREAMINFO: u8 = 0;
+pub const PADDING: u8 = 1;
+pub const APPLICATION: u8 = 2;
+pub const SEEKTABLE: u8 = 3;
+pub const VORBIS_COMMENT: u8 = 4;
+pub const CUESHEET: u8 = 5;
+pub const PICTURE: u8 = 6;
+
+/// A FLAC metadata block with absolute offsets in the stream.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MetadataBlock {
+ pub block_type: u8,
+ /// the last metadata block in front of the audio frames
+ pub is_last: bool,
+ /// absolute offset of the block header
+ pub offset: u64,
+ /// size of the block data without the header
+ pub size: u64,
+ /// absolute offset of the block data
+ pub data_offset: u64,
+}
+
+/// the decoded STREAMINFO block
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StreamInfo {
+ pub min_block_size: u16,
+ pub max_block_size: u16,
+ /// 0 if unknown
+ pub min_frame_size: u32,
+ /// 0 if unknown
+ pub max_frame_size: u32,
+ pub sample_rate: u32,
+ pub channels: u8,
+ pub bits_per_sample: u8,
+ /// 0 if unknown
+ pub total_samples: u64,
+ /// MD5 of the unencoded audio data
+ pub md5: [u8; 16],
+}
+
+/// the decoded VORBIS_COMMENT block, it is also the comment header of Ogg Vorbis without framing
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct VorbisComment {
+ pub vendor: String,
+ /// the field names and values in the stored order, the names are not normalized
+ pub comments: Vec<(String, String)>,
+}
+
+/// the decoded PICTURE block
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Picture {
+ /// the ID3v2 APIC picture type, 3 is the front cover
+ pub picture_type: u32,
+ pub mime_type: String,
+ pub description: String,
+ pub width: u32,
+ pub height: u32,
+ pub depth: u32,
+ /// number of colors of indexed pictures, else 0
+ pub colors: u32,
+ pub data: Vec<u8>,
+}
+
+/// Reads the metadata blocks of a FLAC file from the current pos of a BlockWiseReader on.
+/// Consumed bytes are removed by pos_drop().
+pub struct FlacReader<'r, 'a> {
+ bwr: &'r mut BlockWiseReader<'a>,
+ buffersize: usize,
+ /// the current block, None before the first block
+ current: Option<MetadataBlock>,
+ /// unread data bytes of the current block
+ remaining: u64,
+}
+
+impl<'r, 'a> FlacReader<'r, 'a> {
+ /// creates a FlacReader which reads ahead in buffersize steps
+ pub fn new(bwr: &'r mut BlockWiseReader<'a>, buffersize: usize) -> Self {
+  Self {
+   bwr,
+   buffersize: buffersize.max(1),
+   current: None,
+   remaining: 0,
+  }
+ }
+
+ /// Returns the next metadata block and sets pos to its data, the rest of the previous block is skipped.
+ /// Returns None behind the last block, then pos is set to the first audio frame.
+ pub fn next_block(&mut self) -> Result<Option<MetadataBlock>, Error> {
+  if self.current.is_some_and(|b| b.is_last) {
+   self.skip_remaining()?;
+   return Ok(None);
+  }
+  if self.current.is_none() {
+   self.skip_id3v2()?;
+   if self.bwr.slurp_exact(4)? < 4 || &self.bwr.get()[..4] != b"fLaC" {
+    return Err(Error::Msg("missing flac signature"));
+   }
+   self.bwr.pos_add(4);
+   self.bwr.pos_drop();
+  } else {
+   self.skip_remaining()?;
+  }
+
+  if self.bwr.slurp_exact(4)? < 4 {
+   return Err(Error::Msg("unexpected end of file in flac metadata block"));
+  }
+  let header = &self.bwr.get()[..4];
+  let block_type = header[0] & 0x7f;
+  if 127 == block_type {
+   return Err(Error::Msg("invalid flac metadata block type"));
+  }
+  if self.current.is_none() && STREAMINFO != block_type {
+   return Err(Error::Msg("missing flac streaminfo block"));
+  }
+  let offset = self.bwr.pos_absolute();
+  let block = MetadataBlock {
+   block_type,
+   is_last: header[0] & 0x80 != 0,
+   offset,
+   size: read_uint(&header[1..], Endian::Big),
+   data_offset: offset + 4,
+  };
+  self.bwr.pos_add(4);
+  self.bwr.pos_drop();
+  self.current = Some(block);
+  self.remaining = block.size;
+  Ok(Some(block))
+ }
+
+ fn skip_id3v2(&mut self) -> Result<(), Error> {
+  if self.bwr.slurp_exact(10)? < 10 || &self.bwr.get()[..3] != b"ID3" {
+   return Ok(());
+  }
+  let header = &self.bwr.get()[..10];
+  if header[6..].iter().any(|b| b & 0x80 != 0) {
+   return Err(Error::Msg("invalid id3v2 tag size"));
+  }
+  let size = header[6..].iter().fold(0u64, |acc, b| acc << 7 | *b as u64);
+  let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+  let count = 10 + size + footer;
+  if self.bwr.pos_skip(count, self.buffersize)? < count {
+   return Err(Error::Msg("unexpected end of file in id3v2 tag"));
+  }
+  Ok(())
+ }
+
+ fn skip_remaining(&mut self) -> Result<(), Error> {
+  let count = self.remaining;
+  if self.bwr.pos_skip(count, self.buffersize)? < count {
+   return Err(Error::Msg("unexpected end of file in flac metadata block"));
+  }
+  self.remaining = 0;
+  Ok(())
+ }
+
+ /// the unread data of the current block as a whole, it remains unread
+ pub fn payload(&mut self) -> Result<&[u8], Error> {
+  let size = self.remaining as usize;
+  if self.bwr.slurp_exact(size)? < size {
+   return Err(Error::Msg("unexpected end of file in flac metadata block"));
+  }
+  Ok(&self.bwr.get()[..size])
+ }
+
+ /// the data of the current block if it is an unread block of block_type
+ fn unread_block(
+  &mut self,
+  block_type: u8,
+  invalid: &'static str,
+ ) -> Result<Option<Fields<'_>>, Error> {
+  match self.current {
+   Some(b) if b.block_type == block_type && b.size == self.remaining => Ok(Some(Fields {
+    data: self.payload()?,
+    invalid,
+   })),
+   _ => Ok(None),
+  }
+ }
+
+ /// the decoded current block if it is an unread STREAMINFO block
+ pub fn stream_info(&mut self) -> Result<Option<StreamInfo>, Error> {
+  let Some(mut f) = self.unread_block(STREAMINFO, "invalid flac streaminfo block")? else {
+   return Ok(None);
+  };
+  let min_block_size = f.uint(2, Endian::Big)? as u16;
+  let max_block_size = f.uint(2, Endian::Big)? as u16;
+  let min_frame_size = f.uint(3, Endian::Big)? as u32;
+  let max_frame_size = f.uint(3, Endian::Big)? as u32;
+  let bits = f.uint(8, Endian::Big)?;
+  let md5 = f.take(16)?.try_into().unwrap();
+  Ok(Some(StreamInfo {
+   min_block_size,
+   max_block_size,
+   min_frame_size,
+   max_frame_size,
+   sample_rate: (bits >> 44) as u32,
+   channels: (bits >> 41 & 0x7) as u8 + 1,
+   bits_per_sample: (bits >> 36 & 0x1f) as u8 + 1,
+   total_samples: bits & 0xf_ffff_ffff,
+   md5,
+  }))
+ }
+
+ /// the decoded current block if it is an unread VORBIS_COMMENT block
+ pub fn vorbis_comment(&mut self) -> Result<Option<VorbisComment>, Error> {
+  let Some(mut f) = self.unread_block(VORBIS_COMMENT, "invalid flac vorbis comment block")? else {
+   return Ok(None);
+  };
+  let vendor = f.string(Endian::Little)?;
+  let count = f.uint(4, Endian::Little)?;
+  let mut comments = vec![];
+  for _ in 0..count {
+   let comment = f.string(Endian::Little)?;
+   let (name, value) = comment.split_once('=').ok_or(Error::Msg(f.invalid))?;
+   comments.push((name.to_string(), value.to_string()));
+  }
+  Ok(Some(VorbisComment { vendor, comments }))
+ }
+
+ /// the decoded current block if it is an unread PICTURE block
+ pub fn picture(&mut self) -> Result<Option<Picture>, Error> {
+  let Some(mut f) = self.unread_block(PICTURE, "invalid flac picture block")? else {
+   return Ok(None);
+  };
+  let picture_type = f.uint(4, Endian::Big)? as u32;
+  let mime_type = f.string(Endian::Big)?;
+  let description = f.string(Endian::Big)?;
+  let width = f.uint(4, Endian::Big)? as u32;
+  let height = f.uint(4, Endian::Big)? as u32;
+  let depth = f.uint(4, Endian::Big)? as u32;
+  let colors = f.uint(4, Endian::Big)? as u32;
+  let len = f.uint(4, Endian::Big)?;
+  let data = f.take(len)?.to_vec();
+  Ok(Some(Picture {
+   picture_type,
+   mime_type,
+   description,
+   width,
+   height,
+   depth,
+   colors,
+   data,
+  }))
+ }
+}
+
+impl Read for FlacReader<'_, '_> {
+ fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+  self.bwr.read_payload(
+   self.buffersize,
+   &mut self.remaining,
+   buf,
+   Some("unexpected end of file in flac metadata block"),
+  )
+ }
+}
+
+/// the page is not the first page of a logical stream and continues a packet of the previous page
+pub const CONTINUED: u8 = 1;
+/// first page of a logical stream
+pub const BOS: u8 = 2;
+/// last page of a logical stream
+pub const EOS: u8 = 4;
+
+/// An Ogg page with absolute offsets in the stream.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct OggPage {
+ /// CONTINUED, BOS and EOS
+ pub header_type: u8,
+ /// the codec specific position behind the last packet which ends on the page, None if no packet ends on it
+ pub granule_position: Option<u64>,
+ pub serial: u32,
+ pub sequence: u32,
+ pub crc: u32,
+ /// the lacing values, a packet ends with a value below 255
+ pub segments: Vec<u8>,
+ /// absolute offset of the capture pattern
+ pub offset: u64,
+ /// size of the payload
+ pub size: u64,
+ /// absolute offset of the payload
+ pub payload_offset: u64,
+ /// bytes skipped by resync in front of the page
+ pub skipped: u64,
+}
+
+impl OggPage {
+ /// the sizes of the packets or packet parts on the page, the last one is incomplete if it is 255 * n
+ pub fn packet_sizes(&self) -> Vec<u64> {
+  let mut sizes = vec![];
+  let mut size = 0;
+  for (i, lacing) in self.segments.iter().enumerate() {
+   size += *lacing as u64;
+   if *lacing < 255 || i + 1 == self.segments.len() {
+    sizes.push(size);
+    size = 0;
+   }
+  }
+  sizes
+ }
+}
+
+const OGG_CAPTURE: &[u8] = b"OggS";
+/// the size of the page header without the lacing values
+const OGG_HEADER_SIZE: usize = 27;
+
+/// Reads the pages of an Ogg stream from the current pos of a BlockWiseReader on.
+/// Consumed bytes are removed by pos_drop().
+pub struct OggReader<'r, 'a> {
+ bwr: &'r mut BlockWiseReader<'a>,
+ buffersize: usize,
+ /// skip invalid data up to the next valid page instead of returning an error
+ resync: bool,
+ /// unread payload bytes of the current page
+ remaining: u64,
+}
+
+impl<'r, 'a> OggReader<'r, 'a> {
+ /// creates an OggReader which reads ahead in buffersize steps
+ pub fn new(bwr: &'r mut BlockWiseReader<'a>, buffersize: usize, resync: bool) -> Self {
+  Self {
+   bwr,
+   buffersize: buffersize.max(1),
+   resync,
+   remaining: 0,
+  }
+ }
+
+ /// Returns the next page with a valid CRC and sets pos to its payload, the rest of the previous page is skipped.
+ /// Returns None at end of file.
+ pub fn next_page(&mut self) -> Result<Option<OggPage>, Error> {
+  let count = self.remaining;
+  if self.bwr.pos_skip(count, self.buffersize)? < count {
+   return Err(Error::Msg("unexpected end of file in ogg page"));
+  }
+  self.remaining = 0;
+  let start = self.bwr.pos_absolute();
+  loop {
+   if 0 == self.bwr.slurp_exact(1)? {
+    return Ok(None);
+   }
+   match self.page() {
+    Ok(mut page) => {
+     page.skipped = page.offset - start;
+     self
+      .bwr
+      .pos_add((page.payload_offset - page.offset) as usize);
+     self.bwr.pos_drop();
+     self.remaining = page.size;
+     return Ok(Some(page));
+    }
+    // read failures are not resynchronized
+    Err(e @ Error::IO(_)) => return Err(e),
+    Err(e) if !self.resync => return Err(e),
+    Err(Error::Msg(_)) => {
+     self.bwr.pos_add(1);
+     if !self.bwr.slurp_search_repos_loop(
+      self.buffersize.max(2 * OGG_CAPTURE.len()),
+      OGG_CAPTURE,
+      FindPos::Begin,
+     )? {
+      self.bwr.pos_add(self.bwr.available_bytes());
+      self.bwr.pos_drop();
+      return Ok(None);
+     }
+     self.bwr.pos_drop();
+    }
+   }
+  }
+ }
+
+ /// checks and decodes the page at pos without altering pos
+ fn page(&mut self) -> Result<OggPage, Error> {
+  if self.bwr.slurp_exact(OGG_HEADER_SIZE)? < OGG_HEADER_SIZE {
+   if self.bwr.get().starts_with(OGG_CAPTURE) || OGG_CAPTURE.starts_with(self.bwr.get()) {
+    return Err(Error::Msg("unexpected end of file in ogg page"));
+   }
+   return Err(Error::Msg("missing ogg capture pattern"));
+  }
+  let header = &self.bwr.get()[..OGG_HEADER_SIZE];
+  if !header.starts_with(OGG_CAPTURE) {
+   return Err(Error::Msg("missing ogg capture pattern"));
+  }
+  if 0 != header[4] {
+   return Err(Error::Msg("unsupported ogg version"));
+  }
+  let header_size = OGG_HEADER_SIZE + header[26] as usize;
+  if self.bwr.slurp_exact(header_size)? < header_size {
+   return Err(Error::Msg("unexpected end of file in ogg page"));
+  }
+  let segments = self.bwr.get()[OGG_HEADER_SIZE..header_size].to_vec();
+  let size = segments.iter().map(|s| *s as usize).sum::<usize>();
+  if self.bwr.slurp_exact(header_size + size)? < header_size + size {
+   return Err(Error::Msg("unexpected end of file in ogg page"));
+  }
+  let page = &self.bwr.get()[..header_size + size];
+  let crc = read_uint(&page[22..26], Endian::Little) as u32;
+  let computed =
+   ogg_crc_update(ogg_crc_update(ogg_crc_update(0, &page[..22]), &[0; 4]), &page[26..]);
+  if crc != computed {
+   return Err(Error::Msg("ogg page crc mismatch"));
+  }
+  let granule = read_uint(&page[6..14], Endian::Little);
+  let offset = self.bwr.pos_absolute();
+  Ok(OggPage {
+   header_type: page[5],
+   granule_position: (u64::MAX != granule).then_some(granule),
+   serial: read_uint(&page[14..18], Endian::Little) as u32,
+   sequence: read_uint(&page[18..22], Endian::Little) as u32,
+   crc,
+   segments,
+   offset,
+   size: size as u64,
+   payload_offset: offset + header_size as u64,
+   skipped: 0,
+  })
+ }
+
+ /// the unread payload of the current page as a whole, it remains unread
+ pub fn payload(&mut self) -> Result<&[u8], Error> {
+  let size = self.remaining as usize;
+  if self.bwr.slurp_exact(size)? < size {
+   return Err(Error::Msg("unexpected end of file in ogg page"));
+  }
+  Ok(&self.bwr.get()[..size])
+ }
+}
+
+impl Read for OggReader<'_, '_> {
+ fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+  self.bwr.read_payload(
+   self.buffersize,
+   &mut self.remaining,
+   buf,
+   Some("unexpected end of file in ogg page"),
+  )
+ }
+}
+
+const OGG_CRC_TABLE: [u32; 256] = ogg_crc_table();
+
+const fn ogg_crc_table() -> [u32; 256] {
+ let mut table = [0u32; 256];
+ let mut i = 0;
+ while i < 256 {
+  let mut c = (i as u32) << 24;
+  let mut k = 0;
+  while k < 8 {
+   c = if c & 0x8000_0000 != 0 { 0x04c1_1db7 ^ (c << 1) } else { c << 1 };
+   k += 1;
+  }
+  table[i] = c;
+  i += 1;
+ }
+ table
+}
+
+/// continues the unreflected CRC-32 of Ogg pages of former data with data, starts with 0
+fn ogg_crc_update(crc: u32, data: &[u8]) -> u32 {
+ data
+  .iter()
+  .fold(crc, |c, b| OGG_CRC_TABLE[((c >> 24) ^ *b as u32) as usize] ^ (c << 8))
+}
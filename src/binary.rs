@@ -0,0 +1,90 @@
+use crate::Error;
+
+/// byte order of numbers
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Endian {
+ Big,
+ Little,
+}
+
+/// reads an unsigned number of up to 8 bytes
+pub(crate) fn read_uint(bytes: &[u8], endian: Endian) -> u64 {
+ match endian {
+  Endian::Big => bytes.iter().fold(0, |acc, b| acc << 8 | *b as u64),
+  Endian::Little => bytes.iter().rev().fold(0, |acc, b| acc << 8 | *b as u64),
+ }
+}
+
+/// takes the fields of a payload one after another, invalid is the error of a truncated field
+pub(crate) struct Fields<'d> {
+ pub(crate) data: &'d [u8],
+ pub(crate) invalid: &'static str,
+}
+
+impl<'d> Fields<'d> {
+ pub(crate) fn take(&mut self, n: u64) -> Result<&'d [u8], Error> {
+  if n > self.data.len() as u64 {
+   return Err(Error::Msg(self.invalid));
+  }
+  let (field, rest) = self.data.split_at(n as usize);
+  self.data = rest;
+  Ok(field)
+ }
+
+ pub(crate) fn uint(&mut self, n: u64, endian: Endian) -> Result<u64, Error> {
+  Ok(read_uint(self.take(n)?, endian))
+ }
+
+ /// an UTF-8 string with a length prefix of 4 bytes
+ pub(crate) fn string(&mut self, endian: Endian) -> Result<String, Error> {
+  let len = self.uint(4, endian)?;
+  let invalid = self.invalid;
+  String::from_utf8(self.take(len)?.to_vec()).map_err(|_| Error::Msg(invalid))
+ }
+
+ /// a field with a length prefix of n bytes
+ pub(crate) fn vec(&mut self, n: u64, endian: Endian) -> Result<Fields<'d>, Error> {
+  let len = self.uint(n, endian)?;
+  Ok(Fields {
+   data: self.take(len)?,
+   invalid: self.invalid,
+  })
+ }
+
+ /// the remaining data as numbers of n bytes
+ pub(crate) fn uint_list(mut self, n: u64, endian: Endian) -> Result<Vec<u64>, Error> {
+  if !(self.data.len() as u64).is_multiple_of(n) {
+   return Err(Error::Msg(self.invalid));
+  }
+  let mut list = vec![];
+  while !self.data.is_empty() {
+   list.push(self.uint(n, endian)?);
+  }
+  Ok(list)
+ }
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+ let mut table = [0u32; 256];
+ let mut i = 0;
+ while i < 256 {
+  let mut c = i as u32;
+  let mut k = 0;
+  while k < 8 {
+   c = if c & 1 != 0 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 };
+   k += 1;
+  }
+  table[i] = c;
+  i += 1;
+ }
+ table
+}
+
+/// continues the CRC-32 (like in PNG, ZIP or gzip) of former data with data, starts with 0
+pub(crate) fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+ !data
+  .iter()
+  .fold(!crc, |c, b| CRC32_TABLE[((c ^ *b as u32) & 0xff) as usize] ^ (c >> 8))
+}
@@ -0,0 +1,177 @@
+/*!
+Reading of bit packed data like codec headers or deflate block headers.
+
+A BitReader is a view on a BlockWiseReader which reads values of up to 64 bits in MSB first order
+(H.264/H.265, AAC ADTS, FLAC frame headers) or LSB first order (deflate). The byte at pos is the
+byte with the next bit, more bytes are slurped on demand. After finish() pos is set to the first
+byte behind the bits, so byte level reading can continue.
+
+```rust
+use blockwise_reader::BlockWiseReader;
+use blockwise_reader::bits::{BitOrder, BitReader};
+
+// an ADTS header
+let mut bwr = BlockWiseReader::from_slice(b"\xff\xf1\x50\x80\x02\x1f\xfcpayload");
+let mut bits = BitReader::new(&mut bwr, 1024, BitOrder::MsbFirst);
+assert_eq!(0xfff, bits.read_bits(12).unwrap());
+bits.skip_bits(4).unwrap();
+assert_eq!(1, bits.read_bits(2).unwrap()); // AAC LC - 1
+assert_eq!(4, bits.read_bits(4).unwrap()); // 44100 Hz
+bits.skip_bits(1).unwrap();
+assert_eq!(2, bits.read_bits(3).unwrap()); // channels
+bits.skip_bits(4).unwrap();
+assert_eq!(16, bits.read_bits(13).unwrap()); // frame length
+bits.skip_bits(13).unwrap();
+bits.finish();
+assert_eq!(b"payload", bwr.get());
+```
+*/
+
+use crate::{BlockWiseReader, Error};
+
+/// the order of the bits in a byte
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BitOrder {
+ /// the highest bit of a byte is read first, values are stored with the highest bit first
+ MsbFirst,
+ /// the lowest bit of a byte is read first, values are stored with the lowest bit first
+ LsbFirst,
+}
+
+/// Reads bits from the current pos of a BlockWiseReader on.
+/// Consumed bytes are removed by pos_drop() in buffersize steps.
+pub struct BitReader<'r, 'a> {
+ bwr: &'r mut BlockWiseReader<'a>,
+ buffersize: usize,
+ order: BitOrder,
+ /// the number of consumed bits of the byte at pos
+ bit: usize,
+}
+
+impl<'r, 'a> BitReader<'r, 'a> {
+ /// creates a BitReader which reads ahead in buffersize steps
+ pub fn new(bwr: &'r mut BlockWiseReader<'a>, buffersize: usize, order: BitOrder) -> Self {
+  Self {
+   bwr,
+   buffersize: buffersize.max(1),
+   order,
+   bit: 0,
+  }
+ }
+
+ /// returns the next bitcount bits, at most 64, without consuming them
+ pub fn peek_bits(&mut self, bitcount: u32) -> Result<u64, Error> {
+  if bitcount > 64 {
+   return Err(Error::Msg("more than 64 bits requested"));
+  }
+  if 0 == bitcount {
+   return Ok(0);
+  }
+  let count = (self.bit + bitcount as usize).div_ceil(8);
+  if self.bwr.available_bytes() < count {
+   self.bwr.slurp(self.buffersize.max(count))?;
+   if self.bwr.slurp_exact(count)? < count {
+    return Err(Error::Msg("unexpected end of file in bit stream"));
+   }
+  }
+  let bytes = &self.bwr.get()[..count];
+  let value = match self.order {
+   BitOrder::MsbFirst => {
+    let all = bytes.iter().fold(0u128, |acc, b| acc << 8 | *b as u128);
+    all >> (count * 8 - self.bit - bitcount as usize)
+   }
+   BitOrder::LsbFirst => {
+    let all = bytes
+     .iter()
+     .rev()
+     .fold(0u128, |acc, b| acc << 8 | *b as u128);
+    all >> self.bit
+   }
+  };
+  Ok((value & ((1u128 << bitcount) - 1)) as u64)
+ }
+
+ /// returns and consumes the next bitcount bits, at most 64
+ pub fn read_bits(&mut self, bitcount: u32) -> Result<u64, Error> {
+  let value = self.peek_bits(bitcount)?;
+  self.advance(bitcount as usize);
+  Ok(value)
+ }
+
+ /// returns and consumes the next bit
+ pub fn read_bit(&mut self) -> Result<bool, Error> {
+  Ok(1 == self.read_bits(1)?)
+ }
+
+ /// Consumes bitcount bits without buffering them.
+ /// If the stream ends before, the reader is set to the end of the stream.
+ pub fn skip_bits(&mut self, bitcount: u64) -> Result<(), Error> {
+  let total = self.bit as u64 + bitcount;
+  let bytecount = total / 8;
+  self.bit = 0;
+  if self.bwr.pos_skip(bytecount, self.buffersize)? < bytecount
+   || !total.is_multiple_of(8) && 0 == self.bwr.slurp_exact(1)?
+  {
+   return Err(Error::Msg("unexpected end of file in bit stream"));
+  }
+  self.bit = (total % 8) as usize;
+  Ok(())
+ }
+
+ fn advance(&mut self, bitcount: usize) {
+  let total = self.bit + bitcount;
+  self.bwr.pos_add(total / 8);
+  self.bit = total % 8;
+  if self.bwr.pos_get() >= self.buffersize {
+   self.bwr.pos_drop();
+  }
+ }
+
+ /// reads an unsigned Exp-Golomb code ue(v) like in H.264 and H.265
+ pub fn read_ue(&mut self) -> Result<u64, Error> {
+  let mut leading_zeros = 0;
+  while !self.read_bit()? {
+   leading_zeros += 1;
+   if leading_zeros > 63 {
+    return Err(Error::Msg("invalid exp-golomb code"));
+   }
+  }
+  Ok((1 << leading_zeros) - 1 + self.read_bits(leading_zeros)?)
+ }
+
+ /// reads a signed Exp-Golomb code se(v) like in H.264 and H.265
+ pub fn read_se(&mut self) -> Result<i64, Error> {
+  let k = self.read_ue()?;
+  if 1 == k % 2 {
+   Ok(k.div_ceil(2) as i64)
+  } else {
+   Ok(-((k / 2) as i64))
+  }
+ }
+
+ /// true if the next bit is the first bit of a byte
+ pub fn is_aligned(&self) -> bool {
+  0 == self.bit
+ }
+
+ /// skips the rest of a partially consumed byte and returns the number of skipped bits
+ pub fn align(&mut self) -> u32 {
+  if self.is_aligned() {
+   return 0;
+  }
+  let skipped = 8 - self.bit as u32;
+  self.advance(skipped as usize);
+  skipped
+ }
+
+ /// the absolute position of the next bit in the stream
+ pub fn bit_position(&self) -> u64 {
+  self.bwr.pos_absolute() * 8 + self.bit as u64
+ }
+
+ /// aligns to the next byte and drops the consumed bytes, pos of the BlockWiseReader is set to the byte behind the bits
+ pub fn finish(mut self) {
+  self.align();
+  self.bwr.pos_drop();
+ }
+}
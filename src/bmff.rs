@@ -0,0 +1,203 @@
+/*!
+Walking over the box tree of ISO base media files like MP4, MOV, 3GP or HEIF.
+
+Boxes with 32 bit and 64 bit (largesize) sizes, boxes which extend to the end of file and `uuid`
+boxes are supported. The payload of a box can be read by Read, the rest of it is skipped by the next
+call of next_box() without buffering it. Container boxes like `moov` or `trak` can be entered, then
+their children are returned by next_box().
+
+```rust
+use blockwise_reader::BlockWiseReader;
+use blockwise_reader::bmff::{is_container, BoxReader};
+
+let mut bwr = BlockWiseReader::from_slice(
+ b"\0\0\0\x10ftypisom\0\0\0\0\0\0\0\x10moov\0\0\0\x08mvhd\0\0\0\0mdat1234",
+);
+let mut boxes = BoxReader::new(&mut bwr, 1024);
+let mut found = vec![];
+while let Some(b) = boxes.next_box().unwrap() {
+ if is_container(&b.box_type) {
+  boxes.enter().unwrap();
+ }
+ found.push((b.box_type, b.depth, b.offset, b.size));
+}
+assert_eq!(
+ vec![
+  (*b"ftyp", 0, 0, Some(16)),
+  (*b"moov", 0, 16, Some(16)),
+  (*b"mvhd", 1, 24, Some(8)),
+  (*b"mdat", 0, 32, None),
+ ],
+ found
+);
+```
+*/
+
+use crate::binary::{read_uint, Endian};
+use crate::{BlockWiseReader, Error};
+use std::io::Read;
+
+/// the types of boxes which only contain other boxes
+const CONTAINERS: &[&[u8; 4]] = &[
+ b"moov", b"trak", b"edts", b"mdia", b"minf", b"dinf", b"stbl", b"mvex", b"moof", b"traf", b"mfra",
+ b"udta", b"meta", b"ipro", b"sinf", b"schi", b"iprp", b"ipco", b"grpl", b"tref",
+];
+
+/// true if boxes of the type contain other boxes, like moov or trak
+pub fn is_container(box_type: &[u8; 4]) -> bool {
+ CONTAINERS.contains(&box_type)
+}
+
+/// A box with absolute offsets in the stream.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BoxHeader {
+ pub box_type: [u8; 4],
+ /// the extended type of `uuid` boxes
+ pub user_type: Option<[u8; 16]>,
+ /// absolute offset of the box
+ pub offset: u64,
+ /// size of the box including the header, None if the box extends to the end of file
+ pub size: Option<u64>,
+ /// absolute offset of the payload
+ pub payload_offset: u64,
+ /// the number of entered boxes around the box
+ pub depth: usize,
+}
+
+/// Reads boxes from the current pos of a BlockWiseReader on, consumed bytes are removed by pos_drop().
+/// The payload of the current box can be read by Read.
+pub struct BoxReader<'r, 'a> {
+ bwr: &'r mut BlockWiseReader<'a>,
+ buffersize: usize,
+ /// unread payload bytes of the current box, None up to end of file
+ remaining: Option<u64>,
+ /// the type of the current box
+ current: [u8; 4],
+ /// absolute ends of the entered boxes, None up to end of file
+ containers: Vec<Option<u64>>,
+}
+
+impl<'r, 'a> BoxReader<'r, 'a> {
+ /// creates a BoxReader which reads ahead in buffersize steps
+ pub fn new(bwr: &'r mut BlockWiseReader<'a>, buffersize: usize) -> Self {
+  Self {
+   bwr,
+   buffersize: buffersize.max(1),
+   remaining: Some(0),
+   current: [0; 4],
+   containers: vec![],
+  }
+ }
+
+ /// Returns the next box and sets pos to its payload, the rest of the previous box is skipped.
+ /// Returns None at end of file.
+ pub fn next_box(&mut self) -> Result<Option<BoxHeader>, Error> {
+  let count = self.remaining.unwrap_or(u64::MAX);
+  if self.bwr.pos_skip(count, self.buffersize)? < count && self.remaining.is_some() {
+   return Err(Error::Msg("unexpected end of file in box"));
+  }
+  self.remaining = Some(0);
+  let offset = self.bwr.pos_absolute();
+  while let Some(Some(end)) = self.containers.last() {
+   if offset < *end {
+    break;
+   }
+   if offset > *end {
+    return Err(Error::Msg("box exceeds its container"));
+   }
+   self.containers.pop();
+  }
+
+  let available = self.bwr.slurp_exact(8)?;
+  if 0 == available {
+   if self.containers.iter().any(Option::is_some) {
+    return Err(Error::Msg("unexpected end of file in box"));
+   }
+   self.containers.clear();
+   return Ok(None);
+  }
+  if available < 8 {
+   return Err(Error::Msg("unexpected end of file in box header"));
+  }
+  let data = self.bwr.get();
+  let mut size = read_uint(&data[..4], Endian::Big);
+  let box_type: [u8; 4] = data[4..8].try_into().unwrap();
+  let mut header_size = 8;
+  if 1 == size {
+   header_size = 16;
+   if self.bwr.slurp_exact(header_size)? < header_size {
+    return Err(Error::Msg("unexpected end of file in box header"));
+   }
+   size = read_uint(&self.bwr.get()[8..16], Endian::Big);
+  }
+  let mut user_type = None;
+  if b"uuid" == &box_type {
+   if self.bwr.slurp_exact(header_size + 16)? < header_size + 16 {
+    return Err(Error::Msg("unexpected end of file in box header"));
+   }
+   user_type = Some(
+    self.bwr.get()[header_size..header_size + 16]
+     .try_into()
+     .unwrap(),
+   );
+   header_size += 16;
+  }
+
+  let container_end = self.containers.last().copied().flatten();
+  let size = match (size, container_end) {
+   (0, None) => None,
+   (0, Some(end)) => Some(end - offset),
+   (size, _) if size < header_size as u64 || offset.checked_add(size).is_none() => {
+    return Err(Error::Msg("invalid box size"))
+   }
+   (size, _) => Some(size),
+  };
+  if let (Some(size), Some(end)) = (size, container_end) {
+   if size > end - offset {
+    return Err(Error::Msg("box exceeds its container"));
+   }
+  }
+  self.bwr.pos_add(header_size);
+  self.bwr.pos_drop();
+  self.remaining = size.map(|size| size - header_size as u64);
+  self.current = box_type;
+  Ok(Some(BoxHeader {
+   box_type,
+   user_type,
+   offset,
+   size,
+   payload_offset: offset + header_size as u64,
+   depth: self.containers.len(),
+  }))
+ }
+
+ /// Iterates over the unread payload of the current box as boxes, the children have a depth increased by one.
+ /// The version and flags of a `meta` box are skipped, unless it is a QuickTime `meta` box without them.
+ pub fn enter(&mut self) -> Result<(), Error> {
+  if b"meta" == &self.current {
+   let available = self.bwr.slurp_exact(8)?;
+   if available >= 8 && &self.bwr.get()[4..8] != b"hdlr" && self.remaining.is_none_or(|r| r >= 4) {
+    self.bwr.pos_add(4);
+    self.remaining = self.remaining.map(|r| r - 4);
+   }
+  }
+  let end = self.remaining.map(|r| self.bwr.pos_absolute() + r);
+  self.containers.push(end);
+  self.remaining = Some(0);
+  self.current = [0; 4];
+  Ok(())
+ }
+}
+
+impl Read for BoxReader<'_, '_> {
+ fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+  // a box which extends to the end of the file ends with the stream
+  let mut remaining = self.remaining.unwrap_or(u64::MAX);
+  let unexpected_eof = self.remaining.map(|_| "unexpected end of file in box");
+  let n = self
+   .bwr
+   .read_payload(self.buffersize, &mut remaining, buf, unexpected_eof)?;
+  self.remaining = self.remaining.map(|r| r - n as u64);
+  Ok(n)
+ }
+}
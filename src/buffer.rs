@@ -0,0 +1,87 @@
+use std::ops::Deref;
+
+/// the data a window is moved over, it is never copied as long as the window is not converted
+/// into an owned vector
+pub(crate) enum Backing<'a> {
+ Slice(&'a [u8]),
+ #[cfg(feature = "bytes")]
+ Bytes(bytes::Bytes),
+ #[cfg(feature = "mmap")]
+ Mmap(memmap2::Mmap),
+}
+
+impl AsRef<[u8]> for Backing<'_> {
+ fn as_ref(&self) -> &[u8] {
+  match *self {
+   Backing::Slice(s) => s,
+   #[cfg(feature = "bytes")]
+   Backing::Bytes(ref b) => b,
+   #[cfg(feature = "mmap")]
+   Backing::Mmap(ref m) => m,
+  }
+ }
+}
+
+/// The internal data of the BlockWiseReader.
+/// Either an owned vector which is filled by a reader or a visible window start..end over
+/// data which is already completely accessible.
+pub(crate) enum Buffer<'a> {
+ Owned(Vec<u8>),
+ Window {
+  data: Backing<'a>,
+  start: usize,
+  end: usize,
+ },
+}
+
+impl Buffer<'_> {
+ /// Moves the end of a window so that at least len bytes are visible, as far as there is data.
+ /// Returns None for an owned vector, otherwise if the end of the data is visible.
+ pub(crate) fn window_extend(&mut self, len: usize) -> Option<bool> {
+  match self {
+   Buffer::Owned(_) => None,
+   Buffer::Window { data, start, end } => {
+    let total = data.as_ref().len();
+    *end = total.min(start.saturating_add(len).max(*end));
+    Some(*end == total)
+   }
+  }
+ }
+
+ /// removes the first len visible bytes and returns them
+ pub(crate) fn cut(&mut self, len: usize) -> Vec<u8> {
+  match self {
+   Buffer::Owned(v) => {
+    let mut ret = v.split_off(len);
+    std::mem::swap(v, &mut ret);
+    ret
+   }
+   Buffer::Window { data, start, .. } => {
+    let ret = data.as_ref()[*start..*start + len].to_vec();
+    *start += len;
+    ret
+   }
+  }
+ }
+
+ /// removes the first len visible bytes
+ pub(crate) fn drop_front(&mut self, len: usize) {
+  match self {
+   Buffer::Owned(v) => {
+    v.drain(..len);
+   }
+   Buffer::Window { start, .. } => *start += len,
+  }
+ }
+}
+
+impl Deref for Buffer<'_> {
+ type Target = [u8];
+
+ fn deref(&self) -> &[u8] {
+  match self {
+   Buffer::Owned(v) => v,
+   Buffer::Window { data, start, end } => &data.as_ref()[*start..*end],
+  }
+ }
+}
@@ -0,0 +1,281 @@
+/*!
+Iterating over "tag + length + payload" chunks like in PNG, RIFF (WAV, AVI, WebP) or IFF files.
+
+The layout of the chunks is configured by a ChunkFormat, ready-made formats for PNG, RIFF and IFF
+are included. The payload of a chunk can be read by Read, the rest of it is skipped by the next call
+of next_chunk() without buffering it. Container chunks like RIFF or LIST can be entered, then their
+payload is iterated as chunks.
+
+```rust
+use std::io::Read;
+use blockwise_reader::BlockWiseReader;
+use blockwise_reader::chunk::{ChunkFormat, ChunkReader};
+
+let mut bwr = BlockWiseReader::from_slice(b"RIFF\x18\0\0\0WAVEfmt \x03\0\0\0abc\0data\0\0\0\0");
+let mut chunks = ChunkReader::new(&mut bwr, 1024, ChunkFormat::RIFF);
+
+let riff = chunks.next_chunk().unwrap().unwrap();
+assert_eq!(b"RIFF", &riff.tag[..]);
+let mut form_type = [0u8; 4];
+chunks.read_exact(&mut form_type).unwrap();
+assert_eq!(b"WAVE", &form_type);
+chunks.enter();
+
+let fmt = chunks.next_chunk().unwrap().unwrap();
+assert_eq!((&b"fmt "[..], 3, 1), (&fmt.tag[..], fmt.size, fmt.depth));
+let data = chunks.next_chunk().unwrap().unwrap();
+assert_eq!((&b"data"[..], 24), (&data.tag[..], data.offset));
+assert!(chunks.next_chunk().unwrap().is_none());
+```
+*/
+
+pub use crate::binary::Endian;
+use crate::binary::{crc32_update, read_uint};
+use crate::{BlockWiseReader, Error};
+use std::{cmp::min, io::Read};
+
+/// the signature in front of the chunks of a PNG file
+pub const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The layout of the chunks.
+/// A chunk consists of the header with tag and length, the payload, the optional CRC and the padding.
+#[derive(Clone, Copy, Debug)]
+pub struct ChunkFormat {
+ /// size of the tag in bytes
+ pub tag_size: usize,
+ /// size of the length field in bytes, 1 up to 8
+ pub length_size: usize,
+ pub endian: Endian,
+ /// the length field is in front of the tag
+ pub length_first: bool,
+ /// the length counts the header too
+ pub length_includes_header: bool,
+ /// the payload is padded to a multiple of alignment bytes
+ pub alignment: u64,
+ /// a CRC-32 over tag and payload follows the payload
+ pub crc: bool,
+ /// verify the CRC, then skipped payloads are read to compute it
+ pub verify_crc: bool,
+}
+
+impl ChunkFormat {
+ /// PNG chunks, the PNG_SIGNATURE has to be skipped before
+ pub const PNG: Self = Self {
+  tag_size: 4,
+  length_size: 4,
+  endian: Endian::Big,
+  length_first: true,
+  length_includes_header: false,
+  alignment: 1,
+  crc: true,
+  verify_crc: false,
+ };
+
+ /// RIFF chunks like in WAV, AVI or WebP files
+ pub const RIFF: Self = Self {
+  tag_size: 4,
+  length_size: 4,
+  endian: Endian::Little,
+  length_first: false,
+  length_includes_header: false,
+  alignment: 2,
+  crc: false,
+  verify_crc: false,
+ };
+
+ /// IFF chunks like in AIFF files
+ pub const IFF: Self = Self {
+  endian: Endian::Big,
+  ..Self::RIFF
+ };
+
+ fn header_size(&self) -> usize {
+  self.tag_size + self.length_size
+ }
+
+ fn trailer_size(&self, size: u64) -> u64 {
+  let padding = match size % self.alignment.max(1) {
+   0 => 0,
+   rest => self.alignment - rest,
+  };
+  padding + if self.crc { 4 } else { 0 }
+ }
+}
+
+/// A chunk with absolute offsets in the stream.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ChunkHeader {
+ pub tag: Vec<u8>,
+ /// size of the payload
+ pub size: u64,
+ /// absolute offset of the chunk header
+ pub offset: u64,
+ /// absolute offset of the payload
+ pub payload_offset: u64,
+ /// the number of entered containers around the chunk
+ pub depth: usize,
+}
+
+/// Reads chunks from the current pos of a BlockWiseReader on, consumed bytes are removed by pos_drop().
+/// The payload of the current chunk can be read by Read.
+pub struct ChunkReader<'r, 'a> {
+ bwr: &'r mut BlockWiseReader<'a>,
+ buffersize: usize,
+ format: ChunkFormat,
+ /// unread payload bytes of the current chunk
+ remaining: u64,
+ /// padding and CRC behind the payload of the current chunk
+ trailer: u64,
+ /// the CRC of tag and payload up to now, if it is verified
+ crc: Option<u32>,
+ /// absolute payload end and trailer size of the entered containers
+ containers: Vec<(u64, u64)>,
+}
+
+impl<'r, 'a> ChunkReader<'r, 'a> {
+ /// creates a ChunkReader which reads ahead in buffersize steps
+ pub fn new(bwr: &'r mut BlockWiseReader<'a>, buffersize: usize, format: ChunkFormat) -> Self {
+  Self {
+   bwr,
+   buffersize: buffersize.max(1),
+   format,
+   remaining: 0,
+   trailer: 0,
+   crc: None,
+   containers: vec![],
+  }
+ }
+
+ /// Returns the next chunk and sets pos to its payload.
+ /// The rest of the previous chunk is skipped and its CRC is verified if configured.
+ /// Returns None at end of file, then pos is behind the last chunk.
+ pub fn next_chunk(&mut self) -> Result<Option<ChunkHeader>, Error> {
+  self.finish_chunk()?;
+  while let Some((end, trailer)) = self.containers.last().copied() {
+   let pos = self.bwr.pos_absolute();
+   if pos < end {
+    break;
+   }
+   if pos > end {
+    return Err(Error::Msg("chunk exceeds its container"));
+   }
+   self.skip(trailer)?;
+   self.containers.pop();
+  }
+
+  if !(1..=8).contains(&self.format.length_size) {
+   return Err(Error::Msg("invalid chunk length size"));
+  }
+  let header_size = self.format.header_size();
+  let available = self.bwr.slurp_exact(header_size)?;
+  if 0 == available && self.containers.is_empty() {
+   return Ok(None);
+  }
+  if available < header_size {
+   return Err(Error::Msg("unexpected end of file in chunk header"));
+  }
+  let header = &self.bwr.get()[..header_size];
+  let (length, tag) = if self.format.length_first {
+   header.split_at(self.format.length_size)
+  } else {
+   let (tag, length) = header.split_at(self.format.tag_size);
+   (length, tag)
+  };
+  let mut size = read_uint(length, self.format.endian);
+  if self.format.length_includes_header {
+   size = size
+    .checked_sub(header_size as u64)
+    .ok_or(Error::Msg("invalid chunk length"))?;
+  }
+  let offset = self.bwr.pos_absolute();
+  let chunk = ChunkHeader {
+   tag: tag.to_vec(),
+   size,
+   offset,
+   payload_offset: offset + header_size as u64,
+   depth: self.containers.len(),
+  };
+  let chunk_end = chunk
+   .payload_offset
+   .checked_add(size)
+   .and_then(|end| end.checked_add(self.format.trailer_size(size)))
+   .ok_or(Error::Msg("invalid chunk length"))?;
+  if let Some((end, _)) = self.containers.last() {
+   if chunk_end > *end {
+    return Err(Error::Msg("chunk exceeds its container"));
+   }
+  }
+  self.crc = (self.format.crc && self.format.verify_crc).then(|| crc32_update(0, tag));
+  self.bwr.pos_add(header_size);
+  self.bwr.pos_drop();
+  self.remaining = size;
+  self.trailer = self.format.trailer_size(size);
+  Ok(Some(chunk))
+ }
+
+ /// Iterates over the unread payload of the current chunk as chunks, like for RIFF or LIST chunks.
+ /// The chunks have a depth increased by one, after the payload the iteration continues behind the container.
+ /// The CRC of a container is not verified.
+ pub fn enter(&mut self) {
+  let end = self.bwr.pos_absolute() + self.remaining;
+  self.containers.push((end, self.trailer));
+  self.remaining = 0;
+  self.trailer = 0;
+  self.crc = None;
+ }
+
+ /// skips the rest of the current chunk and verifies its CRC
+ fn finish_chunk(&mut self) -> Result<(), Error> {
+  if self.crc.is_some() {
+   // the unread payload is read for the CRC
+   while self.remaining > 0 {
+    let want = min(self.remaining, self.buffersize as u64) as usize;
+    let n = min(self.bwr.slurp(want)?, want);
+    if 0 == n {
+     return Err(Error::Msg("unexpected end of file in chunk"));
+    }
+    let data = &self.bwr.get()[..n];
+    self.crc = self.crc.map(|crc| crc32_update(crc, data));
+    self.bwr.pos_add(n);
+    self.bwr.pos_drop();
+    self.remaining -= n as u64;
+   }
+   if self.bwr.slurp_exact(4)? < 4 {
+    return Err(Error::Msg("unexpected end of file in chunk"));
+   }
+   let stored = read_uint(&self.bwr.get()[..4], Endian::Big) as u32;
+   if self.crc.take() != Some(stored) {
+    return Err(Error::Msg("chunk crc mismatch"));
+   }
+   self.bwr.pos_add(4);
+   self.trailer -= 4;
+  }
+  let count = self.remaining + self.trailer;
+  self.skip(count)?;
+  self.remaining = 0;
+  self.trailer = 0;
+  Ok(())
+ }
+
+ fn skip(&mut self, count: u64) -> Result<(), Error> {
+  if self.bwr.pos_skip(count, self.buffersize)? < count {
+   return Err(Error::Msg("unexpected end of file in chunk"));
+  }
+  Ok(())
+ }
+}
+
+impl Read for ChunkReader<'_, '_> {
+ fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+  let n = self.bwr.read_payload(
+   self.buffersize,
+   &mut self.remaining,
+   buf,
+   Some("unexpected end of file in chunk"),
+  )?;
+  if let Some(crc) = self.crc {
+   self.crc = Some(crc32_update(crc, &buf[..n]));
+  }
+  Ok(n)
+ }
+}
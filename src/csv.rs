@@ -0,0 +1,167 @@
+/*!
+Splitting of CSV data into records without parsing the fields.
+
+Line breaks, delimiters and doubled quotes inside quoted fields are respected, so a record can
+span several lines. The records are reported as byte ranges with absolute offsets in the stream.
+
+```rust
+use blockwise_reader::BlockWiseReader;
+use blockwise_reader::csv::{CsvConfig, CsvReader};
+
+let mut bwr = BlockWiseReader::from_slice(b"id,text\r\n1,\"multi\nline, \"\"quoted\"\"\"\r\n2,x");
+let config = CsvConfig {
+ fields: true,
+ ..CsvConfig::default()
+};
+let mut csv = CsvReader::new(&mut bwr, 1024, config);
+
+assert_eq!(0..7, csv.next_record().unwrap().unwrap().range);
+let record = csv.next_record().unwrap().unwrap();
+assert_eq!(9..35, record.range);
+assert_eq!(vec![9..10, 11..35], record.fields);
+assert_eq!(b"\"multi\nline, \"\"quoted\"\"\"", &csv.record()[2..]);
+assert_eq!(37..40, csv.next_record().unwrap().unwrap().range);
+assert!(csv.next_record().unwrap().is_none());
+```
+*/
+
+use crate::{BlockWiseReader, Error};
+use std::ops::Range;
+
+/// the syntax of the CSV data and limits
+#[derive(Clone, Copy, Debug)]
+pub struct CsvConfig {
+ /// separates the fields of a record
+ pub delimiter: u8,
+ /// encloses fields which contain delimiters, line breaks or doubled quotes
+ pub quote: u8,
+ /// collect the ranges of the fields of every record
+ pub fields: bool,
+ /// maximal size of a record including its line break
+ pub max_record_size: usize,
+}
+
+impl Default for CsvConfig {
+ fn default() -> Self {
+  Self {
+   delimiter: b',',
+   quote: b'"',
+   fields: false,
+   max_record_size: 1024 * 1024,
+  }
+ }
+}
+
+/// A record as absolute byte ranges in the stream.
+/// The range excludes the line break, the fields are only collected if CsvConfig::fields is set.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CsvRecord {
+ pub range: Range<u64>,
+ pub fields: Vec<Range<u64>>,
+}
+
+/// Reads CSV records from the current pos of a BlockWiseReader on.
+/// The bytes of a record are removed from the BlockWiseReader by pos_drop() when the next record is read,
+/// so the memory stays bounded by the max_record_size.
+pub struct CsvReader<'r, 'a> {
+ bwr: &'r mut BlockWiseReader<'a>,
+ buffersize: usize,
+ config: CsvConfig,
+ /// the last record as distance of its begin to pos and its length
+ last: (usize, usize),
+}
+
+impl<'r, 'a> CsvReader<'r, 'a> {
+ /// creates a CsvReader which reads ahead in buffersize steps
+ pub fn new(bwr: &'r mut BlockWiseReader<'a>, buffersize: usize, config: CsvConfig) -> Self {
+  Self {
+   bwr,
+   buffersize: buffersize.max(1),
+   config,
+   last: (0, 0),
+  }
+ }
+
+ /// the raw bytes of the record which was read last, excluding the line break
+ pub fn record(&self) -> &[u8] {
+  &self.bwr.get_back(self.last.0)[..self.last.1]
+ }
+
+ /// Reads the next record and sets pos behind its line break.
+ /// Returns None at end of file.
+ pub fn next_record(&mut self) -> Result<Option<CsvRecord>, Error> {
+  self.bwr.pos_drop();
+  self.last = (0, 0);
+  let base = self.bwr.pos_absolute();
+  let CsvConfig {
+   delimiter,
+   quote,
+   fields,
+   max_record_size,
+  } = self.config;
+
+  let mut field_ends = vec![];
+  let mut in_quotes = false;
+  let mut idx = 0;
+  let (end, consumed) = loop {
+   let data = self.bwr.get();
+   let scan_end = data.len().min(max_record_size);
+   while idx < scan_end {
+    match data[idx] {
+     b if b == quote => in_quotes = !in_quotes,
+     b'\n' if !in_quotes => break,
+     b if b == delimiter && !in_quotes && fields => field_ends.push(idx),
+     _ => {}
+    }
+    idx += 1;
+   }
+   if idx < scan_end {
+    let end = if idx > 0 && data[idx - 1] == b'\r' { idx - 1 } else { idx };
+    break (end, idx + 1);
+   }
+   if idx >= max_record_size {
+    return Err(Error::Msg("csv record exceeds max_record_size"));
+   }
+   let available = self.bwr.available_bytes();
+   if self.bwr.slurp(available + self.buffersize)? == available && self.bwr.eof {
+    if 0 == idx {
+     return Ok(None);
+    }
+    if in_quotes {
+     return Err(Error::Msg("unterminated quote in csv record"));
+    }
+    let end = if idx > 0 && self.bwr.get()[idx - 1] == b'\r' { idx - 1 } else { idx };
+    break (end, idx);
+   }
+  };
+
+  self.bwr.pos_add(consumed);
+  self.last = (consumed, end);
+  let field_ranges = if fields {
+   let mut start = 0;
+   field_ends.push(end);
+   field_ends
+    .into_iter()
+    .map(|field_end| {
+     let range = base + start as u64..base + field_end as u64;
+     start = field_end + 1;
+     range
+    })
+    .collect()
+  } else {
+   vec![]
+  };
+  Ok(Some(CsvRecord {
+   range: base..base + end as u64,
+   fields: field_ranges,
+  }))
+ }
+}
+
+impl Iterator for CsvReader<'_, '_> {
+ type Item = Result<CsvRecord, Error>;
+
+ fn next(&mut self) -> Option<Self::Item> {
+  self.next_record().transpose()
+ }
+}
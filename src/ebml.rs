@@ -0,0 +1,334 @@
+/*!
+Reading of EBML data like Matroska or WebM files.
+
+The element IDs and sizes are variable length integers, so the header of an element is read step by
+step. The element tree is walked in pre-order up to a configurable depth, master elements are
+entered automatically and the data of the other elements is skipped without buffering it, unless it
+is read as a typed value.
+
+Elements of unknown size, like the Segment or Cluster of a live stream, end at the end of their
+parent, at end of file or in front of a master element of the same or a higher level.
+
+```rust
+use blockwise_reader::BlockWiseReader;
+use blockwise_reader::ebml::{EbmlConfig, EbmlReader, INFO, SEGMENT, TIMESTAMP_SCALE, TITLE};
+
+let mut bwr = BlockWiseReader::from_slice(
+ b"\x18\x53\x80\x67\x01\xff\xff\xff\xff\xff\xff\xff\x15\x49\xa9\x66\x8b\x2a\xd7\xb1\x83\x0f\x42\x40\x7b\xa9\x81x",
+);
+let mut ebml = EbmlReader::new(&mut bwr, 1024, EbmlConfig::default());
+assert_eq!(SEGMENT, ebml.next_element().unwrap().unwrap().id);
+assert_eq!(INFO, ebml.next_element().unwrap().unwrap().id);
+let element = ebml.next_element().unwrap().unwrap();
+assert_eq!((TIMESTAMP_SCALE, 2), (element.id, element.depth));
+assert_eq!(1_000_000, ebml.read_uint().unwrap());
+assert_eq!(TITLE, ebml.next_element().unwrap().unwrap().id);
+assert_eq!("x", ebml.read_string().unwrap());
+assert!(ebml.next_element().unwrap().is_none());
+```
+*/
+
+use crate::{BlockWiseReader, Error};
+
+pub const EBML: u32 = 0x1a45_dfa3;
+pub const DOC_TYPE: u32 = 0x4282;
+pub const VOID: u32 = 0xec;
+pub const SEGMENT: u32 = 0x1853_8067;
+pub const SEEK_HEAD: u32 = 0x114d_9b74;
+pub const INFO: u32 = 0x1549_a966;
+pub const TIMESTAMP_SCALE: u32 = 0x2a_d7b1;
+pub const DURATION: u32 = 0x4489;
+pub const DATE_UTC: u32 = 0x4461;
+pub const TITLE: u32 = 0x7ba9;
+pub const MUXING_APP: u32 = 0x4d80;
+pub const WRITING_APP: u32 = 0x5741;
+pub const TRACKS: u32 = 0x1654_ae6b;
+pub const TRACK_ENTRY: u32 = 0xae;
+pub const TRACK_NUMBER: u32 = 0xd7;
+pub const TRACK_TYPE: u32 = 0x83;
+pub const CODEC_ID: u32 = 0x86;
+pub const VIDEO: u32 = 0xe0;
+pub const AUDIO: u32 = 0xe1;
+pub const CLUSTER: u32 = 0x1f43_b675;
+pub const CUES: u32 = 0x1c53_bb6b;
+pub const TAGS: u32 = 0x1254_c367;
+
+/// the master elements of WebM and Matroska with their level
+pub const WEBM_MASTERS: &[(u32, usize)] = &[
+ (EBML, 0),
+ (SEGMENT, 0),
+ (SEEK_HEAD, 1),
+ (0x4dbb, 2),
+ (INFO, 1),
+ (0x6924, 2),
+ (TRACKS, 1),
+ (TRACK_ENTRY, 2),
+ (VIDEO, 3),
+ (0x55b0, 4),
+ (AUDIO, 3),
+ (0x6d80, 3),
+ (0x6240, 4),
+ (0x5035, 5),
+ (CLUSTER, 1),
+ (0xa0, 2),
+ (0x75a1, 3),
+ (0xa6, 4),
+ (CUES, 1),
+ (0xbb, 2),
+ (0xb7, 3),
+ (0x1941_a469, 1),
+ (0x61a7, 2),
+ (0x1043_a770, 1),
+ (0x45b9, 2),
+ (0xb6, 3),
+ (0x80, 4),
+ (TAGS, 1),
+ (0x7373, 2),
+ (0x63c0, 3),
+ (0x67c8, 3),
+];
+
+/// seconds from 1970-01-01 to 2001-01-01, the epoch of EBML dates
+pub const EBML_EPOCH_UNIX_SECONDS: i64 = 978_307_200;
+
+/// the schema and limits
+#[derive(Clone, Copy, Debug)]
+pub struct EbmlConfig<'m> {
+ /// the IDs and levels of the master elements which contain other elements
+ pub masters: &'m [(u32, usize)],
+ /// elements deeper than max_depth are skipped, 0 returns only the top level elements
+ pub max_depth: usize,
+ /// maximal size of a value read by the typed read methods
+ pub max_value_size: usize,
+}
+
+impl Default for EbmlConfig<'_> {
+ fn default() -> Self {
+  Self {
+   masters: WEBM_MASTERS,
+   max_depth: 8,
+   max_value_size: 1024 * 1024,
+  }
+ }
+}
+
+/// An element with absolute offsets in the stream.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Element {
+ /// the ID including its length marker bits, like it is written in specifications
+ pub id: u32,
+ /// absolute offset of the element
+ pub offset: u64,
+ /// size of the data, None for unknown size
+ pub size: Option<u64>,
+ /// absolute offset of the data
+ pub data_offset: u64,
+ /// the number of master elements around the element
+ pub depth: usize,
+}
+
+struct Container {
+ id: u32,
+ level: usize,
+ /// absolute end, None for unknown size
+ end: Option<u64>,
+}
+
+/// Walks over the elements from the current pos of a BlockWiseReader on, consumed bytes are removed by pos_drop().
+pub struct EbmlReader<'r, 'a, 'm> {
+ bwr: &'r mut BlockWiseReader<'a>,
+ buffersize: usize,
+ config: EbmlConfig<'m>,
+ /// unread data bytes of the current element
+ remaining: u64,
+ containers: Vec<Container>,
+}
+
+impl<'r, 'a, 'm> EbmlReader<'r, 'a, 'm> {
+ /// creates an EbmlReader which reads ahead in buffersize steps
+ pub fn new(bwr: &'r mut BlockWiseReader<'a>, buffersize: usize, config: EbmlConfig<'m>) -> Self {
+  Self {
+   bwr,
+   buffersize: buffersize.max(1),
+   config,
+   remaining: 0,
+   containers: vec![],
+  }
+ }
+
+ /// Returns the next element up to max_depth in pre-order and sets pos to its data.
+ /// The unread data of the previous element is skipped, master elements are entered.
+ /// Returns None at end of file.
+ pub fn next_element(&mut self) -> Result<Option<Element>, Error> {
+  loop {
+   let count = self.remaining;
+   if self.bwr.pos_skip(count, self.buffersize)? < count {
+    return Err(Error::Msg("unexpected end of file in ebml element"));
+   }
+   self.remaining = 0;
+   let offset = self.bwr.pos_absolute();
+   while let Some(Container { end: Some(end), .. }) = self.containers.last() {
+    if offset < *end {
+     break;
+    }
+    if offset > *end {
+     return Err(Error::Msg("ebml element exceeds its parent"));
+    }
+    self.containers.pop();
+   }
+
+   if 0 == self.bwr.slurp_exact(1)? {
+    if self.containers.iter().any(|c| c.end.is_some()) {
+     return Err(Error::Msg("unexpected end of file in ebml element"));
+    }
+    self.containers.clear();
+    return Ok(None);
+   }
+   let (id_len, id) = self.vint(0, 4, "invalid ebml element id")?;
+   let id = (id | 1 << (7 * id_len)) as u32;
+   let (size_len, size) = self.vint(id_len, 8, "invalid ebml element size")?;
+   let size = (size != (1 << (7 * size_len)) - 1).then_some(size);
+   let header_len = id_len + size_len;
+
+   let master = self
+    .config
+    .masters
+    .iter()
+    .find(|(m, _)| *m == id)
+    .map(|(_, level)| *level);
+   if let Some(level) = master {
+    while let Some(Container {
+     end: None,
+     level: l,
+     ..
+    }) = self.containers.last()
+    {
+     if level > *l {
+      break;
+     }
+     self.containers.pop();
+    }
+   }
+   let depth = self.containers.len();
+   let data_offset = offset + header_len as u64;
+   if let (Some(size), Some(end)) = (size, self.containers.last().and_then(|c| c.end)) {
+    if data_offset + size > end {
+     return Err(Error::Msg("ebml element exceeds its parent"));
+    }
+   }
+   self.bwr.pos_add(header_len);
+   self.bwr.pos_drop();
+
+   match (master, size) {
+    (Some(level), size) if depth < self.config.max_depth || size.is_none() => {
+     self.containers.push(Container {
+      id,
+      level,
+      end: size.map(|size| data_offset + size),
+     })
+    }
+    (_, Some(size)) => self.remaining = size,
+    (_, None) => return Err(Error::Msg("unknown size of a non master ebml element")),
+   }
+   if depth <= self.config.max_depth {
+    return Ok(Some(Element {
+     id,
+     offset,
+     size,
+     data_offset,
+     depth,
+    }));
+   }
+  }
+ }
+
+ /// the IDs of the entered master elements, from the outside to the inside
+ pub fn path(&self) -> Vec<u32> {
+  self.containers.iter().map(|c| c.id).collect()
+ }
+
+ /// Decodes a variable length integer at pos + at with up to max_len bytes.
+ /// Returns the length and the value without the length marker.
+ fn vint(
+  &mut self,
+  at: usize,
+  max_len: usize,
+  invalid: &'static str,
+ ) -> Result<(usize, u64), Error> {
+  if self.bwr.slurp_exact(at + 1)? < at + 1 {
+   return Err(Error::Msg("unexpected end of file in ebml element header"));
+  }
+  let first = self.bwr.get()[at];
+  let len = first.leading_zeros() as usize + 1;
+  if len > max_len {
+   return Err(Error::Msg(invalid));
+  }
+  if self.bwr.slurp_exact(at + len)? < at + len {
+   return Err(Error::Msg("unexpected end of file in ebml element header"));
+  }
+  let value = self.bwr.get()[at + 1..at + len]
+   .iter()
+   .fold((first as u64) & (0xff >> len), |acc, b| acc << 8 | *b as u64);
+  Ok((len, value))
+ }
+
+ /// the data of the current element which is not a master element, it is consumed
+ pub fn read_binary(&mut self) -> Result<&[u8], Error> {
+  if self.remaining > self.config.max_value_size as u64 {
+   return Err(Error::Msg("ebml value exceeds max_value_size"));
+  }
+  let size = self.remaining as usize;
+  if self.bwr.slurp_exact(size)? < size {
+   return Err(Error::Msg("unexpected end of file in ebml element"));
+  }
+  self.bwr.pos_add(size);
+  self.remaining = 0;
+  Ok(&self.bwr.get_back(size)[..size])
+ }
+
+ /// the data of the current element as unsigned integer
+ pub fn read_uint(&mut self) -> Result<u64, Error> {
+  let data = self.read_binary()?;
+  if data.len() > 8 {
+   return Err(Error::Msg("invalid ebml integer"));
+  }
+  Ok(data.iter().fold(0, |acc, b| acc << 8 | *b as u64))
+ }
+
+ /// the data of the current element as signed integer
+ pub fn read_int(&mut self) -> Result<i64, Error> {
+  let data = self.read_binary()?;
+  if data.len() > 8 {
+   return Err(Error::Msg("invalid ebml integer"));
+  }
+  let negative = data.first().is_some_and(|b| b & 0x80 != 0);
+  let init = if negative { -1 } else { 0 };
+  Ok(data.iter().fold(init, |acc, b| acc << 8 | *b as i64))
+ }
+
+ /// the data of the current element as float of 0, 4 or 8 bytes
+ pub fn read_float(&mut self) -> Result<f64, Error> {
+  let data = self.read_binary()?;
+  match data.len() {
+   0 => Ok(0.0),
+   4 => Ok(f32::from_be_bytes(data.try_into().unwrap()) as f64),
+   8 => Ok(f64::from_be_bytes(data.try_into().unwrap())),
+   _ => Err(Error::Msg("invalid ebml float")),
+  }
+ }
+
+ /// the data of the current element as string, trailing zero bytes are removed
+ pub fn read_string(&mut self) -> Result<String, Error> {
+  let data = self.read_binary()?;
+  let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+  String::from_utf8(data[..end].to_vec()).map_err(|_| Error::Msg("invalid ebml string"))
+ }
+
+ /// the data of the current element as date in nanoseconds since 2001-01-01, see EBML_EPOCH_UNIX_SECONDS
+ pub fn read_date(&mut self) -> Result<i64, Error> {
+  if !matches!(self.remaining, 0 | 8) {
+   return Err(Error::Msg("invalid ebml date"));
+  }
+  self.read_int()
+ }
+}
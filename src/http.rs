@@ -0,0 +1,475 @@
+/*!
+Parsing of HTTP/1.x request and response heads.
+
+The head is read blockwise up to the empty line which ends it, the parsed names and values borrow
+from the data of the BlockWiseReader. Afterwards pos is set to the first byte of the body.
+A body in chunked transfer coding can then be read by the ChunkedReader.
+
+```rust
+use stringreader::StringReader;
+use blockwise_reader::BlockWiseReader;
+use blockwise_reader::http::{read_request, HeadLimits, Version};
+
+let sr = StringReader::new("GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\nbody");
+let mut bwr = BlockWiseReader::new(Box::new(sr));
+
+let request = read_request(&mut bwr, 1024, &HeadLimits::default()).unwrap();
+assert_eq!(b"GET", request.method);
+assert_eq!(b"/index.html", request.target);
+assert_eq!(Version::Http11, request.version);
+assert_eq!(Some("example.com".as_bytes()), request.header(b"host"));
+
+bwr.slurp(4).unwrap();
+assert_eq!(b"body", bwr.get());
+```
+*/
+
+use crate::{BlockWiseReader, Error};
+use memmem::{Searcher, TwoWaySearcher};
+use std::{
+ borrow::Cow,
+ cmp::{max, min},
+ io::Read,
+};
+
+/// limits which are checked while reading a head
+#[derive(Clone, Copy, Debug)]
+pub struct HeadLimits {
+ /// maximal size of the head including the empty line at its end
+ pub max_head_size: usize,
+ /// maximal number of header fields
+ pub max_headers: usize,
+}
+
+impl Default for HeadLimits {
+ fn default() -> Self {
+  Self {
+   max_head_size: 64 * 1024,
+   max_headers: 100,
+  }
+ }
+}
+
+/// the HTTP version of a request or status line
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Version {
+ Http10,
+ Http11,
+}
+
+/// A header field.
+/// The value is trimmed and may contain obsolete line folding, see unfolded().
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Header<'h> {
+ pub name: &'h [u8],
+ pub value: &'h [u8],
+}
+
+impl<'h> Header<'h> {
+ /// the value with every obsolete line folding replaced by a single space
+ pub fn unfolded(&self) -> Cow<'h, [u8]> {
+  if !self.value.contains(&b'\n') {
+   return Cow::Borrowed(self.value);
+  }
+  let mut ret = Vec::with_capacity(self.value.len());
+  let mut folding = false;
+  for &b in self.value {
+   match b {
+    b'\r' | b'\n' => folding = true,
+    b' ' | b'\t' if folding => {}
+    _ => {
+     if folding {
+      ret.push(b' ');
+      folding = false;
+     }
+     ret.push(b);
+    }
+   }
+  }
+  Cow::Owned(ret)
+ }
+}
+
+/// a parsed request head
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Request<'h> {
+ pub method: &'h [u8],
+ pub target: &'h [u8],
+ pub version: Version,
+ pub headers: Vec<Header<'h>>,
+}
+
+impl<'h> Request<'h> {
+ /// the value of the first header field with the given name, compared case insensitive
+ pub fn header(&self, name: &[u8]) -> Option<&'h [u8]> {
+  find_header(&self.headers, name)
+ }
+}
+
+/// a parsed response head
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Response<'h> {
+ pub version: Version,
+ pub status: u16,
+ pub reason: &'h [u8],
+ pub headers: Vec<Header<'h>>,
+}
+
+impl<'h> Response<'h> {
+ /// the value of the first header field with the given name, compared case insensitive
+ pub fn header(&self, name: &[u8]) -> Option<&'h [u8]> {
+  find_header(&self.headers, name)
+ }
+}
+
+/// Reads a request head from pos on, reading ahead in buffersize steps.
+/// Empty lines before the request line are skipped.
+/// On success pos is set to the begin of the body, otherwise pos remains unaltered.
+pub fn read_request<'h>(
+ bwr: &'h mut BlockWiseReader<'_>,
+ buffersize: usize,
+ limits: &HeadLimits,
+) -> Result<Request<'h>, Error> {
+ let (start_line, headers) =
+  read_head(bwr, buffersize, limits, |line| parse_request_line(line).map(|_| ()))?;
+ let (method, target, version) = parse_request_line(start_line)?;
+ Ok(Request {
+  method,
+  target,
+  version,
+  headers,
+ })
+}
+
+/// Reads a response head from pos on, reading ahead in buffersize steps.
+/// On success pos is set to the begin of the body, otherwise pos remains unaltered.
+pub fn read_response<'h>(
+ bwr: &'h mut BlockWiseReader<'_>,
+ buffersize: usize,
+ limits: &HeadLimits,
+) -> Result<Response<'h>, Error> {
+ let (start_line, headers) =
+  read_head(bwr, buffersize, limits, |line| parse_status_line(line).map(|_| ()))?;
+ let (version, status, reason) = parse_status_line(start_line)?;
+ Ok(Response {
+  version,
+  status,
+  reason,
+  headers,
+ })
+}
+
+fn parse_request_line(line: &[u8]) -> Result<(&[u8], &[u8], Version), Error> {
+ let mut parts = line.splitn(3, |b| *b == b' ');
+ let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next())
+ else {
+  return Err(Error::Msg("invalid http request line"));
+ };
+ if method.is_empty() || !method.iter().all(|b| is_tchar(*b)) {
+  return Err(Error::Msg("invalid http method"));
+ }
+ if target.is_empty() || target.iter().any(|b| b.is_ascii_control()) {
+  return Err(Error::Msg("invalid http request target"));
+ }
+ Ok((method, target, parse_version(version)?))
+}
+
+fn parse_status_line(line: &[u8]) -> Result<(Version, u16, &[u8]), Error> {
+ let mut parts = line.splitn(3, |b| *b == b' ');
+ let (Some(version), Some(status)) = (parts.next(), parts.next()) else {
+  return Err(Error::Msg("invalid http status line"));
+ };
+ if status.len() != 3 || !status.iter().all(u8::is_ascii_digit) {
+  return Err(Error::Msg("invalid http status code"));
+ }
+ let status = status
+  .iter()
+  .fold(0, |acc, b| acc * 10 + u16::from(b - b'0'));
+ Ok((parse_version(version)?, status, parts.next().unwrap_or_default()))
+}
+
+/// Decodes a body in chunked transfer coding from the current pos of a BlockWiseReader on.
+/// The payload is available by Read or chunk by chunk by read_chunk().
+/// Consumed bytes are removed from the BlockWiseReader by pos_drop(), so the memory stays bounded,
+/// this also removes everything before the current pos when the first chunk is read.
+/// After the last chunk pos is set behind the trailer section.
+pub struct ChunkedReader<'r, 'a> {
+ bwr: &'r mut BlockWiseReader<'a>,
+ buffersize: usize,
+ max_chunk_size: u64,
+ limits: HeadLimits,
+ remaining: u64,
+ state: ChunkedState,
+ trailer_block: Vec<u8>,
+ trailer_fields: Vec<(usize, usize, usize)>,
+}
+
+enum ChunkedState {
+ Start,
+ Data,
+ Done,
+}
+
+impl<'r, 'a> ChunkedReader<'r, 'a> {
+ /// Creates a ChunkedReader which reads ahead in buffersize steps and accepts chunks up to max_chunk_size bytes.
+ /// The limits apply to the chunk size lines and to the trailer section.
+ pub fn new(
+  bwr: &'r mut BlockWiseReader<'a>,
+  buffersize: usize,
+  max_chunk_size: u64,
+  limits: HeadLimits,
+ ) -> Self {
+  Self {
+   bwr,
+   buffersize: max(buffersize, 1),
+   max_chunk_size,
+   limits,
+   remaining: 0,
+   state: ChunkedState::Start,
+   trailer_block: vec![],
+   trailer_fields: vec![],
+  }
+ }
+
+ /// returns true if the last chunk and the trailer section are read
+ pub fn is_done(&self) -> bool {
+  matches!(self.state, ChunkedState::Done)
+ }
+
+ /// the header fields of the trailer section, empty as long as is_done() is false
+ pub fn trailers(&self) -> Vec<Header<'_>> {
+  to_headers(&self.trailer_block, &self.trailer_fields)
+ }
+
+ /// Reads the rest of the current chunk or otherwise the next chunk completely and returns its data.
+ /// Returns None after the last chunk.
+ pub fn read_chunk(&mut self) -> Result<Option<&[u8]>, Error> {
+  while 0 == self.remaining {
+   if self.is_done() {
+    return Ok(None);
+   }
+   self.next_chunk()?;
+  }
+  self.bwr.pos_drop();
+  let len = self.remaining as usize;
+  if self.bwr.slurp_exact(len)? < len {
+   return Err(Error::Msg("unexpected end of file in chunked body"));
+  }
+  self.bwr.pos_add(len);
+  self.remaining = 0;
+  Ok(Some(&self.bwr.get_back(len)[..len]))
+ }
+
+ /// reads the CRLF behind the previous chunk data, the chunk size line and for the last chunk the trailer section
+ fn next_chunk(&mut self) -> Result<(), Error> {
+  if let ChunkedState::Data = self.state {
+   if self.bwr.slurp_exact(2)? < 2 || &self.bwr.get()[..2] != b"\r\n" {
+    return Err(Error::Msg("missing CRLF behind chunk data"));
+   }
+   self.bwr.pos_add(2);
+  }
+  self.bwr.pos_drop();
+  let Some(len) = self.bwr.slurp_search_limited(
+   self.buffersize,
+   0,
+   b"\r\n",
+   self.limits.max_head_size,
+   "chunk size line exceeds max_head_size",
+  )?
+  else {
+   return Err(Error::Msg("unexpected end of file in chunked body"));
+  };
+  let line = &self.bwr.get()[..len - 2];
+  let size_end = line.iter().position(|b| *b == b';').unwrap_or(line.len());
+  let Some(size) = parse_chunk_size(line[..size_end].trim_ascii_end()) else {
+   return Err(Error::Msg("invalid chunk size"));
+  };
+  if size > self.max_chunk_size {
+   return Err(Error::Msg("chunk exceeds max_chunk_size"));
+  }
+  self.bwr.pos_add(len);
+  if size > 0 {
+   self.state = ChunkedState::Data;
+   self.remaining = size;
+   return Ok(());
+  }
+
+  if self.bwr.slurp_exact(2)? >= 2 && &self.bwr.get()[..2] == b"\r\n" {
+   self.bwr.pos_add(2);
+  } else {
+   let Some(len) = self.bwr.slurp_search_limited(
+    self.buffersize,
+    0,
+    b"\r\n\r\n",
+    self.limits.max_head_size,
+    "trailer section exceeds max_head_size",
+   )?
+   else {
+    return Err(Error::Msg("unexpected end of file in trailer section"));
+   };
+   let block = &self.bwr.get()[..len - 2];
+   let lines = split_lines(block)?;
+   self.trailer_fields =
+    parse_fields(block, &lines, self.limits.max_headers, "trailer section exceeds max_headers")?;
+   self.trailer_block = block.to_vec();
+   self.bwr.pos_add(len);
+  }
+  self.state = ChunkedState::Done;
+  Ok(())
+ }
+}
+
+impl Read for ChunkedReader<'_, '_> {
+ fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+  if buf.is_empty() {
+   return Ok(0);
+  }
+  while 0 == self.remaining {
+   if self.is_done() {
+    return Ok(0);
+   }
+   self.next_chunk()?;
+  }
+  self.bwr.read_payload(
+   self.buffersize,
+   &mut self.remaining,
+   buf,
+   Some("unexpected end of file in chunked body"),
+  )
+ }
+}
+
+/// Searches the end of the head and splits it into the start line and the header fields.
+/// Sets pos behind the head if the header fields and check_start_line succeed.
+fn read_head<'h>(
+ bwr: &'h mut BlockWiseReader<'_>,
+ buffersize: usize,
+ limits: &HeadLimits,
+ check_start_line: impl Fn(&[u8]) -> Result<(), Error>,
+) -> Result<(&'h [u8], Vec<Header<'h>>), Error> {
+ if 0 == buffersize {
+  return Err(Error::Msg("buffersize 0 leads to an infinite loop"));
+ }
+ let oldpos = bwr.pos_get();
+ let mut skipped = 0;
+ while bwr.slurp_exact(skipped + 2)? >= skipped + 2 && &bwr.get()[skipped..skipped + 2] == b"\r\n" {
+  skipped += 2;
+  if skipped > limits.max_head_size {
+   return Err(Error::Msg("http head exceeds max_head_size"));
+  }
+ }
+
+ let Some(len) = bwr.slurp_search_limited(
+  buffersize,
+  skipped,
+  b"\r\n\r\n",
+  limits.max_head_size,
+  "http head exceeds max_head_size",
+ )?
+ else {
+  return Err(Error::Msg("unexpected end of file in http head"));
+ };
+
+ let head = &bwr.get()[skipped..len - 2];
+ let lines = split_lines(head)?;
+ let start_line_end = lines[0].1;
+ check_start_line(&head[..start_line_end])?;
+ let fields = parse_fields(head, &lines[1..], limits.max_headers, "http head exceeds max_headers")?;
+
+ bwr.pos_add(len);
+ let bwr: &'h BlockWiseReader<'_> = bwr;
+ let head = &bwr.get_from(oldpos)[skipped..len - 2];
+ Ok((&head[..start_line_end], to_headers(head, &fields)))
+}
+
+/// Splits a block where every line ends with CRLF into the (start, end) positions of the lines.
+pub(crate) fn split_lines(block: &[u8]) -> Result<Vec<(usize, usize)>, Error> {
+ let mut lines = vec![];
+ let mut line_start = 0;
+ for (idx, w) in block.windows(2).enumerate() {
+  if w == b"\r\n" {
+   lines.push((line_start, idx));
+   line_start = idx + 2;
+  }
+ }
+ if lines.iter().any(|(start, end)| {
+  block[*start..*end]
+   .iter()
+   .any(|b| *b == b'\r' || *b == b'\n')
+ }) {
+  return Err(Error::Msg("bare CR or LF in http head"));
+ }
+ Ok(lines)
+}
+
+/// Parses header field lines into (name start, name end, value end) positions relative to block.
+pub(crate) fn parse_fields(
+ block: &[u8],
+ lines: &[(usize, usize)],
+ max_headers: usize,
+ too_many: &'static str,
+) -> Result<Vec<(usize, usize, usize)>, Error> {
+ let mut fields: Vec<(usize, usize, usize)> = vec![];
+ for &(start, end) in lines {
+  let line = &block[start..end];
+  if line.starts_with(b" ") || line.starts_with(b"\t") {
+   match fields.last_mut() {
+    None => return Err(Error::Msg("obsolete line folding without header field")),
+    Some(field) => field.2 = end,
+   }
+   continue;
+  }
+  let Some(colon) = line.iter().position(|b| *b == b':') else {
+   return Err(Error::Msg("header field without colon"));
+  };
+  if colon == 0 || !line[..colon].iter().all(|b| is_tchar(*b)) {
+   return Err(Error::Msg("invalid header field name"));
+  }
+  if fields.len() == max_headers {
+   return Err(Error::Msg(too_many));
+  }
+  fields.push((start, start + colon, end));
+ }
+ Ok(fields)
+}
+
+pub(crate) fn to_headers<'h>(block: &'h [u8], fields: &[(usize, usize, usize)]) -> Vec<Header<'h>> {
+ fields
+  .iter()
+  .map(|&(start, colon, end)| Header {
+   name: &block[start..colon],
+   value: block[colon + 1..end].trim_ascii(),
+  })
+  .collect()
+}
+
+fn parse_version(version: &[u8]) -> Result<Version, Error> {
+ match version {
+  b"HTTP/1.0" => Ok(Version::Http10),
+  b"HTTP/1.1" => Ok(Version::Http11),
+  _ => Err(Error::Msg("unsupported http version")),
+ }
+}
+
+fn parse_chunk_size(hex: &[u8]) -> Option<u64> {
+ if hex.is_empty() {
+  return None;
+ }
+ hex.iter().try_fold(0u64, |acc, b| {
+  acc
+   .checked_mul(16)?
+   .checked_add(u64::from((*b as char).to_digit(16)?))
+ })
+}
+
+pub(crate) fn find_header<'h>(headers: &[Header<'h>], name: &[u8]) -> Option<&'h [u8]> {
+ headers
+  .iter()
+  .find(|h| h.name.eq_ignore_ascii_case(name))
+  .map(|h| h.value)
+}
+
+/// token characters of RFC 9110
+fn is_tchar(b: u8) -> bool {
+ b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
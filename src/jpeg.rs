@@ -0,0 +1,212 @@
+/*!
+Walking over the segments of a JPEG file up to the start of the entropy coded image data.
+
+The segments from SOI on (APPn, DQT, SOFn and so on) are returned with their absolute offsets,
+fill bytes in front of markers and standalone markers without length are handled. The walk stops
+behind the header of the SOS segment, so the image data is never loaded. The payload of a segment can
+be read by Read or accessed as a whole, EXIF and XMP payloads of APP1 segments are recognized.
+
+```rust
+use blockwise_reader::BlockWiseReader;
+use blockwise_reader::jpeg::{JpegReader, APP1, SOI, SOS};
+
+let mut bwr = BlockWiseReader::from_slice(
+ b"\xff\xd8\xff\xe1\x00\x0cExif\0\0MM\0\x2a\xff\xff\xda\x00\x02image data",
+);
+let mut jpeg = JpegReader::new(&mut bwr, 1024);
+assert_eq!(SOI, jpeg.next_segment().unwrap().unwrap().marker);
+let app1 = jpeg.next_segment().unwrap().unwrap();
+assert_eq!((APP1, 2, 10), (app1.marker, app1.offset, app1.size));
+assert_eq!(Some(&b"MM\0\x2a"[..]), jpeg.exif().unwrap());
+assert_eq!(SOS, jpeg.next_segment().unwrap().unwrap().marker);
+assert!(jpeg.next_segment().unwrap().is_none());
+assert_eq!(b"image data", bwr.get());
+```
+*/
+
+use crate::{BlockWiseReader, Error};
+use std::io::Read;
+
+/// start of image
+pub const SOI: u8 = 0xd8;
+/// end of image
+pub const EOI: u8 = 0xd9;
+/// start of scan, the entropy coded data follows its header
+pub const SOS: u8 = 0xda;
+/// define quantization tables
+pub const DQT: u8 = 0xdb;
+/// define huffman tables
+pub const DHT: u8 = 0xc4;
+/// baseline start of frame, the other SOFn markers are 0xc1 up to 0xcf without DHT, JPG and DAC
+pub const SOF0: u8 = 0xc0;
+/// JFIF header
+pub const APP0: u8 = 0xe0;
+/// EXIF or XMP data
+pub const APP1: u8 = 0xe1;
+/// comment
+pub const COM: u8 = 0xfe;
+
+const EXIF_PREFIX: &[u8] = b"Exif\0\0";
+const XMP_PREFIX: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+/// A segment with absolute offsets in the stream.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Segment {
+ /// the byte behind 0xff
+ pub marker: u8,
+ /// absolute offset of the 0xff in front of the marker byte, behind the fill bytes
+ pub offset: u64,
+ /// size of the payload without the length field, 0 for standalone markers
+ pub size: u64,
+ /// absolute offset of the payload
+ pub payload_offset: u64,
+}
+
+impl Segment {
+ /// true for the APPn segments
+ pub fn is_app(&self) -> bool {
+  (0xe0..=0xef).contains(&self.marker)
+ }
+
+ /// true for the SOFn segments
+ pub fn is_sof(&self) -> bool {
+  (0xc0..=0xcf).contains(&self.marker) && ![DHT, 0xc8, 0xcc].contains(&self.marker)
+ }
+}
+
+/// markers without length and payload
+fn is_standalone(marker: u8) -> bool {
+ matches!(marker, 0x01 | 0xd0..=0xd9)
+}
+
+/// Reads the segments of a JPEG file from the current pos of a BlockWiseReader on.
+/// Consumed bytes are removed by pos_drop().
+pub struct JpegReader<'r, 'a> {
+ bwr: &'r mut BlockWiseReader<'a>,
+ buffersize: usize,
+ /// the marker of the current segment, None before SOI
+ current: Option<u8>,
+ /// unread payload bytes of the current segment
+ remaining: u64,
+ done: bool,
+}
+
+impl<'r, 'a> JpegReader<'r, 'a> {
+ /// creates a JpegReader which reads ahead in buffersize steps
+ pub fn new(bwr: &'r mut BlockWiseReader<'a>, buffersize: usize) -> Self {
+  Self {
+   bwr,
+   buffersize: buffersize.max(1),
+   current: None,
+   remaining: 0,
+   done: false,
+  }
+ }
+
+ /// Returns the next segment and sets pos to its payload, the rest of the previous segment is skipped.
+ /// Returns None behind SOS, then pos is set to the entropy coded data, or behind EOI.
+ pub fn next_segment(&mut self) -> Result<Option<Segment>, Error> {
+  if self.done {
+   return Ok(None);
+  }
+  let count = self.remaining;
+  if self.bwr.pos_skip(count, self.buffersize)? < count {
+   return Err(Error::Msg("unexpected end of file in jpeg segment"));
+  }
+  self.remaining = 0;
+  match self.current {
+   Some(SOS) | Some(EOI) => {
+    self.done = true;
+    return Ok(None);
+   }
+   None => {
+    if self.bwr.slurp_exact(2)? < 2 || self.bwr.get()[..2] != [0xff, SOI] {
+     return Err(Error::Msg("missing jpeg start of image"));
+    }
+   }
+   Some(_) => {}
+  }
+
+  // fill bytes
+  let mut idx = 0;
+  loop {
+   if self.bwr.slurp_exact(idx + 1)? < idx + 1 {
+    return Err(Error::Msg("unexpected end of file in jpeg segment"));
+   }
+   match self.bwr.get()[idx] {
+    0xff => idx += 1,
+    _ if 0 == idx => return Err(Error::Msg("invalid jpeg marker")),
+    _ => break,
+   }
+   if idx > self.buffersize {
+    self.bwr.pos_add(idx - 1);
+    self.bwr.pos_drop();
+    idx = 1;
+   }
+  }
+  let marker = self.bwr.get()[idx];
+  if 0 == marker {
+   return Err(Error::Msg("invalid jpeg marker"));
+  }
+  let offset = self.bwr.pos_absolute() + idx as u64 - 1;
+  self.bwr.pos_add(idx + 1);
+  let mut size = 0;
+  if !is_standalone(marker) {
+   if self.bwr.slurp_exact(2)? < 2 {
+    return Err(Error::Msg("unexpected end of file in jpeg segment"));
+   }
+   let length = u16::from_be_bytes([self.bwr.get()[0], self.bwr.get()[1]]);
+   if length < 2 {
+    return Err(Error::Msg("invalid jpeg segment length"));
+   }
+   size = length as u64 - 2;
+   self.bwr.pos_add(2);
+  }
+  self.bwr.pos_drop();
+  self.current = Some(marker);
+  self.remaining = size;
+  Ok(Some(Segment {
+   marker,
+   offset,
+   size,
+   payload_offset: self.bwr.pos_absolute(),
+  }))
+ }
+
+ /// the unread payload of the current segment as a whole, it remains unread
+ pub fn payload(&mut self) -> Result<&[u8], Error> {
+  let size = self.remaining as usize;
+  if self.bwr.slurp_exact(size)? < size {
+   return Err(Error::Msg("unexpected end of file in jpeg segment"));
+  }
+  Ok(&self.bwr.get()[..size])
+ }
+
+ /// the TIFF structure of EXIF data if the current segment is an unread APP1 segment with EXIF data
+ pub fn exif(&mut self) -> Result<Option<&[u8]>, Error> {
+  self.app1_with_prefix(EXIF_PREFIX)
+ }
+
+ /// the XMP packet if the current segment is an unread APP1 segment with XMP data
+ pub fn xmp(&mut self) -> Result<Option<&[u8]>, Error> {
+  self.app1_with_prefix(XMP_PREFIX)
+ }
+
+ fn app1_with_prefix(&mut self, prefix: &[u8]) -> Result<Option<&[u8]>, Error> {
+  if Some(APP1) != self.current {
+   return Ok(None);
+  }
+  Ok(self.payload()?.strip_prefix(prefix))
+ }
+}
+
+impl Read for JpegReader<'_, '_> {
+ fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+  self.bwr.read_payload(
+   self.buffersize,
+   &mut self.remaining,
+   buf,
+   Some("unexpected end of file in jpeg segment"),
+  )
+ }
+}
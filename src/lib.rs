@@ -65,12 +65,41 @@ assert_eq!( 442, bwr.pos_get());
 ```
 */
 
+pub mod audio;
+mod binary;
+pub mod bits;
+pub mod bmff;
+mod buffer;
+pub mod chunk;
+pub mod csv;
+pub mod ebml;
+pub mod http;
+pub mod jpeg;
+pub mod multipart;
+#[cfg(feature = "nom")]
+mod nom_driver;
+pub mod proxy_protocol;
+pub mod resp;
+pub mod sse;
+pub mod tar;
+pub mod tls_peek;
+pub mod video;
+pub mod websocket;
+#[cfg(feature = "winnow")]
+mod winnow_driver;
+pub mod zip;
+
+#[cfg(feature = "nom")]
+pub use nom_driver::NomResult;
+#[cfg(feature = "winnow")]
+pub use winnow_driver::{PartialParser, WinnowResult};
+
+use buffer::{Backing, Buffer};
 use memmem::{Searcher, TwoWaySearcher};
 use std::{
  cmp::{max, min},
  hash::BuildHasher,
- io::Read,
- mem::swap,
+ io::{Cursor, Read},
 };
 
 /// this enum decides where to set the internal vector position after a search / find operation
@@ -84,10 +113,12 @@ pub enum FindPos {
 
 /// The BlockWiseReader holds the data which are read in to a specific point and a reader to read from
 pub struct BlockWiseReader<'a> {
- v: Vec<u8>,
+ v: Buffer<'a>,
  r: Box<dyn Read + 'a>,
  pos: usize,
  eof: bool,
+ /// amount of bytes removed by pos_cut and pos_drop
+ removed: u64,
 }
 
 #[derive(Debug)]
@@ -102,11 +133,115 @@ impl From<std::io::Error> for Error {
  }
 }
 
+impl From<Error> for std::io::Error {
+ fn from(value: Error) -> Self {
+  match value {
+   Error::IO(e) => e,
+   Error::Msg(msg) => std::io::Error::new(std::io::ErrorKind::InvalidData, msg),
+  }
+ }
+}
+
 #[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
 pub struct PatternIdx {
  pub idx: usize,
 }
 
+/// Quote and escape rules.
+/// Bytes between an opening quote and its closing quote are quoted.
+/// The escape byte makes the following byte ordinary, inside and outside of quotes.
+#[derive(Clone, Copy)]
+pub struct Quoting<'q> {
+ /// pairs of opening and closing quote bytes
+ pub quotes: &'q [(u8, u8)],
+ pub escape: Option<u8>,
+}
+
+/// the distance from a sync pattern to the next one
+#[derive(Clone, Copy)]
+pub enum SyncInterval<'f> {
+ /// records of a fixed size like transport stream packets
+ Fixed(usize),
+ /// The record size is derived from the header_size bytes at the sync pattern, like for MP3 frames or Ogg pages.
+ /// The function returns None for an invalid header.
+ Length {
+  header_size: usize,
+  length: &'f dyn Fn(&[u8]) -> Option<usize>,
+ },
+}
+
+/// Describes the record boundaries of a stream for a resynchronization.
+#[derive(Clone, Copy)]
+pub struct SyncPattern<'p> {
+ /// the bytes each record starts with
+ pub pattern: &'p [u8],
+ pub interval: SyncInterval<'p>,
+ /// the number of consecutive records needed to accept a boundary
+ pub confirmations: usize,
+}
+
+/// the state of a scan over quoted and escaped bytes
+#[derive(Default)]
+struct QuoteState {
+ /// the closing quote byte inside of quotes
+ quote_close: Option<u8>,
+ escaped: bool,
+}
+
+impl QuoteState {
+ /// advances the state by one byte, returns true if the byte is neither escaped, quoted, a quote nor the escape byte
+ fn plain(&mut self, b: u8, quoting: &Quoting) -> bool {
+  if self.escaped {
+   self.escaped = false;
+   return false;
+  }
+  if Some(b) == quoting.escape {
+   self.escaped = true;
+   return false;
+  }
+  match self.quote_close {
+   Some(close) => {
+    if b == close {
+     self.quote_close = None;
+    }
+    false
+   }
+   None => match quoting.quotes.iter().find(|(open, _)| *open == b) {
+    Some((_, close)) => {
+     self.quote_close = Some(*close);
+     false
+    }
+    None => true,
+   },
+  }
+ }
+}
+
+/// Searches pattern in data from idx on, where the first byte of a match must neither be escaped nor quoted.
+/// Advances idx and qs up to the match or as far as the data allow a decision, so the scan can be continued with more data.
+fn search_quoted_from(
+ data: &[u8],
+ idx: &mut usize,
+ qs: &mut QuoteState,
+ quoting: &Quoting,
+ pattern: &[u8],
+) -> Option<usize> {
+ while *idx < data.len() {
+  if qs.quote_close.is_none() && !qs.escaped {
+   let rest = &data[*idx..];
+   if rest.starts_with(pattern) {
+    return Some(*idx);
+   }
+   if pattern.starts_with(rest) {
+    return None;
+   }
+  }
+  qs.plain(data[*idx], quoting);
+  *idx += 1;
+ }
+ None
+}
+
 #[derive(Clone, Copy, PartialEq, PartialOrd)]
 struct BufferIdx {
  idx: usize,
@@ -132,10 +267,82 @@ impl<'a> BlockWiseReader<'a> {
  /// creates a new BlockWiseReader from the given reader
  pub fn new(r: Box<dyn Read + 'a>) -> Self {
   Self {
-   v: vec![],
+   v: Buffer::Owned(vec![]),
    r,
    pos: 0,
    eof: false,
+   removed: 0,
+  }
+ }
+
+ /// Creates a new BlockWiseReader which memory maps the given file.
+ /// The slurp methods then only move a visible window over the mapping, nothing is copied.
+ /// Falls back to buffered reading like new() if the file can not be mapped, e.g. for pipes.
+ /// The file must not be modified as long as the BlockWiseReader exists.
+ #[cfg(feature = "mmap")]
+ pub fn new_mmap(file: std::fs::File) -> Self {
+  let mapping = match file.metadata() {
+   Ok(md) if md.is_file() && md.len() > 0 => unsafe { memmap2::Mmap::map(&file) }.ok(),
+   _ => None,
+  };
+  match mapping {
+   None => Self::new(Box::new(file)),
+   Some(mapping) => Self {
+    v: Buffer::Window {
+     data: Backing::Mmap(mapping),
+     start: 0,
+     end: 0,
+    },
+    r: Box::new(std::io::empty()),
+    pos: 0,
+    eof: false,
+    removed: 0,
+   },
+  }
+ }
+
+ /// Creates a new BlockWiseReader directly on the given slice without copying it.
+ /// All data are available from the beginning, so slurp does nothing and eof is reached immediately.
+ pub fn from_slice(s: &'a [u8]) -> Self {
+  Self::from_backing(Backing::Slice(s))
+ }
+
+ /// Creates a new BlockWiseReader directly on the given Bytes without copying them.
+ /// All data are available from the beginning, so slurp does nothing and eof is reached immediately.
+ #[cfg(feature = "bytes")]
+ pub fn from_bytes(b: bytes::Bytes) -> Self {
+  Self::from_backing(Backing::Bytes(b))
+ }
+
+ fn from_backing(data: Backing<'a>) -> Self {
+  let end = data.as_ref().len();
+  Self {
+   v: Buffer::Window {
+    data,
+    start: 0,
+    end,
+   },
+   r: Box::new(std::io::empty()),
+   pos: 0,
+   eof: true,
+   removed: 0,
+  }
+ }
+
+ /// Converts a window into an owned vector, which is needed to modify the data.
+ /// The bytes behind the window are read from then on by a cursor over the former window data.
+ fn owned(&mut self) -> &mut Vec<u8> {
+  if let Buffer::Window { .. } = self.v {
+   let v = Buffer::Owned(self.v.to_vec());
+   if let Buffer::Window { data, end, .. } = std::mem::replace(&mut self.v, v) {
+    let mut c = Cursor::new(data);
+    c.set_position(end as u64);
+    self.r = Box::new(c);
+   }
+  }
+  match &mut self.v {
+   Buffer::Owned(v) => v,
+   Buffer::Window { .. } => unreachable!(),
   }
  }
 
@@ -158,15 +365,20 @@ impl<'a> BlockWiseReader<'a> {
    return Ok(self.available_bytes());
   }
   let pos = self.pos;
+  if let Some(eof) = self.v.window_extend(pos + bytecount) {
+   self.eof = eof;
+   return Ok(self.available_bytes());
+  }
   let read_start = self.v.len();
   if read_start >= pos + bytecount {
    return Ok(self.available_bytes());
   }
   let amount_to_read = pos + bytecount - read_start;
-  self.v.resize(pos + bytecount, 0);
-  let rod = self.r.read(&mut self.v[read_start..])?;
+  let Buffer::Owned(v) = &mut self.v else { unreachable!() };
+  v.resize(pos + bytecount, 0);
+  let rod = self.r.read(&mut v[read_start..])?;
   if rod < amount_to_read {
-   self.v.truncate(read_start + rod);
+   v.truncate(read_start + rod);
   }
   if rod == 0 {
    self.eof = true;
@@ -174,15 +386,32 @@ impl<'a> BlockWiseReader<'a> {
   Ok(self.available_bytes())
  }
 
+ /// Calls slurp until at least bytecount bytes from the current position ahead are available or end
+ /// of file is reached, this is needed for readers which return less bytes than requested like sockets.
+ /// Returns the amount of available bytes starting at pos.
+ pub fn slurp_exact(&mut self, bytecount: usize) -> Result<usize, std::io::Error> {
+  loop {
+   let available = self.slurp(bytecount)?;
+   if available >= bytecount || self.eof {
+    return Ok(available);
+   }
+  }
+ }
+
  /// Reads bytes from the stream in buffersize steps as long as there are bytes available.
  pub fn slurp_loop(&mut self, buffersize: usize) -> Result<usize, std::io::Error> {
+  if let Some(eof) = self.v.window_extend(usize::MAX - self.pos) {
+   self.eof = eof;
+   return Ok(self.available_bytes());
+  }
+  let Buffer::Owned(v) = &mut self.v else { unreachable!() };
   loop {
-   let len = self.v.len();
+   let len = v.len();
    let newlen = len + buffersize;
-   self.v.resize(newlen, 0);
-   let rod = self.r.read(&mut self.v[len..])?;
+   v.resize(newlen, 0);
+   let rod = self.r.read(&mut v[len..])?;
    if rod < buffersize {
-    self.v.truncate(len + rod);
+    v.truncate(len + rod);
    }
    if rod == 0 {
     self.eof = true;
@@ -230,17 +459,75 @@ impl<'a> BlockWiseReader<'a> {
 
  /// removes all elements form the beginning of the internal vector to pos and returns the removed elements
  pub fn pos_cut(&mut self) -> Vec<u8> {
-  let mut ret = self.v.split_off(self.pos);
-  swap(&mut self.v, &mut ret);
+  let ret = self.v.cut(self.pos);
+  self.removed += self.pos as u64;
   self.pos = 0;
   ret
  }
 
+ /// removes all elements form the beginning of the internal vector to pos like pos_cut, but without returning them
+ pub fn pos_drop(&mut self) {
+  self.v.drop_front(self.pos);
+  self.removed += self.pos as u64;
+  self.pos = 0;
+ }
+
+ /// Skips bytecount bytes from pos on without keeping them, the bytes up to the new pos are dropped like by pos_drop.
+ /// Reads in buffersize steps, so the memory stays bounded also for huge bytecounts.
+ /// Returns the amount of skipped bytes, which is less than bytecount if end of file is reached.
+ pub fn pos_skip(&mut self, bytecount: u64, buffersize: usize) -> Result<u64, std::io::Error> {
+  let mut skipped = 0;
+  loop {
+   let step = min(self.available_bytes() as u64, bytecount - skipped);
+   self.pos_add(step as usize);
+   self.pos_drop();
+   skipped += step;
+   if skipped == bytecount {
+    return Ok(skipped);
+   }
+   let want = min(bytecount - skipped, buffersize.max(1) as u64);
+   if 0 == self.slurp(want as usize)? && self.eof {
+    return Ok(skipped);
+   }
+  }
+ }
+
+ /// Copies the next bytes of a payload with remaining unread bytes into buf for the Read implementations of the
+ /// module readers, at most buffersize bytes at once. pos is set behind the copied bytes and they are dropped.
+ /// If the stream ends before, unexpected_eof is returned as error, or 0 if unexpected_eof is None.
+ pub(crate) fn read_payload(
+  &mut self,
+  buffersize: usize,
+  remaining: &mut u64,
+  buf: &mut [u8],
+  unexpected_eof: Option<&'static str>,
+ ) -> Result<usize, std::io::Error> {
+  if 0 == *remaining || buf.is_empty() {
+   return Ok(0);
+  }
+  let want = min(min(*remaining, buf.len() as u64), buffersize as u64) as usize;
+  let available = self.slurp(want)?;
+  if 0 == available {
+   return match unexpected_eof {
+    Some(msg) => Err(Error::Msg(msg).into()),
+    None => Ok(0),
+   };
+  }
+  let n = min(want, available);
+  buf[..n].copy_from_slice(&self.get()[..n]);
+  self.pos_add(n);
+  self.pos_drop();
+  *remaining -= n as u64;
+  Ok(n)
+ }
+
  /// copies the data of the slice s at the position pos
  pub fn pos_inject(&mut self, s: &[u8]) {
-  let v3 = self.v.split_off(self.pos);
-  self.v.extend(s);
-  self.v.extend(v3);
+  let pos = self.pos;
+  let v = self.owned();
+  let v3 = v.split_off(pos);
+  v.extend(s);
+  v.extend(v3);
  }
 
  /// returns all data from pos to the end of the internal vector
@@ -417,6 +704,11 @@ impl<'a> BlockWiseReader<'a> {
   self.pos
  }
 
+ /// the position in the stream, which is pos plus the amount of bytes removed by pos_cut and pos_drop
+ pub fn pos_absolute(&self) -> u64 {
+  self.removed + self.pos as u64
+ }
+
  /// Convenience method, calls self.slurp_search_repos(bytecount, bytes, FindPos::Begin).
  pub fn slurp_search_repos0(
   &mut self,
@@ -561,6 +853,39 @@ impl<'a> BlockWiseReader<'a> {
   }
  }
 
+ /// Searches pattern from pos + from on, reading ahead in buffersize steps up to max_len bytes behind from.
+ /// Returns the offset behind the pattern relative to pos or None at end of file, too_long is returned as
+ /// error if the pattern is not found within max_len bytes.
+ pub(crate) fn slurp_search_limited(
+  &mut self,
+  buffersize: usize,
+  from: usize,
+  pattern: &[u8],
+  max_len: usize,
+  too_long: &'static str,
+ ) -> Result<Option<usize>, Error> {
+  let searcher = TwoWaySearcher::new(pattern);
+  let mut searched = from;
+  loop {
+   let available = self.available_bytes();
+   let search_from = max(from, (searched + 1).saturating_sub(pattern.len()));
+   if let Some(found) = searcher.search_in(&self.get()[search_from..]) {
+    let end = search_from + found + pattern.len();
+    if end - from > max_len {
+     return Err(Error::Msg(too_long));
+    }
+    return Ok(Some(end));
+   }
+   if available >= from + max_len {
+    return Err(Error::Msg(too_long));
+   }
+   searched = available;
+   if self.slurp(min(available + buffersize, from + max_len + 1))? == available && self.eof {
+    return Ok(None);
+   }
+  }
+ }
+
  /// Applies self.slurp_find_multiple_repos_idx in a loop up to end of file or the pattern was found.
  /// The buffer must be bigger than the byte slice.
  pub fn slurp_find_multiple_repos_loop_idx(
@@ -635,6 +960,234 @@ impl<'a> BlockWiseReader<'a> {
   }
  }
 
+ /// searches a byte in the available bytes which is neither escaped nor quoted
+ pub fn find_quoted(&self, e: u8, quoting: &Quoting) -> Option<usize> {
+  self.search_quoted(&[e], quoting)
+ }
+
+ /// searches a byte slice in the available bytes, its first byte must neither be escaped nor quoted
+ pub fn search_quoted(&self, bytes: &[u8], quoting: &Quoting) -> Option<usize> {
+  search_quoted_from(self.get(), &mut 0, &mut QuoteState::default(), quoting, bytes)
+ }
+
+ /// Slurps bytecount bytes.
+ /// Sets pos regarding the fp flag if the byte was found in the available bytes outside of quotes and not escaped.
+ /// If nothing was found pos remains unaltered.
+ /// Returns true if something was found, false otherwise.
+ pub fn slurp_find_quoted_repos(
+  &mut self,
+  bytecount: usize,
+  e: u8,
+  quoting: &Quoting,
+  fp: FindPos,
+ ) -> Result<bool, std::io::Error> {
+  self.slurp_search_quoted_repos(bytecount, &[e], quoting, fp)
+ }
+
+ /// Slurps bytecount bytes.
+ /// Sets pos regarding the fp flag if the byte slice was found in the available bytes outside of quotes and not escaped.
+ /// If nothing was found pos remains unaltered.
+ /// Returns true if something was found, false otherwise.
+ pub fn slurp_search_quoted_repos(
+  &mut self,
+  bytecount: usize,
+  bytes: &[u8],
+  quoting: &Quoting,
+  fp: FindPos,
+ ) -> Result<bool, std::io::Error> {
+  self.slurp(bytecount)?;
+  Ok(match self.search_quoted(bytes, quoting) {
+   None => false,
+   Some(pos) => {
+    let offset = match fp {
+     FindPos::Begin => 0,
+     FindPos::End => bytes.len(),
+    };
+    self.pos_add(pos + offset);
+    true
+   }
+  })
+ }
+
+ /// Like self.slurp_find_quoted_repos, but reads repeatedly buffersize bytes up to end of file or the byte was found.
+ /// The quote and escape state is carried over the block boundaries.
+ /// The byte has to be found within max_len bytes behind pos, otherwise an error is returned.
+ pub fn slurp_find_quoted_repos_loop(
+  &mut self,
+  buffersize: usize,
+  max_len: usize,
+  e: u8,
+  quoting: &Quoting,
+  fp: FindPos,
+ ) -> Result<bool, Error> {
+  self.slurp_search_quoted_repos_loop(buffersize, max_len, &[e], quoting, fp)
+ }
+
+ /// Like self.slurp_search_quoted_repos, but reads repeatedly buffersize bytes up to end of file or the byte slice was found.
+ /// The quote and escape state is carried over the block boundaries.
+ /// The byte slice has to be found within max_len bytes behind pos, so no more than max_len bytes are slurped.
+ /// Otherwise an error is returned and pos remains unaltered.
+ pub fn slurp_search_quoted_repos_loop(
+  &mut self,
+  buffersize: usize,
+  max_len: usize,
+  bytes: &[u8],
+  quoting: &Quoting,
+  fp: FindPos,
+ ) -> Result<bool, Error> {
+  if 0 == buffersize {
+   return Err(Error::Msg("buffersize 0 leads to an infinite loop"));
+  }
+  if bytes.is_empty() {
+   return Err(Error::Msg("every byte slice must not be of length 0"));
+  }
+  let mut qs = QuoteState::default();
+  let mut idx = 0;
+  loop {
+   let data = &self.get()[..min(self.available_bytes(), max_len)];
+   if let Some(found) = search_quoted_from(data, &mut idx, &mut qs, quoting, bytes) {
+    let offset = match fp {
+     FindPos::Begin => 0,
+     FindPos::End => bytes.len(),
+    };
+    self.pos_add(found + offset);
+    return Ok(true);
+   }
+   let available = self.available_bytes();
+   if available >= max_len {
+    return Err(Error::Msg("quoted search exceeds max_len"));
+   }
+   if self.slurp(min(available + buffersize, max_len))? == available && self.eof {
+    return Ok(false);
+   }
+  }
+ }
+
+ /// Searches the first record boundary from pos on which is confirmed by sync.confirmations consecutive records,
+ /// reading ahead in buffersize steps. A boundary followed by less than sync.confirmations records up to
+ /// end of file is not accepted. Sets pos to the boundary and returns the number of skipped bytes.
+ /// The skipped bytes are removed by pos_drop() while searching, so the memory stays bounded by the records
+ /// needed for the confirmation also for long runs of garbage.
+ /// Returns None if there is no confirmed boundary up to end of file, then all bytes are consumed.
+ pub fn slurp_resync_repos_loop(
+  &mut self,
+  buffersize: usize,
+  sync: &SyncPattern,
+ ) -> Result<Option<usize>, Error> {
+  if 0 == buffersize {
+   return Err(Error::Msg("buffersize 0 leads to an infinite loop"));
+  }
+  if sync.pattern.is_empty() {
+   return Err(Error::Msg("empty sync pattern"));
+  }
+  let header_size = match sync.interval {
+   SyncInterval::Fixed(0) => return Err(Error::Msg("sync interval 0 leads to an infinite loop")),
+   SyncInterval::Fixed(_) => sync.pattern.len(),
+   SyncInterval::Length { header_size, .. } => max(header_size, sync.pattern.len()),
+  };
+  let mut skipped = 0;
+  loop {
+   // the bytes in front of the next candidate are dropped, except for a possible begin of the pattern
+   loop {
+    if let Some(idx) = self.search(sync.pattern) {
+     self.pos_add(idx);
+     self.pos_drop();
+     skipped += idx;
+     break;
+    }
+    let available = self.available_bytes();
+    let garbage = available.saturating_sub(sync.pattern.len() - 1);
+    self.pos_add(garbage);
+    self.pos_drop();
+    skipped += garbage;
+    let available = available - garbage;
+    if self.slurp(available + buffersize)? == available && self.eof {
+     self.pos_add(available);
+     self.pos_drop();
+     return Ok(None);
+    }
+   }
+   let mut offset = 0;
+   let mut count = 0;
+   let confirmed = loop {
+    if self.slurp_exact(offset + header_size)? < offset + header_size {
+     break false;
+    }
+    let data = &self.get()[offset..];
+    if !data.starts_with(sync.pattern) {
+     break false;
+    }
+    let size = match sync.interval {
+     SyncInterval::Fixed(size) => size,
+     SyncInterval::Length { length, .. } => match length(&data[..header_size]) {
+      Some(size) if size > 0 => size,
+      _ => break false,
+     },
+    };
+    count += 1;
+    if count >= sync.confirmations {
+     break true;
+    }
+    offset += size;
+   };
+   if confirmed {
+    return Ok(Some(skipped));
+   }
+   self.pos_add(1);
+   skipped += 1;
+  }
+ }
+
+ /// Skips a balanced region like a JSON object or array which begins at pos with an opening byte of pairs.
+ /// Reads repeatedly buffersize bytes up to the matching closing byte, which is searched respecting the quoting.
+ /// Sets pos behind the region and returns true, returns false if there is no opening byte at pos.
+ /// Returns an error for unbalanced input and for nesting deeper than max_depth, pos remains unaltered then.
+ pub fn slurp_balanced(
+  &mut self,
+  buffersize: usize,
+  pairs: &[(u8, u8)],
+  quoting: &Quoting,
+  max_depth: usize,
+ ) -> Result<bool, Error> {
+  if 0 == buffersize {
+   return Err(Error::Msg("buffersize 0 leads to an infinite loop"));
+  }
+  if self.slurp_exact(1)? == 0 || !pairs.iter().any(|(open, _)| *open == self.get()[0]) {
+   return Ok(false);
+  }
+  let mut closes: Vec<u8> = vec![];
+  let mut qs = QuoteState::default();
+  let mut idx = 0;
+  loop {
+   let data = self.get();
+   while idx < data.len() {
+    let b = data[idx];
+    idx += 1;
+    if !qs.plain(b, quoting) {
+     continue;
+    }
+    if let Some((_, close)) = pairs.iter().find(|(open, _)| *open == b) {
+     closes.push(*close);
+     if closes.len() > max_depth {
+      return Err(Error::Msg("balanced region exceeds max_depth"));
+     }
+    } else if pairs.iter().any(|(_, close)| *close == b) {
+     if closes.pop() != Some(b) {
+      return Err(Error::Msg("unbalanced: unexpected closing byte"));
+     }
+     if closes.is_empty() {
+      self.pos_add(idx);
+      return Ok(true);
+     }
+    }
+   }
+   let available = data.len();
+   if self.slurp(available + buffersize)? == available && self.eof {
+    return Err(Error::Msg("unbalanced: end of file inside of the region"));
+   }
+  }
+ }
+
  /// returns true if eof is reached
  fn eof(&self) -> bool {
   self.eof
@@ -0,0 +1,270 @@
+/*!
+Splitting of MIME multipart bodies like multipart/form-data uploads or multipart emails.
+
+The parts are read one after another from the current pos of a BlockWiseReader on,
+every part provides its header fields and its body by Read.
+The preamble is skipped, after the close delimiter pos is set to the begin of the epilogue.
+
+```rust
+use std::io::Read;
+use blockwise_reader::BlockWiseReader;
+use blockwise_reader::http::HeadLimits;
+use blockwise_reader::multipart::{boundary, Multipart};
+
+let content_type = b"multipart/form-data; boundary=\"XyZ\"";
+let mut bwr = BlockWiseReader::from_slice(
+ b"--XyZ\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nvalue a\r\n--XyZ--\r\n",
+);
+
+let boundary = boundary(content_type).unwrap();
+let mut multipart = Multipart::new(&mut bwr, boundary, 1024, HeadLimits::default()).unwrap();
+let mut part = multipart.next_part().unwrap().unwrap();
+assert_eq!(
+ Some("form-data; name=\"a\"".as_bytes()),
+ part.header(b"content-disposition")
+);
+let mut body = String::new();
+part.read_to_string(&mut body).unwrap();
+assert_eq!("value a", body);
+assert!(multipart.next_part().unwrap().is_none());
+```
+*/
+
+use crate::http::{find_header, parse_fields, split_lines, to_headers, HeadLimits, Header};
+use crate::{BlockWiseReader, Error};
+use memmem::{Searcher, TwoWaySearcher};
+use std::{
+ cmp::{max, min},
+ io::Read,
+};
+
+/// Extracts the boundary parameter of a multipart Content-Type header value, quotes are removed.
+pub fn boundary(content_type: &[u8]) -> Option<&[u8]> {
+ content_type
+  .split(|b| *b == b';')
+  .skip(1)
+  .find_map(|param| {
+   let param = param.trim_ascii();
+   let (name, value) = param.split_at(param.iter().position(|b| *b == b'=')?);
+   if !name.trim_ascii_end().eq_ignore_ascii_case(b"boundary") {
+    return None;
+   }
+   let value = value[1..].trim_ascii_start();
+   match value.strip_prefix(b"\"") {
+    None => Some(value),
+    Some(quoted) => quoted.strip_suffix(b"\""),
+   }
+  })
+}
+
+/// Reads the parts of a multipart body.
+/// Consumed bytes are removed from the BlockWiseReader by pos_drop(), so the memory per part stays
+/// bounded by the buffersize and the limits of the header section.
+pub struct Multipart<'r, 'a> {
+ bwr: &'r mut BlockWiseReader<'a>,
+ /// CRLF, two dashes and the boundary
+ delimiter: Vec<u8>,
+ buffersize: usize,
+ limits: HeadLimits,
+ state: MultipartState,
+ header_block: Vec<u8>,
+ header_fields: Vec<(usize, usize, usize)>,
+}
+
+enum MultipartState {
+ Preamble,
+ Body,
+ Delimiter,
+ Done,
+}
+
+impl<'r, 'a> Multipart<'r, 'a> {
+ /// Creates a Multipart for the given boundary which reads ahead in buffersize steps.
+ /// The buffersize must be bigger than the boundary, the limits apply to the header section of every part.
+ pub fn new(
+  bwr: &'r mut BlockWiseReader<'a>,
+  boundary: &[u8],
+  buffersize: usize,
+  limits: HeadLimits,
+ ) -> Result<Self, Error> {
+  if boundary.is_empty() || boundary.len() > 70 {
+   return Err(Error::Msg("invalid multipart boundary"));
+  }
+  let delimiter = [b"\r\n--", boundary].concat();
+  if buffersize <= delimiter.len() {
+   return Err(Error::Msg("error: buffersize <= delimiter.len()"));
+  }
+  Ok(Self {
+   bwr,
+   delimiter,
+   buffersize,
+   limits,
+   state: MultipartState::Preamble,
+   header_block: vec![],
+   header_fields: vec![],
+  })
+ }
+
+ /// Returns the next part or None after the close delimiter.
+ /// The rest of the body of the previous part is skipped.
+ pub fn next_part(&mut self) -> Result<Option<Part<'_, 'r, 'a>>, Error> {
+  match self.state {
+   MultipartState::Done => return Ok(None),
+   MultipartState::Preamble => self.skip_preamble()?,
+   MultipartState::Body => {
+    let mut sink = [0u8; 1024];
+    while let MultipartState::Body = self.state {
+     self.read_body(&mut sink)?;
+    }
+   }
+   MultipartState::Delimiter => {}
+  }
+
+  // the rest of the delimiter line
+  if self.bwr.slurp_exact(2)? >= 2 && &self.bwr.get()[..2] == b"--" {
+   self.bwr.pos_add(2);
+   if self.bwr.slurp_exact(2)? >= 2 && &self.bwr.get()[..2] == b"\r\n" {
+    self.bwr.pos_add(2);
+   }
+   self.state = MultipartState::Done;
+   return Ok(None);
+  }
+  let Some(len) = self.bwr.slurp_search_limited(
+   self.buffersize,
+   0,
+   b"\r\n",
+   self.limits.max_head_size,
+   "multipart delimiter line exceeds max_head_size",
+  )?
+  else {
+   return Err(Error::Msg("unexpected end of file in multipart body"));
+  };
+  if !self.bwr.get()[..len - 2]
+   .iter()
+   .all(|b| *b == b' ' || *b == b'\t')
+  {
+   return Err(Error::Msg("invalid multipart delimiter line"));
+  }
+  self.bwr.pos_add(len);
+
+  self.header_block.clear();
+  self.header_fields.clear();
+  if self.bwr.slurp_exact(2)? >= 2 && &self.bwr.get()[..2] == b"\r\n" {
+   self.bwr.pos_add(2);
+  } else {
+   let Some(len) = self.bwr.slurp_search_limited(
+    self.buffersize,
+    0,
+    b"\r\n\r\n",
+    self.limits.max_head_size,
+    "multipart header section exceeds max_head_size",
+   )?
+   else {
+    return Err(Error::Msg("unexpected end of file in multipart header section"));
+   };
+   let block = &self.bwr.get()[..len - 2];
+   let lines = split_lines(block)?;
+   self.header_fields = parse_fields(
+    block,
+    &lines,
+    self.limits.max_headers,
+    "multipart header section exceeds max_headers",
+   )?;
+   self.header_block.extend_from_slice(block);
+   self.bwr.pos_add(len);
+  }
+  self.bwr.pos_drop();
+  self.state = MultipartState::Body;
+  Ok(Some(Part { multipart: self }))
+ }
+
+ /// skips everything up to and including the first delimiter, which may be at the begin without CRLF
+ fn skip_preamble(&mut self) -> Result<(), Error> {
+  let dash_boundary = &self.delimiter[2..];
+  let len = dash_boundary.len();
+  if self.bwr.slurp_exact(len)? >= len && &self.bwr.get()[..len] == dash_boundary {
+   self.bwr.pos_add(len);
+   self.state = MultipartState::Delimiter;
+   return Ok(());
+  }
+  loop {
+   let available = self.bwr.available_bytes();
+   if let Some(found) = TwoWaySearcher::new(&self.delimiter).search_in(self.bwr.get()) {
+    self.bwr.pos_add(found + self.delimiter.len());
+    self.state = MultipartState::Delimiter;
+    return Ok(());
+   }
+   self
+    .bwr
+    .pos_add(available.saturating_sub(self.delimiter.len() - 1));
+   self.bwr.pos_drop();
+   let available = self.bwr.available_bytes();
+   if self.bwr.slurp(available + self.buffersize)? == available && self.bwr.eof {
+    return Err(Error::Msg("missing multipart delimiter"));
+   }
+  }
+ }
+
+ /// Copies body bytes up to the next delimiter into buf, returns 0 and consumes the delimiter at the end of the body.
+ fn read_body(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+  if buf.is_empty() {
+   return Ok(0);
+  }
+  loop {
+   let MultipartState::Body = self.state else {
+    return Ok(0);
+   };
+   let available = self.bwr.available_bytes();
+   let safe = match TwoWaySearcher::new(&self.delimiter).search_in(self.bwr.get()) {
+    Some(0) => {
+     self.bwr.pos_add(self.delimiter.len());
+     self.bwr.pos_drop();
+     self.state = MultipartState::Delimiter;
+     return Ok(0);
+    }
+    Some(found) => found,
+    None => available.saturating_sub(self.delimiter.len() - 1),
+   };
+   if safe > 0 {
+    let n = min(safe, buf.len());
+    buf[..n].copy_from_slice(&self.bwr.get()[..n]);
+    self.bwr.pos_add(n);
+    if self.bwr.pos_get() >= self.buffersize {
+     self.bwr.pos_drop();
+    }
+    return Ok(n);
+   }
+   if self
+    .bwr
+    .slurp(max(available + self.buffersize, self.delimiter.len()))?
+    == available
+    && self.bwr.eof
+   {
+    return Err(Error::Msg("unexpected end of file in multipart body"));
+   }
+  }
+ }
+}
+
+/// A part of a multipart body, the body is read by Read up to the next delimiter.
+pub struct Part<'m, 'r, 'a> {
+ multipart: &'m mut Multipart<'r, 'a>,
+}
+
+impl Part<'_, '_, '_> {
+ /// the header fields of the part
+ pub fn headers(&self) -> Vec<Header<'_>> {
+  to_headers(&self.multipart.header_block, &self.multipart.header_fields)
+ }
+
+ /// the value of the first header field with the given name, compared case insensitive
+ pub fn header(&self, name: &[u8]) -> Option<&[u8]> {
+  find_header(&self.headers(), name)
+ }
+}
+
+impl Read for Part<'_, '_, '_> {
+ fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+  Ok(self.multipart.read_body(buf)?)
+ }
+}
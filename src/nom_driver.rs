@@ -0,0 +1,62 @@
+use crate::{BlockWiseReader, Error};
+use nom::{
+ error::{ErrorKind, ParseError},
+ Err, Needed, Parser,
+};
+use std::cmp::max;
+
+impl<'a> BlockWiseReader<'a> {
+ /// Applies a streaming nom parser on the available bytes.
+ /// As long as the parser returns Err::Incomplete more bytes are slurped: the missing amount for
+ /// Needed::Size, otherwise at least buffersize bytes, doubling the available bytes with each try.
+ /// At end of file the parser is applied in complete mode and an Incomplete which remains is turned
+ /// into an Err::Error of kind ErrorKind::Complete.
+ /// On success pos is set behind the consumed bytes, otherwise pos remains unaltered.
+ /// The output may borrow from the internal data, therefore the successful parser call is repeated
+ /// once on the final data, so the parser should not have side effects.
+ pub fn slurp_nom<'s, P>(
+  &'s mut self,
+  buffersize: usize,
+  mut parser: P,
+ ) -> Result<NomResult<'s, P>, Error>
+ where
+  P: for<'i> Parser<&'i [u8]>,
+ {
+  if 0 == buffersize {
+   return Err(Error::Msg("buffersize 0 leads to an infinite loop"));
+  }
+  loop {
+   let needed = match parser.parse(self.get()) {
+    Err(Err::Incomplete(needed)) => needed,
+    _ => break,
+   };
+   let available = self.available_bytes();
+   let bytecount = match needed {
+    Needed::Size(n) => available + n.get(),
+    Needed::Unknown => max(buffersize, 2 * available),
+   };
+   if self.slurp(bytecount)? == available && self.eof {
+    break;
+   }
+  }
+
+  let eof = self.eof;
+  let Self { v, pos, .. } = self;
+  let input: &'s [u8] = &v[*pos..];
+  let res = if eof { parser.parse_complete(input) } else { parser.parse(input) };
+  Ok(match res {
+   Ok((rest, o)) => {
+    *pos += input.len() - rest.len();
+    Ok(o)
+   }
+   Err(Err::Incomplete(_)) => {
+    Err(Err::Error(ParseError::from_error_kind(input, ErrorKind::Complete)))
+   }
+   Err(e) => Err(e),
+  })
+ }
+}
+
+/// the outcome of a nom parser applied by BlockWiseReader::slurp_nom
+pub type NomResult<'s, P> =
+ Result<<P as Parser<&'s [u8]>>::Output, Err<<P as Parser<&'s [u8]>>::Error>>;
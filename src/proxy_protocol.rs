@@ -0,0 +1,288 @@
+/*!
+Detection and parsing of PROXY protocol headers in front of a TCP stream.
+
+Proxies like HAProxy prepend the original source and destination addresses to a connection, as
+text line in version 1 or as binary header with TLVs in version 2. read_proxy_header() reads only
+as many bytes as needed to decide whether such a header is present. If there is none, pos remains
+unaltered, else pos is set to the first byte of the application data.
+
+```rust
+use stringreader::StringReader;
+use blockwise_reader::BlockWiseReader;
+use blockwise_reader::proxy_protocol::{read_proxy_header, ProxyAddresses};
+
+let sr = StringReader::new("PROXY TCP4 192.0.2.1 198.51.100.7 56324 443\r\nGET / HTTP/1.1\r\n");
+let mut bwr = BlockWiseReader::new(Box::new(sr));
+let header = read_proxy_header(&mut bwr).unwrap().unwrap();
+assert_eq!(
+ ProxyAddresses::Inet {
+  source: "192.0.2.1:56324".parse().unwrap(),
+  destination: "198.51.100.7:443".parse().unwrap(),
+ },
+ header.addresses
+);
+bwr.slurp(3).unwrap();
+assert!(bwr.get().starts_with(b"GET"));
+
+let sr = StringReader::new("GET / HTTP/1.1\r\n");
+let mut bwr = BlockWiseReader::new(Box::new(sr));
+assert!(read_proxy_header(&mut bwr).unwrap().is_none());
+assert_eq!(0, bwr.pos_get());
+```
+*/
+
+use crate::{BlockWiseReader, Error};
+use std::{
+ cmp::min,
+ net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+/// the begin of a version 1 header
+pub const V1_PREFIX: &[u8] = b"PROXY ";
+/// the signature of a version 2 header
+pub const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+/// the maximal size of a version 1 header including CRLF
+pub const V1_MAX_SIZE: usize = 107;
+
+pub const PP2_TYPE_ALPN: u8 = 0x01;
+pub const PP2_TYPE_AUTHORITY: u8 = 0x02;
+pub const PP2_TYPE_CRC32C: u8 = 0x03;
+pub const PP2_TYPE_NOOP: u8 = 0x04;
+pub const PP2_TYPE_UNIQUE_ID: u8 = 0x05;
+pub const PP2_TYPE_SSL: u8 = 0x20;
+pub const PP2_TYPE_NETNS: u8 = 0x30;
+
+/// the size of a unix socket address in a version 2 header
+const UNIX_ADDRESS_SIZE: usize = 108;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Command {
+ /// the connection was established by the proxy itself, like for health checks, the addresses are unknown
+ Local,
+ /// the connection was relayed for a client
+ Proxy,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Transport {
+ Unspec,
+ Stream,
+ Datagram,
+}
+
+/// the addresses of the relayed connection
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ProxyAddresses {
+ Unknown,
+ Inet {
+  source: SocketAddr,
+  destination: SocketAddr,
+ },
+ /// the paths of unix sockets without the trailing zero bytes
+ Unix {
+  source: Vec<u8>,
+  destination: Vec<u8>,
+ },
+}
+
+/// a type-length-value field of a version 2 header
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Tlv {
+ pub tlv_type: u8,
+ pub value: Vec<u8>,
+}
+
+/// a parsed PROXY protocol header
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ProxyHeader {
+ /// 1 or 2
+ pub version: u8,
+ pub command: Command,
+ pub transport: Transport,
+ pub addresses: ProxyAddresses,
+ /// the TLVs of a version 2 header in the stored order
+ pub tlvs: Vec<Tlv>,
+}
+
+impl ProxyHeader {
+ /// the value of the first TLV of tlv_type
+ pub fn tlv(&self, tlv_type: u8) -> Option<&[u8]> {
+  self
+   .tlvs
+   .iter()
+   .find(|t| t.tlv_type == tlv_type)
+   .map(|t| &t.value[..])
+ }
+}
+
+/// true if data and the signature are equal up to the length of the shorter one
+fn is_prefix(data: &[u8], signature: &[u8]) -> bool {
+ let n = min(data.len(), signature.len());
+ data[..n] == signature[..n]
+}
+
+/// Reads a PROXY protocol header of version 1 or 2 from pos on.
+/// Returns None and leaves pos unaltered if the data don't begin with a header, only the bytes up to the
+/// first difference from the signatures are slurped. On success pos is set behind the header,
+/// on errors pos remains unaltered.
+pub fn read_proxy_header(bwr: &mut BlockWiseReader<'_>) -> Result<Option<ProxyHeader>, Error> {
+ let mut bytecount = 1;
+ loop {
+  let available = min(bwr.slurp_exact(bytecount)?, V2_SIGNATURE.len());
+  let data = &bwr.get()[..available];
+  if data.starts_with(V1_PREFIX) {
+   return read_v1(bwr).map(Some);
+  }
+  if data.starts_with(V2_SIGNATURE) {
+   return read_v2(bwr).map(Some);
+  }
+  if available < bytecount || !(is_prefix(data, V1_PREFIX) || is_prefix(data, V2_SIGNATURE)) {
+   return Ok(None);
+  }
+  bytecount = available + 1;
+ }
+}
+
+fn read_v1(bwr: &mut BlockWiseReader<'_>) -> Result<ProxyHeader, Error> {
+ let mut idx = V1_PREFIX.len();
+ let end = loop {
+  if idx + 2 > V1_MAX_SIZE || bwr.slurp_exact(idx + 2)? < idx + 2 {
+   return Err(Error::Msg("invalid proxy protocol v1 header"));
+  }
+  if b"\r\n" == &bwr.get()[idx..idx + 2] {
+   break idx;
+  }
+  idx += 1;
+ };
+ let line = std::str::from_utf8(&bwr.get()[V1_PREFIX.len()..end])
+  .map_err(|_| Error::Msg("invalid proxy protocol v1 header"))?;
+ let parts: Vec<&str> = line.split(' ').collect();
+ let header = match parts[..] {
+  ["UNKNOWN", ..] => ProxyHeader {
+   version: 1,
+   command: Command::Proxy,
+   transport: Transport::Unspec,
+   addresses: ProxyAddresses::Unknown,
+   tlvs: vec![],
+  },
+  [protocol @ ("TCP4" | "TCP6"), source, destination, source_port, destination_port] => {
+   let ip = |s: &str| -> Result<IpAddr, Error> {
+    let ip = match protocol {
+     "TCP4" => s.parse::<Ipv4Addr>().map(IpAddr::V4),
+     _ => s.parse::<Ipv6Addr>().map(IpAddr::V6),
+    };
+    ip.map_err(|_| Error::Msg("invalid proxy protocol v1 address"))
+   };
+   let port = |s: &str| -> Result<u16, Error> {
+    if s.starts_with('0') && s.len() > 1 || !s.bytes().all(|b| b.is_ascii_digit()) {
+     return Err(Error::Msg("invalid proxy protocol v1 port"));
+    }
+    s.parse()
+     .map_err(|_| Error::Msg("invalid proxy protocol v1 port"))
+   };
+   ProxyHeader {
+    version: 1,
+    command: Command::Proxy,
+    transport: Transport::Stream,
+    addresses: ProxyAddresses::Inet {
+     source: SocketAddr::new(ip(source)?, port(source_port)?),
+     destination: SocketAddr::new(ip(destination)?, port(destination_port)?),
+    },
+    tlvs: vec![],
+   }
+  }
+  _ => return Err(Error::Msg("invalid proxy protocol v1 header")),
+ };
+ bwr.pos_add(end + 2);
+ Ok(header)
+}
+
+fn read_v2(bwr: &mut BlockWiseReader<'_>) -> Result<ProxyHeader, Error> {
+ let header_size = V2_SIGNATURE.len() + 4;
+ if bwr.slurp_exact(header_size)? < header_size {
+  return Err(Error::Msg("unexpected end of file in proxy protocol v2 header"));
+ }
+ let fixed = &bwr.get()[V2_SIGNATURE.len()..header_size];
+ if 0x20 != fixed[0] & 0xf0 {
+  return Err(Error::Msg("unsupported proxy protocol version"));
+ }
+ let command = match fixed[0] & 0x0f {
+  0 => Command::Local,
+  1 => Command::Proxy,
+  _ => return Err(Error::Msg("invalid proxy protocol v2 command")),
+ };
+ let transport = match fixed[1] & 0x0f {
+  0 => Transport::Unspec,
+  1 => Transport::Stream,
+  2 => Transport::Datagram,
+  _ => return Err(Error::Msg("invalid proxy protocol v2 transport")),
+ };
+ let family = fixed[1] >> 4;
+ let size = header_size + u16::from_be_bytes([fixed[2], fixed[3]]) as usize;
+ if bwr.slurp_exact(size)? < size {
+  return Err(Error::Msg("unexpected end of file in proxy protocol v2 header"));
+ }
+ let data = &bwr.get()[header_size..size];
+ let address_size = match family {
+  0 => 0,
+  1 => 12,
+  2 => 36,
+  3 => 2 * UNIX_ADDRESS_SIZE,
+  _ => return Err(Error::Msg("invalid proxy protocol v2 address family")),
+ };
+ if data.len() < address_size {
+  return Err(Error::Msg("invalid proxy protocol v2 address length"));
+ }
+ let (address, mut rest) = data.split_at(address_size);
+ let addresses = match (command, family) {
+  (Command::Local, _) | (_, 0) => ProxyAddresses::Unknown,
+  (_, 1) => {
+   let ip =
+    |at: usize| IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(&address[at..at + 4]).unwrap()));
+   let port = |at: usize| u16::from_be_bytes([address[at], address[at + 1]]);
+   ProxyAddresses::Inet {
+    source: SocketAddr::new(ip(0), port(8)),
+    destination: SocketAddr::new(ip(4), port(10)),
+   }
+  }
+  (_, 2) => {
+   let ip =
+    |at: usize| IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(&address[at..at + 16]).unwrap()));
+   let port = |at: usize| u16::from_be_bytes([address[at], address[at + 1]]);
+   ProxyAddresses::Inet {
+    source: SocketAddr::new(ip(0), port(32)),
+    destination: SocketAddr::new(ip(16), port(34)),
+   }
+  }
+  _ => {
+   let path = |a: &[u8]| a[..a.iter().position(|b| 0 == *b).unwrap_or(a.len())].to_vec();
+   ProxyAddresses::Unix {
+    source: path(&address[..UNIX_ADDRESS_SIZE]),
+    destination: path(&address[UNIX_ADDRESS_SIZE..]),
+   }
+  }
+ };
+ let mut tlvs = vec![];
+ while !rest.is_empty() {
+  if rest.len() < 3 {
+   return Err(Error::Msg("invalid proxy protocol v2 tlv"));
+  }
+  let len = 3 + u16::from_be_bytes([rest[1], rest[2]]) as usize;
+  if rest.len() < len {
+   return Err(Error::Msg("invalid proxy protocol v2 tlv"));
+  }
+  tlvs.push(Tlv {
+   tlv_type: rest[0],
+   value: rest[3..len].to_vec(),
+  });
+  rest = &rest[len..];
+ }
+ bwr.pos_add(size);
+ Ok(ProxyHeader {
+  version: 2,
+  command,
+  transport,
+  addresses,
+  tlvs,
+ })
+}
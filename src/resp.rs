@@ -0,0 +1,325 @@
+/*!
+Parsing of RESP2 and RESP3 frames as used by Redis.
+
+A frame is read blockwise up to its end, also if it is nested, and afterwards pos is set behind it.
+read_frame() returns the raw bytes of the frame for forwarding or recording, read_value() parses it
+into a RespValue which borrows from the data of the BlockWiseReader. Inline commands, which are
+plain lines like they are typed into telnet, are accepted at the top level.
+
+```rust
+use stringreader::StringReader;
+use blockwise_reader::BlockWiseReader;
+use blockwise_reader::resp::{read_frame, read_value, RespLimits, RespValue};
+
+let sr = StringReader::new("*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n+OK\r\nPING\r\n");
+let mut bwr = BlockWiseReader::new(Box::new(sr));
+let limits = RespLimits::default();
+
+assert_eq!(
+ Some(RespValue::Array(vec![RespValue::BulkString(b"GET"), RespValue::BulkString(b"key")])),
+ read_value(&mut bwr, 1024, &limits).unwrap()
+);
+assert_eq!(Some(&b"+OK\r\n"[..]), read_frame(&mut bwr, 1024, &limits).unwrap());
+assert_eq!(
+ Some(RespValue::Inline(vec![b"PING"])),
+ read_value(&mut bwr, 1024, &limits).unwrap()
+);
+assert_eq!(None, read_value(&mut bwr, 1024, &limits).unwrap());
+```
+*/
+
+use crate::{BlockWiseReader, Error};
+use std::cmp::max;
+
+/// limits which are checked while reading a frame
+#[derive(Clone, Copy, Debug)]
+pub struct RespLimits {
+ /// maximal size of a line including CRLF, this applies also to inline commands
+ pub max_line_size: usize,
+ /// maximal length of a bulk string, a bulk error or a verbatim string
+ pub max_bulk_size: usize,
+ /// maximal nesting depth of aggregates, attributes included
+ pub max_depth: usize,
+}
+
+impl Default for RespLimits {
+ fn default() -> Self {
+  Self {
+   max_line_size: 64 * 1024,
+   max_bulk_size: 512 * 1024 * 1024,
+   max_depth: 128,
+  }
+ }
+}
+
+/// a parsed RESP value
+#[derive(Clone, PartialEq, Debug)]
+pub enum RespValue<'v> {
+ SimpleString(&'v [u8]),
+ Error(&'v [u8]),
+ Integer(i64),
+ BulkString(&'v [u8]),
+ Array(Vec<RespValue<'v>>),
+ /// the RESP3 null as well as the RESP2 null bulk string and null array
+ Null,
+ Boolean(bool),
+ Double(f64),
+ /// the decimal digits with an optional sign
+ BigNumber(&'v [u8]),
+ BulkError(&'v [u8]),
+ /// format is the three byte encoding like txt or mkd
+ VerbatimString {
+  format: &'v [u8],
+  data: &'v [u8],
+ },
+ /// the key value pairs in the stored order
+ Map(Vec<(RespValue<'v>, RespValue<'v>)>),
+ Set(Vec<RespValue<'v>>),
+ Push(Vec<RespValue<'v>>),
+ /// the attributes and the value they belong to
+ Attribute {
+  attributes: Vec<(RespValue<'v>, RespValue<'v>)>,
+  value: Box<RespValue<'v>>,
+ },
+ /// the arguments of an inline command, split at spaces and tabs
+ Inline(Vec<&'v [u8]>),
+}
+
+/// the meaning of a line
+enum Header<'l> {
+ Scalar(RespValue<'l>),
+ /// a blob of the given length follows
+ Blob(u8, usize),
+ /// the given number of elements follow
+ Aggregate(u8, u64),
+}
+
+/// true for the first bytes of RESP2 and RESP3 types
+fn is_type(b: u8) -> bool {
+ b"+-:$*_#,(!=%~>|".contains(&b)
+}
+
+/// Reads a frame from pos on, reading ahead in buffersize steps, and returns its bytes.
+/// Returns None if no bytes are available.
+/// On success pos is set behind the frame, otherwise pos remains unaltered.
+/// Streamed strings and aggregates of RESP3 are not supported.
+pub fn read_frame<'f>(
+ bwr: &'f mut BlockWiseReader<'_>,
+ buffersize: usize,
+ limits: &RespLimits,
+) -> Result<Option<&'f [u8]>, Error> {
+ let Some(len) = frame_len(bwr, max(buffersize, 1), limits)? else {
+  return Ok(None);
+ };
+ bwr.pos_add(len);
+ Ok(Some(&bwr.get_back(len)[..len]))
+}
+
+/// Reads a frame like read_frame() and parses it.
+pub fn read_value<'v>(
+ bwr: &'v mut BlockWiseReader<'_>,
+ buffersize: usize,
+ limits: &RespLimits,
+) -> Result<Option<RespValue<'v>>, Error> {
+ let Some(frame) = read_frame(bwr, buffersize, limits)? else {
+  return Ok(None);
+ };
+ if !is_type(frame[0]) {
+  let line = &frame[..frame.len() - 2];
+  return Ok(Some(RespValue::Inline(
+   line
+    .split(|b| b" \t".contains(b))
+    .filter(|arg| !arg.is_empty())
+    .collect(),
+  )));
+ }
+ parse_value(frame, &mut 0).map(Some)
+}
+
+/// the length of the frame at pos, checking it completely
+fn frame_len(
+ bwr: &mut BlockWiseReader<'_>,
+ buffersize: usize,
+ limits: &RespLimits,
+) -> Result<Option<usize>, Error> {
+ if 0 == bwr.slurp_exact(1)? {
+  return Ok(None);
+ }
+ // the number of missing elements of the open aggregates, and whether it is an attribute
+ let mut open: Vec<(u64, bool)> = vec![];
+ let mut at = 0;
+ loop {
+  let Some(end) = bwr.slurp_search_limited(
+   buffersize,
+   at,
+   b"\r\n",
+   limits.max_line_size,
+   "resp line exceeds max_line_size",
+  )?
+  else {
+   return Err(Error::Msg("unexpected end of file in resp frame"));
+  };
+  let line = &bwr.get()[at..end - 2];
+  if open.is_empty() && 0 == at && !line.first().is_some_and(|b| is_type(*b)) {
+   return Ok(Some(end));
+  }
+  at = end;
+  match parse_header(line)? {
+   Header::Scalar(_) => {}
+   Header::Blob(t, len) => {
+    if len > limits.max_bulk_size {
+     return Err(Error::Msg("resp bulk string exceeds max_bulk_size"));
+    }
+    let blob_end = at + len + 2;
+    if bwr.slurp_exact(blob_end)? < blob_end {
+     return Err(Error::Msg("unexpected end of file in resp frame"));
+    }
+    if &bwr.get()[blob_end - 2..blob_end] != b"\r\n" {
+     return Err(Error::Msg("missing CRLF behind resp bulk string"));
+    }
+    blob_value(t, &bwr.get()[at..blob_end - 2])?;
+    at = blob_end;
+   }
+   Header::Aggregate(t, n) => {
+    if open.len() >= limits.max_depth {
+     return Err(Error::Msg("resp frame exceeds max_depth"));
+    }
+    if n > 0 {
+     open.push((n, b'|' == t));
+     continue;
+    }
+    if b'|' == t {
+     continue;
+    }
+   }
+  }
+  // the element is complete, which may complete the enclosing aggregates too
+  loop {
+   let Some((missing, attribute)) = open.last_mut() else {
+    return Ok(Some(at));
+   };
+   *missing -= 1;
+   if *missing > 0 {
+    break;
+   }
+   let attribute = *attribute;
+   open.pop();
+   if attribute {
+    // the value the attributes belong to follows
+    break;
+   }
+  }
+ }
+}
+
+/// parses the line of a value without CRLF
+fn parse_header(line: &[u8]) -> Result<Header<'_>, Error> {
+ let Some((&t, content)) = line.split_first() else {
+  return Err(Error::Msg("invalid resp type"));
+ };
+ let scalar = match t {
+  b'+' => RespValue::SimpleString(content),
+  b'-' => RespValue::Error(content),
+  b':' => {
+   RespValue::Integer(parse_number::<i64>(content).ok_or(Error::Msg("invalid resp integer"))?)
+  }
+  b'_' if content.is_empty() => RespValue::Null,
+  b'#' => match content {
+   b"t" => RespValue::Boolean(true),
+   b"f" => RespValue::Boolean(false),
+   _ => return Err(Error::Msg("invalid resp boolean")),
+  },
+  b'_' => return Err(Error::Msg("invalid resp null")),
+  b',' => RespValue::Double(parse_number(content).ok_or(Error::Msg("invalid resp double"))?),
+  b'(' => {
+   let digits = content.strip_prefix(b"-").unwrap_or(content);
+   if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+    return Err(Error::Msg("invalid resp big number"));
+   }
+   RespValue::BigNumber(content)
+  }
+  b'$' | b'*' if content == b"-1" => RespValue::Null,
+  b'$' | b'!' | b'=' => {
+   let len = parse_length(content)?;
+   return Ok(Header::Blob(t, usize::try_from(len).unwrap_or(usize::MAX)));
+  }
+  b'*' | b'~' | b'>' => return Ok(Header::Aggregate(t, parse_length(content)?)),
+  b'%' | b'|' => {
+   let pairs = parse_length(content)?;
+   let n = pairs
+    .checked_mul(2)
+    .ok_or(Error::Msg("invalid resp length"))?;
+   return Ok(Header::Aggregate(t, n));
+  }
+  _ => return Err(Error::Msg("invalid resp type")),
+ };
+ Ok(Header::Scalar(scalar))
+}
+
+fn parse_number<T: std::str::FromStr>(content: &[u8]) -> Option<T> {
+ std::str::from_utf8(content).ok()?.parse().ok()
+}
+
+/// a length consists of decimal digits only
+fn parse_length(content: &[u8]) -> Result<u64, Error> {
+ if content.is_empty() || !content.iter().all(u8::is_ascii_digit) {
+  return Err(Error::Msg("invalid resp length"));
+ }
+ parse_number(content).ok_or(Error::Msg("invalid resp length"))
+}
+
+fn blob_value(t: u8, data: &[u8]) -> Result<RespValue<'_>, Error> {
+ Ok(match t {
+  b'$' => RespValue::BulkString(data),
+  b'!' => RespValue::BulkError(data),
+  _ => {
+   if data.len() < 4 || b':' != data[3] {
+    return Err(Error::Msg("invalid resp verbatim string"));
+   }
+   RespValue::VerbatimString {
+    format: &data[..3],
+    data: &data[4..],
+   }
+  }
+ })
+}
+
+/// parses the value at the given index of a frame which was checked by frame_len()
+fn parse_value<'v>(frame: &'v [u8], at: &mut usize) -> Result<RespValue<'v>, Error> {
+ let Some(len) = frame[*at..].windows(2).position(|w| w == b"\r\n") else {
+  return Err(Error::Msg("unexpected end of file in resp frame"));
+ };
+ let line = &frame[*at..*at + len];
+ *at += len + 2;
+ let (t, n) = match parse_header(line)? {
+  Header::Scalar(value) => return Ok(value),
+  Header::Blob(t, len) => {
+   let data = &frame[*at..*at + len];
+   *at += len + 2;
+   return blob_value(t, data);
+  }
+  Header::Aggregate(t, n) => (t, n),
+ };
+ let mut elements = vec![];
+ for _ in 0..n {
+  elements.push(parse_value(frame, at)?);
+ }
+ let pairs = |elements: Vec<RespValue<'v>>| {
+  let mut it = elements.into_iter();
+  let mut pairs = vec![];
+  while let (Some(key), Some(value)) = (it.next(), it.next()) {
+   pairs.push((key, value));
+  }
+  pairs
+ };
+ Ok(match t {
+  b'*' => RespValue::Array(elements),
+  b'~' => RespValue::Set(elements),
+  b'>' => RespValue::Push(elements),
+  b'%' => RespValue::Map(pairs(elements)),
+  _ => RespValue::Attribute {
+   attributes: pairs(elements),
+   value: Box::new(parse_value(frame, at)?),
+  },
+ })
+}
@@ -0,0 +1,199 @@
+/*!
+Parsing of Server-Sent Events streams (text/event-stream).
+
+The SseReader reads the stream line by line, lines may end with CRLF, LF or CR. The fields of the
+lines are collected until an empty line dispatches the event, the data lines are joined by LF.
+Comments and unknown fields are ignored, like the event-stream interpretation of the HTML standard
+describes it. Consumed lines are removed from the BlockWiseReader, so the memory stays bounded by the
+maximal event size also for endless streams.
+
+```rust
+use stringreader::StringReader;
+use blockwise_reader::BlockWiseReader;
+use blockwise_reader::sse::SseReader;
+
+let sr = StringReader::new(": keep-alive\n\nevent: update\ndata: {\"a\":1}\r\ndata:second line\rid: 7\n\n");
+let mut bwr = BlockWiseReader::new(Box::new(sr));
+let mut sse = SseReader::new(&mut bwr, 1024, 64 * 1024);
+
+let event = sse.next_event().unwrap().unwrap();
+assert_eq!("update", event.event);
+assert_eq!("{\"a\":1}\nsecond line", event.data);
+assert_eq!("7", event.id);
+assert!(sse.next_event().unwrap().is_none());
+```
+*/
+
+use crate::{BlockWiseReader, Error};
+
+/// a dispatched event
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Event {
+ /// the event type, "message" if no event field was given
+ pub event: String,
+ /// the data lines joined by LF
+ pub data: String,
+ /// the last event ID, it stays valid for the following events until an id field changes it
+ pub id: String,
+ /// the reconnection time in milliseconds of the last valid retry field so far
+ pub retry: Option<u64>,
+}
+
+/// Reads the events of a Server-Sent Events stream from the current pos of a BlockWiseReader on.
+pub struct SseReader<'r, 'a> {
+ bwr: &'r mut BlockWiseReader<'a>,
+ buffersize: usize,
+ max_event_size: usize,
+ /// true after a byte order mark at the begin of the stream was checked
+ started: bool,
+ /// the size of the lines of the unfinished event
+ event_size: usize,
+ event: String,
+ data: String,
+ last_event_id: String,
+ retry: Option<u64>,
+}
+
+impl<'r, 'a> SseReader<'r, 'a> {
+ /// Creates a SseReader which reads ahead in buffersize steps.
+ /// The lines of an event including comments and line terminators may have max_event_size bytes together.
+ pub fn new(bwr: &'r mut BlockWiseReader<'a>, buffersize: usize, max_event_size: usize) -> Self {
+  Self {
+   bwr,
+   buffersize: buffersize.max(1),
+   max_event_size,
+   started: false,
+   event_size: 0,
+   event: String::new(),
+   data: String::new(),
+   last_event_id: String::new(),
+   retry: None,
+  }
+ }
+
+ /// the last event ID which was set by an id field
+ pub fn last_event_id(&self) -> &str {
+  &self.last_event_id
+ }
+
+ /// the reconnection time in milliseconds which was set by a retry field
+ pub fn retry(&self) -> Option<u64> {
+  self.retry
+ }
+
+ /// Reads lines up to the next event which has data and returns it.
+ /// Returns None at the end of the stream, an unfinished event in front of it is discarded.
+ pub fn next_event(&mut self) -> Result<Option<Event>, Error> {
+  if !self.started {
+   if self.bwr.slurp_exact(3)? >= 3 && self.bwr.get().starts_with(b"\xef\xbb\xbf") {
+    self.bwr.pos_add(3);
+    self.bwr.pos_drop();
+   }
+   self.started = true;
+  }
+  loop {
+   let Some((len, terminator)) = self.next_line()? else {
+    self.event_size = 0;
+    self.event.clear();
+    self.data.clear();
+    return Ok(None);
+   };
+   let line = &self.bwr.get()[..len];
+   if line.is_empty() {
+    let event = self.dispatch();
+    self.consume(terminator);
+    match event {
+     Some(event) => return Ok(Some(event)),
+     None => continue,
+    }
+   }
+   if b':' != line[0] {
+    let (name, value) = match line.iter().position(|b| b':' == *b) {
+     Some(idx) => {
+      let value = &line[idx + 1..];
+      (&line[..idx], value.strip_prefix(b" ").unwrap_or(value))
+     }
+     None => (line, &b""[..]),
+    };
+    let value = String::from_utf8_lossy(value);
+    match name {
+     b"event" => self.event = value.into_owned(),
+     b"data" => {
+      self.data.push_str(&value);
+      self.data.push('\n');
+     }
+     b"id" if !value.contains('\0') => self.last_event_id = value.into_owned(),
+     b"retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+      self.retry = value.parse().ok();
+     }
+     _ => {}
+    }
+   }
+   self.event_size += len + terminator;
+   self.consume(len + terminator);
+  }
+ }
+
+ /// returns the collected event if it has data and resets the fields
+ fn dispatch(&mut self) -> Option<Event> {
+  self.event_size = 0;
+  let event = std::mem::take(&mut self.event);
+  let mut data = std::mem::take(&mut self.data);
+  if data.is_empty() {
+   return None;
+  }
+  data.pop();
+  Some(Event {
+   event: if event.is_empty() { "message".to_string() } else { event },
+   data,
+   id: self.last_event_id.clone(),
+   retry: self.retry,
+  })
+ }
+
+ fn consume(&mut self, n: usize) {
+  self.bwr.pos_add(n);
+  self.bwr.pos_drop();
+ }
+
+ /// Searches the end of the line at pos and returns the length of the line and of its terminator.
+ /// Returns None if the stream ends without a line terminator.
+ fn next_line(&mut self) -> Result<Option<(usize, usize)>, Error> {
+  let max_len = self.max_event_size.saturating_sub(self.event_size);
+  let mut searched = 0;
+  loop {
+   let data = self.bwr.get();
+   if let Some(idx) = data[searched..]
+    .iter()
+    .position(|b| b'\r' == *b || b'\n' == *b)
+    .map(|idx| searched + idx)
+   {
+    let terminator = match data.get(idx + 1) {
+     _ if b'\n' == data[idx] => Some(1),
+     Some(b'\n') => Some(2),
+     Some(_) => Some(1),
+     // a CR at the end of the available bytes may be followed by a LF
+     None if self.bwr.eof => Some(1),
+     None => None,
+    };
+    if let Some(terminator) = terminator {
+     if idx + terminator > max_len {
+      return Err(Error::Msg("sse event exceeds max_event_size"));
+     }
+     return Ok(Some((idx, terminator)));
+    }
+    searched = idx;
+   } else {
+    searched = data.len();
+   }
+   if searched >= max_len {
+    return Err(Error::Msg("sse event exceeds max_event_size"));
+   }
+   if self.bwr.eof {
+    return Ok(None);
+   }
+   let available = data.len();
+   self.bwr.slurp(available + self.buffersize)?;
+  }
+ }
+}
@@ -0,0 +1,328 @@
+/*!
+Walking over the entries of a tar archive without extracting it.
+
+Only the 512 byte headers are parsed, the data of the entries is skipped by pos_skip() if it is not
+read by Read, so the memory stays bounded also for huge entries. Ustar, GNU long names and
+PAX extended headers are supported, the walk ends at the two zero blocks.
+
+```rust,no_run
+use blockwise_reader::BlockWiseReader;
+use blockwise_reader::tar::TarReader;
+
+# fn main() -> Result<(), blockwise_reader::Error> {
+let file = std::fs::File::open("layer.tar")?;
+let mut bwr = BlockWiseReader::new(Box::new(file));
+let mut tar = TarReader::new(&mut bwr, 64 * 1024, 1024 * 1024);
+while let Some(entry) = tar.next_entry()? {
+ println!("{} {}", String::from_utf8_lossy(&entry.name), entry.size);
+}
+# Ok(())
+# }
+```
+*/
+
+use crate::{BlockWiseReader, Error};
+use std::io::Read;
+
+const BLOCK: usize = 512;
+
+/// key and value of PAX records
+type PaxRecords = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// the type of a tar entry
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EntryType {
+ Regular,
+ HardLink,
+ Symlink,
+ CharDevice,
+ BlockDevice,
+ Directory,
+ Fifo,
+ Contiguous,
+ Other(u8),
+}
+
+impl EntryType {
+ fn from_flag(flag: u8) -> Self {
+  match flag {
+   b'0' | 0 => EntryType::Regular,
+   b'1' => EntryType::HardLink,
+   b'2' => EntryType::Symlink,
+   b'3' => EntryType::CharDevice,
+   b'4' => EntryType::BlockDevice,
+   b'5' => EntryType::Directory,
+   b'6' => EntryType::Fifo,
+   b'7' => EntryType::Contiguous,
+   x => EntryType::Other(x),
+  }
+ }
+}
+
+/// An entry of a tar archive, long names and PAX values are already applied.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TarEntry {
+ pub name: Vec<u8>,
+ pub link_name: Vec<u8>,
+ pub entry_type: EntryType,
+ pub size: u64,
+ pub mode: u32,
+ pub uid: u64,
+ pub gid: u64,
+ pub mtime: u64,
+ /// absolute offset of the ustar header of the entry in the stream
+ pub header_offset: u64,
+ /// absolute offset of the data of the entry in the stream
+ pub data_offset: u64,
+}
+
+/// Reads the entries of a tar archive from the current pos of a BlockWiseReader on.
+/// The data of the current entry can be read by Read, the rest of it is skipped by next_entry().
+pub struct TarReader<'r, 'a> {
+ bwr: &'r mut BlockWiseReader<'a>,
+ buffersize: usize,
+ max_extension_size: usize,
+ /// unread data bytes of the current entry
+ remaining: u64,
+ /// padding bytes behind the data of the current entry
+ padding: u64,
+ /// the records of PAX global headers, applied to all following entries
+ global: PaxRecords,
+ done: bool,
+}
+
+/// values of extension headers for the next entry
+#[derive(Default)]
+struct Extensions {
+ long_name: Option<Vec<u8>>,
+ long_link_name: Option<Vec<u8>>,
+ pax: PaxRecords,
+}
+
+impl<'r, 'a> TarReader<'r, 'a> {
+ /// Creates a TarReader which reads ahead in buffersize steps.
+ /// GNU long names and PAX extended headers are limited to max_extension_size bytes.
+ pub fn new(
+  bwr: &'r mut BlockWiseReader<'a>,
+  buffersize: usize,
+  max_extension_size: usize,
+ ) -> Self {
+  Self {
+   bwr,
+   buffersize: buffersize.max(1),
+   max_extension_size,
+   remaining: 0,
+   padding: 0,
+   global: vec![],
+   done: false,
+  }
+ }
+
+ /// Returns the next entry or None at the end of the archive, then pos is behind the zero blocks.
+ /// The rest of the data of the previous entry is skipped.
+ pub fn next_entry(&mut self) -> Result<Option<TarEntry>, Error> {
+  if self.done {
+   return Ok(None);
+  }
+  self.skip_data()?;
+  let mut ext = Extensions::default();
+  loop {
+   let available = self.bwr.slurp_exact(BLOCK)?;
+   if 0 == available
+    && ext.long_name.is_none()
+    && ext.long_link_name.is_none()
+    && ext.pax.is_empty()
+   {
+    self.done = true;
+    return Ok(None);
+   }
+   if available < BLOCK {
+    return Err(Error::Msg("unexpected end of file in tar header"));
+   }
+   let header_offset = self.bwr.pos_absolute();
+   let header = &self.bwr.get()[..BLOCK];
+   if header.iter().all(|b| *b == 0) {
+    self.bwr.pos_add(BLOCK);
+    if self.bwr.slurp_exact(BLOCK)? >= BLOCK && self.bwr.get()[..BLOCK].iter().all(|b| *b == 0) {
+     self.bwr.pos_add(BLOCK);
+    }
+    self.bwr.pos_drop();
+    self.done = true;
+    return Ok(None);
+   }
+   if checksum(header) != parse_number(&header[148..156])? {
+    return Err(Error::Msg("invalid tar header checksum"));
+   }
+   let size = parse_number(&header[124..136])?;
+   let flag = header[156];
+   if let b'L' | b'K' | b'x' | b'g' = flag {
+    if size > self.max_extension_size as u64 {
+     return Err(Error::Msg("tar extension header exceeds max_extension_size"));
+    }
+    self.bwr.pos_add(BLOCK);
+    let size = size as usize;
+    if self.bwr.slurp_exact(size)? < size {
+     return Err(Error::Msg("unexpected end of file in tar extension header"));
+    }
+    let data = &self.bwr.get()[..size];
+    match flag {
+     b'L' => ext.long_name = Some(until_nul(data).to_vec()),
+     b'K' => ext.long_link_name = Some(until_nul(data).to_vec()),
+     b'x' => ext.pax.extend(parse_pax(data)?),
+     _ => self.global.extend(parse_pax(data)?),
+    }
+    self.bwr.pos_add(size);
+    self.remaining = 0;
+    self.padding = padding(size as u64);
+    self.skip_data()?;
+    continue;
+   }
+
+   let mut entry = TarEntry {
+    name: header_name(header),
+    link_name: until_nul(&header[157..257]).to_vec(),
+    entry_type: EntryType::from_flag(flag),
+    size,
+    mode: parse_number(&header[100..108])? as u32,
+    uid: parse_number(&header[108..116])?,
+    gid: parse_number(&header[116..124])?,
+    mtime: parse_number(&header[136..148])?,
+    header_offset,
+    data_offset: header_offset + BLOCK as u64,
+   };
+   if let Some(name) = ext.long_name {
+    entry.name = name;
+   }
+   if let Some(link_name) = ext.long_link_name {
+    entry.link_name = link_name;
+   }
+   for (key, value) in self.global.iter().chain(ext.pax.iter()) {
+    apply_pax(&mut entry, key, value)?;
+   }
+   // the data is padded to a full block
+   if entry.size > u64::MAX - (BLOCK as u64 - 1) {
+    return Err(Error::Msg("invalid tar entry size"));
+   }
+   self.bwr.pos_add(BLOCK);
+   self.bwr.pos_drop();
+   self.remaining = entry.size;
+   self.padding = padding(entry.size);
+   return Ok(Some(entry));
+  }
+ }
+
+ /// skips the unread data and the padding of the current entry
+ fn skip_data(&mut self) -> Result<(), Error> {
+  let count = self.remaining + self.padding;
+  if self.bwr.pos_skip(count, self.buffersize)? < count {
+   return Err(Error::Msg("unexpected end of file in tar entry data"));
+  }
+  self.remaining = 0;
+  self.padding = 0;
+  Ok(())
+ }
+}
+
+impl Read for TarReader<'_, '_> {
+ fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+  self.bwr.read_payload(
+   self.buffersize,
+   &mut self.remaining,
+   buf,
+   Some("unexpected end of file in tar entry data"),
+  )
+ }
+}
+
+/// the sum of all header bytes, the checksum field counts as spaces
+fn checksum(header: &[u8]) -> u64 {
+ header
+  .iter()
+  .enumerate()
+  .map(|(i, b)| if (148..156).contains(&i) { b' ' } else { *b } as u64)
+  .sum()
+}
+
+fn padding(size: u64) -> u64 {
+ (BLOCK as u64 - size % BLOCK as u64) % BLOCK as u64
+}
+
+fn until_nul(field: &[u8]) -> &[u8] {
+ match field.iter().position(|b| *b == 0) {
+  None => field,
+  Some(end) => &field[..end],
+ }
+}
+
+/// the name of a ustar header including the prefix, GNU headers use the prefix field otherwise
+fn header_name(header: &[u8]) -> Vec<u8> {
+ let name = until_nul(&header[..100]);
+ let prefix = until_nul(&header[345..500]);
+ if &header[257..263] != b"ustar\0" || prefix.is_empty() {
+  return name.to_vec();
+ }
+ [prefix, b"/", name].concat()
+}
+
+/// parses an octal number field or a GNU base-256 number field
+fn parse_number(field: &[u8]) -> Result<u64, Error> {
+ if field[0] & 0x80 != 0 {
+  if field[0] != 0x80 || field.len() > 9 && field[1..field.len() - 8].iter().any(|b| *b != 0) {
+   return Err(Error::Msg("invalid tar number field"));
+  }
+  return Ok(field[1..].iter().fold(0, |acc, b| acc << 8 | *b as u64));
+ }
+ let digits = until_nul(field).trim_ascii();
+ digits
+  .iter()
+  .try_fold(0u64, |acc, b| match (b, acc.checked_mul(8)) {
+   (b'0'..=b'7', Some(acc)) => Ok(acc | (b - b'0') as u64),
+   _ => Err(Error::Msg("invalid tar number field")),
+  })
+}
+
+/// splits PAX records of the form "length key=value\n"
+fn parse_pax(mut data: &[u8]) -> Result<PaxRecords, Error> {
+ let invalid = Error::Msg("invalid pax extended header");
+ let mut records = vec![];
+ while !data.is_empty() && data[0] != 0 {
+  let Some(space) = data.iter().position(|b| *b == b' ') else {
+   return Err(invalid);
+  };
+  let len = std::str::from_utf8(&data[..space])
+   .ok()
+   .and_then(|len| len.parse::<usize>().ok())
+   .filter(|len| *len > space + 1 && *len <= data.len())
+   .ok_or(Error::Msg("invalid pax extended header"))?;
+  let Some(record) = data[space + 1..len].strip_suffix(b"\n") else {
+   return Err(invalid);
+  };
+  let Some(eq) = record.iter().position(|b| *b == b'=') else {
+   return Err(invalid);
+  };
+  records.push((record[..eq].to_vec(), record[eq + 1..].to_vec()));
+  data = &data[len..];
+ }
+ Ok(records)
+}
+
+fn apply_pax(entry: &mut TarEntry, key: &[u8], value: &[u8]) -> Result<(), Error> {
+ let decimal = |value: &[u8]| {
+  // mtime may have a fraction which is cut off
+  let digits = value.split(|b| *b == b'.').next().unwrap_or_default();
+  std::str::from_utf8(digits)
+   .ok()
+   .and_then(|digits| digits.parse::<u64>().ok())
+   .ok_or(Error::Msg("invalid pax extended header"))
+ };
+ match key {
+  b"path" => entry.name = value.to_vec(),
+  b"linkpath" => entry.link_name = value.to_vec(),
+  b"size" => entry.size = decimal(value)?,
+  b"mtime" => entry.mtime = decimal(value)?,
+  b"uid" => entry.uid = decimal(value)?,
+  b"gid" => entry.gid = decimal(value)?,
+  _ => {}
+ }
+ Ok(())
+}
@@ -0,0 +1,223 @@
+/*!
+Peeking at the TLS ClientHello at the begin of a connection, like for routing by SNI.
+
+The handshake records are read from pos on and the ClientHello is reassembled, also if it is
+fragmented over several records. pos is never altered and nothing is dropped, so all bytes can be
+forwarded unchanged afterwards.
+
+```rust
+use blockwise_reader::BlockWiseReader;
+use blockwise_reader::tls_peek::peek_client_hello;
+
+let mut hello = vec![0x03, 0x03];
+hello.extend_from_slice(&[0; 32]); // random
+hello.extend_from_slice(&[0, 0, 2, 0x13, 0x01, 1, 0]); // session id, cipher suites, compression
+let sni = b"\x00\x00\x00\x10\x00\x0e\x00\x00\x0bexample.com";
+hello.extend_from_slice(&(sni.len() as u16).to_be_bytes());
+hello.extend_from_slice(sni);
+let mut handshake = vec![1, 0, 0, hello.len() as u8];
+handshake.extend_from_slice(&hello);
+let mut data = vec![22, 3, 1, 0, handshake.len() as u8];
+data.extend_from_slice(&handshake);
+
+let mut bwr = BlockWiseReader::from_slice(&data);
+let hello = peek_client_hello(&mut bwr, 16 * 1024).unwrap().unwrap();
+assert_eq!(Some("example.com"), hello.server_name.as_deref());
+assert_eq!(data.len(), hello.size);
+assert_eq!(0, bwr.pos_get());
+```
+*/
+
+use crate::binary::{Endian, Fields};
+use crate::{BlockWiseReader, Error};
+
+/// the content type of handshake records
+pub const HANDSHAKE: u8 = 22;
+/// the handshake type of a ClientHello
+pub const CLIENT_HELLO: u8 = 1;
+/// the maximal length of a plaintext record
+pub const MAX_RECORD_LENGTH: usize = 16384;
+
+pub const EXTENSION_SERVER_NAME: u16 = 0;
+pub const EXTENSION_ALPN: u16 = 16;
+pub const EXTENSION_SUPPORTED_VERSIONS: u16 = 43;
+
+const RECORD_HEADER_SIZE: usize = 5;
+const HANDSHAKE_HEADER_SIZE: usize = 4;
+
+/// an extension of a ClientHello
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Extension {
+ pub extension_type: u16,
+ pub data: Vec<u8>,
+}
+
+/// a parsed ClientHello
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ClientHello {
+ /// the version of the first record header
+ pub record_version: u16,
+ /// the legacy_version of the ClientHello, TLS 1.3 is announced by supported_versions
+ pub version: u16,
+ pub random: [u8; 32],
+ pub session_id: Vec<u8>,
+ pub cipher_suites: Vec<u16>,
+ pub compression_methods: Vec<u8>,
+ /// the extensions in the stored order
+ pub extensions: Vec<Extension>,
+ /// the host_name of the server_name extension
+ pub server_name: Option<String>,
+ /// the protocols of the ALPN extension
+ pub alpn: Vec<Vec<u8>>,
+ /// the versions of the supported_versions extension
+ pub supported_versions: Vec<u16>,
+ /// the size of the records which contain the ClientHello, from pos on
+ pub size: usize,
+}
+
+impl ClientHello {
+ /// the data of the first extension of extension_type
+ pub fn extension(&self, extension_type: u16) -> Option<&[u8]> {
+  self
+   .extensions
+   .iter()
+   .find(|e| e.extension_type == extension_type)
+   .map(|e| &e.data[..])
+ }
+}
+
+/// Reads the handshake records from pos on up to the end of the ClientHello and parses it.
+/// Returns None if the data at pos don't begin with a handshake record.
+/// pos remains unaltered and the read bytes remain in the BlockWiseReader.
+/// The size of the ClientHello message is limited by max_hello_size.
+pub fn peek_client_hello(
+ bwr: &mut BlockWiseReader<'_>,
+ max_hello_size: usize,
+) -> Result<Option<ClientHello>, Error> {
+ if 0 == bwr.slurp_exact(1)? || HANDSHAKE != bwr.get()[0] {
+  return Ok(None);
+ }
+ let mut message = vec![];
+ let mut offset = 0;
+ let mut record_version = None;
+ let hello_size = loop {
+  if bwr.slurp_exact(offset + RECORD_HEADER_SIZE)? < offset + RECORD_HEADER_SIZE {
+   return Err(Error::Msg("unexpected end of file in tls record"));
+  }
+  let header = &bwr.get()[offset..offset + RECORD_HEADER_SIZE];
+  if HANDSHAKE != header[0] {
+   return Err(Error::Msg("unexpected tls record type"));
+  }
+  if 3 != header[1] {
+   return Err(Error::Msg("unsupported tls record version"));
+  }
+  record_version.get_or_insert(u16::from_be_bytes([header[1], header[2]]));
+  let length = u16::from_be_bytes([header[3], header[4]]) as usize;
+  if 0 == length || length > MAX_RECORD_LENGTH {
+   return Err(Error::Msg("invalid tls record length"));
+  }
+  offset += RECORD_HEADER_SIZE;
+  if bwr.slurp_exact(offset + length)? < offset + length {
+   return Err(Error::Msg("unexpected end of file in tls record"));
+  }
+  message.extend_from_slice(&bwr.get()[offset..offset + length]);
+  offset += length;
+  if message.len() >= HANDSHAKE_HEADER_SIZE {
+   if CLIENT_HELLO != message[0] {
+    return Err(Error::Msg("not a tls client hello"));
+   }
+   let hello_size = u32::from_be_bytes([0, message[1], message[2], message[3]]) as usize;
+   if hello_size > max_hello_size {
+    return Err(Error::Msg("tls client hello exceeds max_hello_size"));
+   }
+   if message.len() >= HANDSHAKE_HEADER_SIZE + hello_size {
+    break hello_size;
+   }
+  }
+ };
+ let body = &message[HANDSHAKE_HEADER_SIZE..HANDSHAKE_HEADER_SIZE + hello_size];
+ let mut hello = parse_client_hello(body)?;
+ hello.record_version = record_version.unwrap_or_default();
+ hello.size = offset;
+ Ok(Some(hello))
+}
+
+fn parse_client_hello(body: &[u8]) -> Result<ClientHello, Error> {
+ let mut f = Fields {
+  data: body,
+  invalid: "invalid tls client hello",
+ };
+ let version = f.uint(2, Endian::Big)? as u16;
+ let random = f.take(32)?.try_into().unwrap();
+ let session_id = f.vec(1, Endian::Big)?.data.to_vec();
+ if session_id.len() > 32 {
+  return Err(Error::Msg(f.invalid));
+ }
+ let cipher_suites = u16_list(f.vec(2, Endian::Big)?)?;
+ let compression_methods = f.vec(1, Endian::Big)?.data.to_vec();
+ let mut hello = ClientHello {
+  record_version: 0,
+  version,
+  random,
+  session_id,
+  cipher_suites,
+  compression_methods,
+  extensions: vec![],
+  server_name: None,
+  alpn: vec![],
+  supported_versions: vec![],
+  size: 0,
+ };
+ if f.data.is_empty() {
+  return Ok(hello);
+ }
+ let mut extensions = f.vec(2, Endian::Big)?;
+ if !f.data.is_empty() {
+  return Err(Error::Msg(f.invalid));
+ }
+ while !extensions.data.is_empty() {
+  let extension_type = extensions.uint(2, Endian::Big)? as u16;
+  let mut data = extensions.vec(2, Endian::Big)?;
+  hello.extensions.push(Extension {
+   extension_type,
+   data: data.data.to_vec(),
+  });
+  match extension_type {
+   EXTENSION_SERVER_NAME if !data.data.is_empty() => {
+    let mut names = data.vec(2, Endian::Big)?;
+    while !names.data.is_empty() {
+     let name_type = names.uint(1, Endian::Big)?;
+     let name = names.vec(2, Endian::Big)?.data;
+     if 0 == name_type && hello.server_name.is_none() {
+      hello.server_name =
+       Some(String::from_utf8(name.to_vec()).map_err(|_| Error::Msg(f.invalid))?);
+     }
+    }
+   }
+   EXTENSION_ALPN => {
+    let mut protocols = data.vec(2, Endian::Big)?;
+    while !protocols.data.is_empty() {
+     hello
+      .alpn
+      .push(protocols.vec(1, Endian::Big)?.data.to_vec());
+    }
+   }
+   EXTENSION_SUPPORTED_VERSIONS => hello.supported_versions = u16_list(data.vec(1, Endian::Big)?)?,
+   _ => data.data = &[],
+  }
+  if !data.data.is_empty() {
+   return Err(Error::Msg(f.invalid));
+  }
+ }
+ Ok(hello)
+}
+
+/// a list of u16 which fills the whole vector
+fn u16_list(f: Fields) -> Result<Vec<u16>, Error> {
+ Ok(
+  f.uint_list(2, Endian::Big)?
+   .into_iter()
+   .map(|n| n as u16)
+   .collect(),
+ )
+}
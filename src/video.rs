@@ -0,0 +1,335 @@
+/*!
+Synchronisation on MPEG transport streams and iteration of H.264/H.265 NAL units in Annex-B byte streams.
+
+The TsReader locks on a transport stream by searching a position where the sync byte 0x47 repeats
+every 188, 192 (M2TS with timecode prefix) or 204 (with Reed-Solomon parity) bytes over several
+packets. The lock is lost on a missing sync byte and then searched again, the skipped bytes are
+reported.
+
+The NalReader finds the NAL units between the `00 00 01` and `00 00 00 01` start codes. The payload
+of a NAL unit is read by Read with the emulation prevention bytes removed, so a NAL unit is never
+loaded as a whole.
+
+```rust
+use blockwise_reader::BlockWiseReader;
+use blockwise_reader::video::NalReader;
+use std::io::Read;
+
+let mut bwr = BlockWiseReader::from_slice(b"\0\0\0\x01\x67\x42\0\0\x03\x01\0\0\x01\x68\xce");
+let mut nal = NalReader::new(&mut bwr, 1024);
+let unit = nal.next_nal().unwrap().unwrap();
+assert_eq!((0, 4, 7), (unit.offset, unit.start_code_len, unit.h264_type()));
+let mut rbsp = vec![];
+nal.read_to_end(&mut rbsp).unwrap();
+assert_eq!(b"\x67\x42\0\0\x01", &rbsp[..]);
+let unit = nal.next_nal().unwrap().unwrap();
+assert_eq!((10, 3, 8), (unit.offset, unit.start_code_len, unit.h264_type()));
+assert!(nal.next_nal().unwrap().is_none());
+```
+*/
+
+use crate::{BlockWiseReader, Error};
+use std::{cmp::min, io::Read};
+
+/// the first byte of every transport stream packet
+pub const SYNC_BYTE: u8 = 0x47;
+/// the size of a transport stream packet without timecode prefix and parity
+pub const TS_PACKET_SIZE: usize = 188;
+/// the packet sizes which are recognized, in the order they are tried
+pub const TS_PACKET_SIZES: [usize; 3] = [188, 192, 204];
+
+/// the offset of the sync byte in a packet, 192 byte packets start with a 4 byte timecode
+fn sync_offset(packet_size: usize) -> usize {
+ if 192 == packet_size {
+  4
+ } else {
+  0
+ }
+}
+
+/// A transport stream packet with absolute offsets in the stream.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TsPacket {
+ /// absolute offset of the packet including the timecode prefix
+ pub offset: u64,
+ /// 188, 192 or 204
+ pub packet_size: usize,
+ /// the timecode prefix of 192 byte packets
+ pub timecode: Option<u32>,
+ pub transport_error: bool,
+ pub payload_unit_start: bool,
+ pub pid: u16,
+ pub scrambling: u8,
+ pub has_adaptation_field: bool,
+ pub has_payload: bool,
+ pub continuity_counter: u8,
+ /// bytes skipped in front of the packet to gain or regain the lock
+ pub skipped: u64,
+}
+
+/// Reads the packets of a transport stream from the current pos of a BlockWiseReader on.
+/// Consumed bytes are removed by pos_drop().
+pub struct TsReader<'r, 'a> {
+ bwr: &'r mut BlockWiseReader<'a>,
+ buffersize: usize,
+ /// the number of packets with sync byte needed for a lock
+ lock_packets: usize,
+ /// the packet size while locked
+ packet_size: Option<usize>,
+ /// the size of the current packet which is kept in the buffer
+ current: Option<usize>,
+}
+
+impl<'r, 'a> TsReader<'r, 'a> {
+ /// Creates a TsReader which reads ahead in buffersize steps.
+ /// A lock needs lock_packets consecutive sync bytes, or less if the whole stream is shorter.
+ pub fn new(bwr: &'r mut BlockWiseReader<'a>, buffersize: usize, lock_packets: usize) -> Self {
+  Self {
+   bwr,
+   buffersize: buffersize.max(1),
+   lock_packets: lock_packets.max(1),
+   packet_size: None,
+   current: None,
+  }
+ }
+
+ /// Returns the next packet, pos is set to it and it stays buffered up to the next call.
+ /// Returns None at end of file.
+ pub fn next_packet(&mut self) -> Result<Option<TsPacket>, Error> {
+  if let Some(size) = self.current.take() {
+   self.bwr.pos_add(size);
+   self.bwr.pos_drop();
+  }
+  let start = self.bwr.pos_absolute();
+  loop {
+   let size = match self.packet_size {
+    Some(size) => size,
+    None => match self.lock()? {
+     Some(size) => size,
+     None => return Ok(None),
+    },
+   };
+   self.packet_size = Some(size);
+   let available = self.bwr.slurp_exact(size)?;
+   if 0 == available {
+    return Ok(None);
+   }
+   if available < size {
+    return Err(Error::Msg("unexpected end of file in ts packet"));
+   }
+   let data = &self.bwr.get()[..size];
+   let sync = sync_offset(size);
+   if SYNC_BYTE != data[sync] {
+    self.packet_size = None;
+    self.bwr.pos_add(1);
+    self.bwr.pos_drop();
+    continue;
+   }
+   let header = &data[sync..sync + 4];
+   let offset = self.bwr.pos_absolute();
+   self.current = Some(size);
+   return Ok(Some(TsPacket {
+    offset,
+    packet_size: size,
+    timecode: (4 == sync).then(|| u32::from_be_bytes([data[0], data[1], data[2], data[3]])),
+    transport_error: header[1] & 0x80 != 0,
+    payload_unit_start: header[1] & 0x40 != 0,
+    pid: u16::from_be_bytes([header[1] & 0x1f, header[2]]),
+    scrambling: header[3] >> 6,
+    has_adaptation_field: header[3] & 0x20 != 0,
+    has_payload: header[3] & 0x10 != 0,
+    continuity_counter: header[3] & 0x0f,
+    skipped: offset - start,
+   }));
+  }
+ }
+
+ /// Searches the first position from pos on where the sync bytes repeat, sets pos to it and returns the packet size.
+ /// Returns None at end of file, then all bytes are consumed.
+ fn lock(&mut self) -> Result<Option<usize>, Error> {
+  let window = self.lock_packets * TS_PACKET_SIZES[2] + 4;
+  let mut i = 0;
+  loop {
+   let available = self.bwr.slurp_exact(i + window)?;
+   if i >= available {
+    self.bwr.pos_add(available);
+    self.bwr.pos_drop();
+    return Ok(None);
+   }
+   let end = self.bwr.pos_absolute() + available as u64;
+   let data = &self.bwr.get()[..available];
+   for size in TS_PACKET_SIZES {
+    // only a stream which is shorter than the lock packets as a whole may lock on less packets
+    let short = self.bwr.eof && end < (self.lock_packets * size) as u64;
+    let count = if short { (available - i) / size } else { self.lock_packets };
+    let sync = i + sync_offset(size);
+    if count > 0
+     && sync + (count - 1) * size < available
+     && (0..count).all(|k| SYNC_BYTE == data[sync + k * size])
+    {
+     self.bwr.pos_add(i);
+     self.bwr.pos_drop();
+     return Ok(Some(size));
+    }
+   }
+   i += 1;
+   if i > self.buffersize {
+    self.bwr.pos_add(i);
+    self.bwr.pos_drop();
+    i = 0;
+   }
+  }
+ }
+
+ /// the 188 bytes of the current packet from the sync byte on
+ pub fn packet(&self) -> Option<&[u8]> {
+  let sync = sync_offset(self.current?);
+  Some(&self.bwr.get()[sync..sync + TS_PACKET_SIZE])
+ }
+
+ /// the payload of the current packet behind the adaptation field
+ pub fn payload(&self) -> Result<&[u8], Error> {
+  let Some(packet) = self.packet() else {
+   return Ok(&[]);
+  };
+  let mut start = 4;
+  if packet[3] & 0x20 != 0 {
+   start += 1 + packet[4] as usize;
+   if start > TS_PACKET_SIZE {
+    return Err(Error::Msg("invalid ts adaptation field length"));
+   }
+  }
+  if packet[3] & 0x10 == 0 {
+   return Ok(&[]);
+  }
+  Ok(&packet[start..])
+ }
+}
+
+/// A NAL unit with absolute offsets in the stream.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct NalUnit {
+ /// absolute offset of the start code
+ pub offset: u64,
+ /// 3 or 4
+ pub start_code_len: usize,
+ /// absolute offset of the NAL unit header
+ pub data_offset: u64,
+ /// the first two bytes of the NAL unit, H.264 headers have only the first byte
+ pub header: [u8; 2],
+}
+
+impl NalUnit {
+ /// the nal_unit_type of an H.264 NAL unit, 5 is an IDR slice, 7 a SPS and 8 a PPS
+ pub fn h264_type(&self) -> u8 {
+  self.header[0] & 0x1f
+ }
+
+ /// the nal_unit_type of an H.265 NAL unit, 19 and 20 are IDR slices, 32 a VPS, 33 a SPS and 34 a PPS
+ pub fn h265_type(&self) -> u8 {
+  self.header[0] >> 1 & 0x3f
+ }
+}
+
+/// Reads the NAL units of an Annex-B byte stream from the current pos of a BlockWiseReader on.
+/// Consumed bytes are removed by pos_drop().
+pub struct NalReader<'r, 'a> {
+ bwr: &'r mut BlockWiseReader<'a>,
+ buffersize: usize,
+ /// the end of the current NAL unit is reached
+ ended: bool,
+ /// the number of zero bytes in front of the next byte of the current NAL unit
+ zeros: usize,
+}
+
+impl<'r, 'a> NalReader<'r, 'a> {
+ /// creates a NalReader which reads ahead in buffersize steps
+ pub fn new(bwr: &'r mut BlockWiseReader<'a>, buffersize: usize) -> Self {
+  Self {
+   bwr,
+   buffersize: buffersize.max(3),
+   ended: true,
+   zeros: 0,
+  }
+ }
+
+ /// Returns the next NAL unit and sets pos to its header, the rest of the previous NAL unit is skipped.
+ /// Data in front of the first start code is skipped. Returns None at end of file.
+ pub fn next_nal(&mut self) -> Result<Option<NalUnit>, Error> {
+  // the search for the next start code skips the rest of the current NAL unit
+  self.ended = true;
+  loop {
+   let available = self.bwr.slurp_exact(self.buffersize + 3)?;
+   let data = &self.bwr.get()[..available];
+   if let Some(idx) = data.windows(3).position(|w| w == [0, 0, 1]) {
+    let start_code_len = if idx > 0 && 0 == data[idx - 1] { 4 } else { 3 };
+    let offset = self.bwr.pos_absolute() + (idx + 3 - start_code_len) as u64;
+    self.bwr.pos_add(idx + 3);
+    self.bwr.pos_drop();
+    let available = self.bwr.slurp_exact(2)?;
+    if 0 == available {
+     return Ok(None);
+    }
+    let data = self.bwr.get();
+    self.ended = false;
+    self.zeros = 0;
+    return Ok(Some(NalUnit {
+     offset,
+     start_code_len,
+     data_offset: offset + start_code_len as u64,
+     header: [data[0], if available > 1 { data[1] } else { 0 }],
+    }));
+   }
+   if self.bwr.eof {
+    self.bwr.pos_add(available);
+    self.bwr.pos_drop();
+    return Ok(None);
+   }
+   self.bwr.pos_add(available - 3);
+   self.bwr.pos_drop();
+  }
+ }
+}
+
+impl Read for NalReader<'_, '_> {
+ /// reads the rest of the current NAL unit without emulation prevention bytes
+ fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+  while !self.ended && !buf.is_empty() {
+   let available = self.bwr.slurp_exact(self.buffersize)?;
+   let eof = self.bwr.eof;
+   let data = self.bwr.get();
+   let mut i = 0;
+   let mut n = 0;
+   while n < buf.len() && i < available {
+    let b = data[i];
+    if 0 == b {
+     let rest = &data[i..min(i + 3, available)];
+     if rest.len() < 3 && !eof {
+      break;
+     }
+     if rest == [0, 0, 0] || rest == [0, 0, 1] || rest.len() < 3 && rest.iter().all(|b| 0 == *b) {
+      self.ended = true;
+      break;
+     }
+    }
+    i += 1;
+    if 3 == b && self.zeros >= 2 {
+     self.zeros = 0;
+     continue;
+    }
+    self.zeros = if 0 == b { self.zeros + 1 } else { 0 };
+    buf[n] = b;
+    n += 1;
+   }
+   if i == available && eof {
+    self.ended = true;
+   }
+   self.bwr.pos_add(i);
+   self.bwr.pos_drop();
+   if n > 0 {
+    return Ok(n);
+   }
+  }
+  Ok(0)
+ }
+}
@@ -0,0 +1,280 @@
+/*!
+Reading of WebSocket frames, like behind an HTTP upgrade on the same BlockWiseReader.
+
+The WebSocketReader returns the frame headers one after another, the payload of the current frame
+is read unmasked by Read or by read_payload(). read_message() reassembles fragmented messages, the
+control frames which may appear between the fragments are returned as messages of their own.
+Extensions like permessage-deflate are not applied, their RSV bits are only reported.
+
+```rust
+use blockwise_reader::BlockWiseReader;
+use blockwise_reader::websocket::{Opcode, WebSocketReader};
+
+// a masked text frame, a fragmented text message with a ping in between and a close frame
+let data = b"\x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58\
+\x01\x03Hel\x89\x00\x80\x02lo\x88\x02\x03\xe8";
+let mut bwr = BlockWiseReader::from_slice(data);
+let mut ws = WebSocketReader::new(&mut bwr, 1024, 1024);
+
+let header = ws.next_frame().unwrap().unwrap();
+assert_eq!((true, Opcode::Text, 5), (header.fin, header.opcode, header.payload_len));
+assert_eq!(b"Hello", &ws.read_payload().unwrap()[..]);
+
+let message = ws.read_message(1024).unwrap().unwrap();
+assert_eq!(Opcode::Ping, message.opcode);
+let message = ws.read_message(1024).unwrap().unwrap();
+assert_eq!((Opcode::Text, &b"Hello"[..]), (message.opcode, &message.data[..]));
+let message = ws.read_message(1024).unwrap().unwrap();
+assert_eq!(Some((1000, &b""[..])), message.close_status());
+assert!(ws.read_message(1024).unwrap().is_none());
+```
+*/
+
+use crate::{BlockWiseReader, Error};
+use std::io::Read;
+
+/// the largest payload of a control frame
+pub const MAX_CONTROL_PAYLOAD: u64 = 125;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Opcode {
+ Continuation,
+ Text,
+ Binary,
+ Close,
+ Ping,
+ Pong,
+}
+
+impl Opcode {
+ /// true for Close, Ping and Pong
+ pub fn is_control(self) -> bool {
+  matches!(self, Opcode::Close | Opcode::Ping | Opcode::Pong)
+ }
+}
+
+/// A frame header with absolute offsets in the stream.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FrameHeader {
+ /// the last frame of a message
+ pub fin: bool,
+ /// the three RSV bits which are reserved for extensions
+ pub rsv: u8,
+ pub opcode: Opcode,
+ /// the masking key, frames of clients are masked
+ pub mask: Option<[u8; 4]>,
+ pub payload_len: u64,
+ /// absolute offset of the frame header
+ pub offset: u64,
+ /// absolute offset of the payload
+ pub payload_offset: u64,
+}
+
+/// a complete message or control frame with the unmasked payload
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Message {
+ /// Text, Binary or a control opcode
+ pub opcode: Opcode,
+ pub data: Vec<u8>,
+}
+
+impl Message {
+ /// the status code and the reason of a Close message, None if it has no status code
+ pub fn close_status(&self) -> Option<(u16, &[u8])> {
+  if Opcode::Close != self.opcode || self.data.len() < 2 {
+   return None;
+  }
+  Some((u16::from_be_bytes([self.data[0], self.data[1]]), &self.data[2..]))
+ }
+}
+
+/// Reads WebSocket frames from the current pos of a BlockWiseReader on.
+/// Consumed bytes are removed by pos_drop().
+pub struct WebSocketReader<'r, 'a> {
+ bwr: &'r mut BlockWiseReader<'a>,
+ buffersize: usize,
+ max_frame_size: u64,
+ /// the current frame, None before the first frame
+ current: Option<FrameHeader>,
+ /// unread payload bytes of the current frame
+ remaining: u64,
+ /// the opcode of the first frame of an unfinished fragmented message
+ fragmented: Option<Opcode>,
+ /// the collected payload of an unfinished fragmented message
+ message: Vec<u8>,
+}
+
+impl<'r, 'a> WebSocketReader<'r, 'a> {
+ /// creates a WebSocketReader which reads ahead in buffersize steps and accepts frames up to max_frame_size payload bytes
+ pub fn new(bwr: &'r mut BlockWiseReader<'a>, buffersize: usize, max_frame_size: u64) -> Self {
+  Self {
+   bwr,
+   buffersize: buffersize.max(1),
+   max_frame_size,
+   current: None,
+   remaining: 0,
+   fragmented: None,
+   message: vec![],
+  }
+ }
+
+ /// Returns the next frame header and sets pos to its payload, the rest of the previous payload is skipped.
+ /// Returns None at the end of the stream. On errors pos remains at the frame header.
+ pub fn next_frame(&mut self) -> Result<Option<FrameHeader>, Error> {
+  let count = self.remaining;
+  if self.bwr.pos_skip(count, self.buffersize)? < count {
+   return Err(Error::Msg("unexpected end of file in websocket payload"));
+  }
+  self.remaining = 0;
+  self.bwr.pos_drop();
+
+  let available = self.bwr.slurp_exact(2)?;
+  if 0 == available {
+   if self.fragmented.is_some() {
+    return Err(Error::Msg("unexpected end of file in fragmented websocket message"));
+   }
+   return Ok(None);
+  }
+  if available < 2 {
+   return Err(Error::Msg("unexpected end of file in websocket frame header"));
+  }
+  let (b0, b1) = (self.bwr.get()[0], self.bwr.get()[1]);
+  let opcode = match b0 & 0x0f {
+   0 => Opcode::Continuation,
+   1 => Opcode::Text,
+   2 => Opcode::Binary,
+   8 => Opcode::Close,
+   9 => Opcode::Ping,
+   10 => Opcode::Pong,
+   _ => return Err(Error::Msg("reserved websocket opcode")),
+  };
+  let fin = b0 & 0x80 != 0;
+  let length_size = match b1 & 0x7f {
+   126 => 2,
+   127 => 8,
+   _ => 0,
+  };
+  let mask_size = if b1 & 0x80 != 0 { 4 } else { 0 };
+  let header_size = 2 + length_size + mask_size;
+  if self.bwr.slurp_exact(header_size)? < header_size {
+   return Err(Error::Msg("unexpected end of file in websocket frame header"));
+  }
+  let header = &self.bwr.get()[..header_size];
+  let payload_len = match length_size {
+   0 => (b1 & 0x7f) as u64,
+   2 => u16::from_be_bytes([header[2], header[3]]) as u64,
+   _ => u64::from_be_bytes(header[2..10].try_into().unwrap()),
+  };
+  let minimal = match length_size {
+   0 => 0,
+   2 => 126,
+   _ => 0x10000,
+  };
+  if payload_len < minimal || payload_len >> 63 != 0 {
+   return Err(Error::Msg("invalid websocket payload length"));
+  }
+  let mask = (4 == mask_size).then(|| header[header_size - 4..].try_into().unwrap());
+
+  if opcode.is_control() {
+   if !fin {
+    return Err(Error::Msg("fragmented websocket control frame"));
+   }
+   if payload_len > MAX_CONTROL_PAYLOAD {
+    return Err(Error::Msg("websocket control frame exceeds 125 bytes"));
+   }
+  } else if Opcode::Continuation == opcode {
+   if self.fragmented.is_none() {
+    return Err(Error::Msg("unexpected websocket continuation frame"));
+   }
+  } else if self.fragmented.is_some() {
+   return Err(Error::Msg("unfinished fragmented websocket message"));
+  }
+  if payload_len > self.max_frame_size {
+   return Err(Error::Msg("websocket frame exceeds max_frame_size"));
+  }
+
+  if !opcode.is_control() {
+   self.fragmented = match (fin, opcode) {
+    (true, _) => None,
+    (false, Opcode::Continuation) => self.fragmented,
+    (false, _) => Some(opcode),
+   };
+  }
+  let offset = self.bwr.pos_absolute();
+  let frame = FrameHeader {
+   fin,
+   rsv: (b0 >> 4) & 0x07,
+   opcode,
+   mask,
+   payload_len,
+   offset,
+   payload_offset: offset + header_size as u64,
+  };
+  self.bwr.pos_add(header_size);
+  self.bwr.pos_drop();
+  self.current = Some(frame);
+  self.remaining = payload_len;
+  Ok(Some(frame))
+ }
+
+ /// reads the unread payload of the current frame as a whole and unmasks it
+ pub fn read_payload(&mut self) -> Result<Vec<u8>, Error> {
+  let mut data = vec![];
+  self.read_to_end(&mut data)?;
+  Ok(data)
+ }
+
+ /// Reads the frames up to the end of the next message and returns its unmasked payload.
+ /// Control frames are returned immediately, also between the frames of a fragmented message.
+ /// The payload of a Text message is checked for valid UTF-8.
+ /// Returns None at the end of the stream.
+ pub fn read_message(&mut self, max_message_size: u64) -> Result<Option<Message>, Error> {
+  loop {
+   let fragmented = self.fragmented;
+   let Some(frame) = self.next_frame()? else {
+    return Ok(None);
+   };
+   if frame.opcode.is_control() {
+    return Ok(Some(Message {
+     opcode: frame.opcode,
+     data: self.read_payload()?,
+    }));
+   }
+   if self.message.len() as u64 + frame.payload_len > max_message_size {
+    return Err(Error::Msg("websocket message exceeds max_message_size"));
+   }
+   let data = self.read_payload()?;
+   self.message.extend_from_slice(&data);
+   if !frame.fin {
+    continue;
+   }
+   let opcode = fragmented.unwrap_or(frame.opcode);
+   let data = std::mem::take(&mut self.message);
+   if Opcode::Text == opcode && std::str::from_utf8(&data).is_err() {
+    return Err(Error::Msg("invalid utf-8 in websocket text message"));
+   }
+   return Ok(Some(Message { opcode, data }));
+  }
+ }
+}
+
+impl Read for WebSocketReader<'_, '_> {
+ fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+  let Some(frame) = self.current else {
+   return Ok(0);
+  };
+  let done = frame.payload_len - self.remaining;
+  let n = self.bwr.read_payload(
+   self.buffersize,
+   &mut self.remaining,
+   buf,
+   Some("unexpected end of file in websocket payload"),
+  )?;
+  if let Some(mask) = frame.mask {
+   for (idx, b) in buf[..n].iter_mut().enumerate() {
+    *b ^= mask[((done + idx as u64) % 4) as usize];
+   }
+  }
+  Ok(n)
+ }
+}
@@ -0,0 +1,91 @@
+use crate::{BlockWiseReader, Error};
+use std::cmp::max;
+use winnow::{
+ error::{Needed, ParserError},
+ stream::StreamIsPartial,
+ Partial,
+};
+
+/// A winnow parser function on a Partial byte slice, the output and the error may borrow from the input.
+/// It is implemented for functions like fn(&mut Partial<&'i [u8]>) -> ModalResult<&'i [u8]>.
+pub trait PartialParser<'i> {
+ type Output;
+ type Error: ParserError<Partial<&'i [u8]>>;
+
+ fn parse_next(&mut self, input: &mut Partial<&'i [u8]>) -> Result<Self::Output, Self::Error>;
+}
+
+impl<'i, F, O, E> PartialParser<'i> for F
+where
+ F: FnMut(&mut Partial<&'i [u8]>) -> Result<O, E>,
+ E: ParserError<Partial<&'i [u8]>>,
+{
+ type Output = O;
+ type Error = E;
+
+ fn parse_next(&mut self, input: &mut Partial<&'i [u8]>) -> Result<O, E> {
+  self(input)
+ }
+}
+
+/// the outcome of a winnow parser applied by BlockWiseReader::slurp_winnow
+pub type WinnowResult<'s, P> =
+ Result<<P as PartialParser<'s>>::Output, <P as PartialParser<'s>>::Error>;
+
+impl<'a> BlockWiseReader<'a> {
+ /// Applies a winnow parser on a Partial stream over the available bytes.
+ /// As long as the parser returns ErrMode::Incomplete more bytes are slurped: the missing amount for
+ /// Needed::Size, otherwise at least buffersize bytes, doubling the available bytes with each try.
+ /// At end of file the stream is marked as complete before the parser is applied.
+ /// Checkpoints of the stream are positions in the internal data from pos on, all of them stay
+ /// available until the parser finished, so backtracking works on the buffered data.
+ /// On success pos is set behind the consumed bytes, otherwise pos remains unaltered.
+ /// The output may borrow from the internal data, therefore the successful parser call is repeated
+ /// once on the final data, so the parser should not have side effects.
+ pub fn slurp_winnow<'s, P>(
+  &'s mut self,
+  buffersize: usize,
+  mut parser: P,
+ ) -> Result<WinnowResult<'s, P>, Error>
+ where
+  P: for<'i> PartialParser<'i>,
+ {
+  if 0 == buffersize {
+   return Err(Error::Msg("buffersize 0 leads to an infinite loop"));
+  }
+  loop {
+   if self.eof {
+    break;
+   }
+   let mut input = Partial::new(self.get());
+   let needed = match parser.parse_next(&mut input) {
+    Err(e) => match e.needed() {
+     Some(needed) => needed,
+     None => break,
+    },
+    Ok(_) => break,
+   };
+   let available = self.available_bytes();
+   let bytecount = match needed {
+    Needed::Size(n) => available + n.get(),
+    Needed::Unknown => max(buffersize, 2 * available),
+   };
+   if self.slurp(bytecount)? == available && self.eof {
+    break;
+   }
+  }
+
+  let eof = self.eof;
+  let Self { v, pos, .. } = self;
+  let data: &'s [u8] = &v[*pos..];
+  let mut input = Partial::new(data);
+  if eof {
+   input.complete();
+  }
+  let res = parser.parse_next(&mut input);
+  if res.is_ok() {
+   *pos += data.len() - input.len();
+  }
+  Ok(res)
+ }
+}
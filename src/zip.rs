@@ -0,0 +1,361 @@
+/*!
+Listing the members of ZIP archives like JAR or DOCX files without decompressing anything.
+
+ZipStreamReader walks over the local file headers from the current pos on, which works on
+streams. Entries with data descriptors are supported by searching the descriptor behind the data.
+read_central_directory seeks to the end of central directory record at the end of a file and
+reads only the central directory, which also works for archives with entries that were modified or
+removed later. Zip64 records and extra fields are supported by both.
+
+```rust,no_run
+use blockwise_reader::BlockWiseReader;
+use blockwise_reader::zip::{read_central_directory, ZipStreamReader};
+
+# fn main() -> Result<(), blockwise_reader::Error> {
+let mut file = std::fs::File::open("app.jar")?;
+for entry in read_central_directory(&mut file, 64 * 1024)? {
+ println!("{} {}", String::from_utf8_lossy(&entry.name), entry.uncompressed_size);
+}
+
+let mut bwr = BlockWiseReader::new(Box::new(file));
+let mut zip = ZipStreamReader::new(&mut bwr, 64 * 1024);
+while let Some(entry) = zip.next_entry()? {
+ println!("{} {}", String::from_utf8_lossy(&entry.name), entry.compressed_size);
+}
+# Ok(())
+# }
+```
+*/
+
+use crate::{BlockWiseReader, Error};
+use std::io::{Read, Seek, SeekFrom};
+
+const LOCAL_HEADER: &[u8] = b"PK\x03\x04";
+const CENTRAL_HEADER: &[u8] = b"PK\x01\x02";
+const DATA_DESCRIPTOR: &[u8] = b"PK\x07\x08";
+const END_OF_CENTRAL_DIRECTORY: &[u8] = b"PK\x05\x06";
+const ZIP64_END_OF_CENTRAL_DIRECTORY: &[u8] = b"PK\x06\x06";
+const ZIP64_LOCATOR: &[u8] = b"PK\x06\x07";
+
+/// flag bit 3: crc and sizes are stored in a data descriptor behind the data
+const FLAG_DATA_DESCRIPTOR: u16 = 0x08;
+
+/// A member of a ZIP archive with absolute offsets in the stream, Zip64 values are already applied.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ZipEntry {
+ pub name: Vec<u8>,
+ pub flags: u16,
+ /// 0 is stored, 8 is deflate
+ pub method: u16,
+ pub crc32: u32,
+ pub compressed_size: u64,
+ pub uncompressed_size: u64,
+ pub dos_time: u16,
+ pub dos_date: u16,
+ /// absolute offset of the local file header
+ pub header_offset: u64,
+ /// absolute offset of the (compressed) data
+ pub data_offset: u64,
+}
+
+/// Reads the entries of a ZIP archive by their local file headers from the current pos of a BlockWiseReader on.
+/// The data of the entries is skipped, consumed bytes are removed by pos_drop().
+pub struct ZipStreamReader<'r, 'a> {
+ bwr: &'r mut BlockWiseReader<'a>,
+ buffersize: usize,
+ done: bool,
+}
+
+impl<'r, 'a> ZipStreamReader<'r, 'a> {
+ /// creates a ZipStreamReader which reads ahead in buffersize steps
+ pub fn new(bwr: &'r mut BlockWiseReader<'a>, buffersize: usize) -> Self {
+  Self {
+   bwr,
+   buffersize: buffersize.max(1),
+   done: false,
+  }
+ }
+
+ /// Returns the next entry and sets pos behind its data and data descriptor.
+ /// Returns None at end of file or at the central directory, then pos is set to its begin.
+ pub fn next_entry(&mut self) -> Result<Option<ZipEntry>, Error> {
+  if self.done {
+   return Ok(None);
+  }
+  let available = self.bwr.slurp_exact(30)?;
+  // the marker of split archives
+  if available >= 4 && 0 == self.bwr.pos_absolute() && self.bwr.get().starts_with(DATA_DESCRIPTOR) {
+   self.bwr.pos_add(4);
+   self.bwr.slurp_exact(30)?;
+  }
+  let data = self.bwr.get();
+  if !data.starts_with(LOCAL_HEADER) {
+   if data.is_empty()
+    || [
+     CENTRAL_HEADER,
+     END_OF_CENTRAL_DIRECTORY,
+     ZIP64_END_OF_CENTRAL_DIRECTORY,
+    ]
+    .iter()
+    .any(|sig| data.starts_with(sig))
+   {
+    self.bwr.pos_drop();
+    self.done = true;
+    return Ok(None);
+   }
+   return Err(Error::Msg("invalid zip local header signature"));
+  }
+  if data.len() < 30 {
+   return Err(Error::Msg("unexpected end of file in zip local header"));
+  }
+  let header_len = 30 + u16_at(data, 26) as usize + u16_at(data, 28) as usize;
+  if self.bwr.slurp_exact(header_len)? < header_len {
+   return Err(Error::Msg("unexpected end of file in zip local header"));
+  }
+  let header_offset = self.bwr.pos_absolute();
+  let (mut entry, zip64) = parse_local_header(&self.bwr.get()[..header_len], header_offset)?;
+  self.bwr.pos_add(header_len);
+  self.bwr.pos_drop();
+
+  if 0 == entry.flags & FLAG_DATA_DESCRIPTOR {
+   let size = entry.compressed_size;
+   if self.bwr.pos_skip(size, self.buffersize)? < size {
+    return Err(Error::Msg("unexpected end of file in zip entry data"));
+   }
+  } else {
+   self.skip_to_descriptor(&mut entry, zip64)?;
+  }
+  Ok(Some(entry))
+ }
+
+ /// Searches the data descriptor whose compressed size matches its distance to the begin of the data,
+ /// the descriptor signature is optional.
+ fn skip_to_descriptor(&mut self, entry: &mut ZipEntry, zip64: bool) -> Result<(), Error> {
+  let size_len = if zip64 { 8 } else { 4 };
+  let unsigned_len = 4 + 2 * size_len;
+  let mut idx = 0;
+  loop {
+   let data = self.bwr.get();
+   let base = self.bwr.pos_absolute() - entry.data_offset;
+   while idx + 4 <= data.len() {
+    let distance = base + idx as u64;
+    let candidate = &data[idx..];
+    if candidate.starts_with(DATA_DESCRIPTOR) {
+     if candidate.len() < 4 + unsigned_len {
+      break;
+     }
+     if read_size(candidate, 8, size_len) == distance {
+      set_descriptor(entry, &candidate[4..], size_len);
+      self.bwr.pos_add(idx + 4 + unsigned_len);
+      self.bwr.pos_drop();
+      return Ok(());
+     }
+    } else if (candidate.starts_with(LOCAL_HEADER) || candidate.starts_with(CENTRAL_HEADER))
+     && distance >= unsigned_len as u64
+    {
+     let descriptor = &data[idx - unsigned_len..];
+     if read_size(descriptor, 4, size_len) == distance - unsigned_len as u64 {
+      set_descriptor(entry, descriptor, size_len);
+      self.bwr.pos_add(idx);
+      self.bwr.pos_drop();
+      return Ok(());
+     }
+    }
+    idx += 1;
+   }
+   // keep enough bytes in front of idx for an unsigned descriptor
+   let keep = idx.saturating_sub(unsigned_len);
+   self.bwr.pos_add(keep);
+   self.bwr.pos_drop();
+   idx -= keep;
+   let available = self.bwr.available_bytes();
+   if self.bwr.slurp(available + self.buffersize)? == available && self.bwr.eof {
+    return Err(Error::Msg("missing zip data descriptor"));
+   }
+  }
+ }
+}
+
+/// Reads the central directory of the ZIP archive which begins at the current position of source, the archive
+/// ends at the end of source. The end of central directory record is searched in the last bytes, then only the
+/// central directory and the local file headers are read in buffersize steps. The position of source is restored.
+pub fn read_central_directory<R: Read + Seek>(
+ source: &mut R,
+ buffersize: usize,
+) -> Result<Vec<ZipEntry>, Error> {
+ let invalid = || Error::Msg("invalid zip central directory");
+ let buffersize = buffersize.max(1);
+ let archive_offset = source.stream_position()?;
+ let len = source
+  .seek(SeekFrom::End(0))?
+  .saturating_sub(archive_offset);
+ let at_archive = |offset: u64| archive_offset.checked_add(offset).ok_or_else(invalid);
+ // the record with a comment of up to 0xffff bytes and the Zip64 locator in front of it
+ let tail_len = len.min(20 + 22 + 0xffff);
+ let tail = read_at(source, archive_offset + len - tail_len, tail_len, buffersize)?;
+ if tail.len() < 22 {
+  return Err(Error::Msg("missing zip end of central directory record"));
+ }
+ let lowest = tail.len().saturating_sub(22 + 0xffff);
+ let Some(eocd) = (lowest..=tail.len() - 22).rev().find(|i| {
+  tail[*i..].starts_with(END_OF_CENTRAL_DIRECTORY)
+   && i + 22 + u16_at(&tail, i + 20) as usize <= tail.len()
+ }) else {
+  return Err(Error::Msg("missing zip end of central directory record"));
+ };
+ let mut count = u16_at(&tail, eocd + 10) as u64;
+ let mut cd_size = u32_at(&tail, eocd + 12) as u64;
+ let mut cd_offset = u32_at(&tail, eocd + 16) as u64;
+ if eocd >= 20 && tail[eocd - 20..].starts_with(ZIP64_LOCATOR) {
+  let record = read_at(source, at_archive(u64_at(&tail, eocd - 12))?, 56, buffersize)?;
+  if record.len() < 56 || !record.starts_with(ZIP64_END_OF_CENTRAL_DIRECTORY) {
+   return Err(invalid());
+  }
+  count = u64_at(&record, 32);
+  cd_size = u64_at(&record, 40);
+  cd_offset = u64_at(&record, 48);
+ }
+ if cd_offset.checked_add(cd_size).is_none_or(|end| end > len) {
+  return Err(invalid());
+ }
+ let data = read_at(source, archive_offset + cd_offset, cd_size, buffersize)?;
+ if data.len() as u64 != cd_size {
+  return Err(invalid());
+ }
+
+ let mut entries = vec![];
+ let mut at = 0;
+ while at < data.len() {
+  if at + 46 > data.len() || !data[at..].starts_with(CENTRAL_HEADER) {
+   return Err(invalid());
+  }
+  let name_len = u16_at(&data, at + 28) as usize;
+  let extra_len = u16_at(&data, at + 30) as usize;
+  let comment_len = u16_at(&data, at + 32) as usize;
+  let end = at + 46 + name_len + extra_len + comment_len;
+  if end > data.len() {
+   return Err(invalid());
+  }
+  let extra = &data[at + 46 + name_len..at + 46 + name_len + extra_len];
+  let mut values = [
+   u32_at(&data, at + 24) as u64,
+   u32_at(&data, at + 20) as u64,
+   u32_at(&data, at + 42) as u64,
+  ];
+  apply_zip64_extra(extra, &mut values)?;
+  let [uncompressed_size, compressed_size, header_offset] = values;
+
+  let local = read_at(source, at_archive(header_offset)?, 30, buffersize)?;
+  if local.len() < 30 || !local.starts_with(LOCAL_HEADER) {
+   return Err(Error::Msg("invalid zip local header signature"));
+  }
+  let data_offset = header_offset + 30 + u16_at(&local, 26) as u64 + u16_at(&local, 28) as u64;
+  entries.push(ZipEntry {
+   name: data[at + 46..at + 46 + name_len].to_vec(),
+   flags: u16_at(&data, at + 8),
+   method: u16_at(&data, at + 10),
+   crc32: u32_at(&data, at + 16),
+   compressed_size,
+   uncompressed_size,
+   dos_time: u16_at(&data, at + 12),
+   dos_date: u16_at(&data, at + 14),
+   header_offset: archive_offset + header_offset,
+   data_offset: archive_offset + data_offset,
+  });
+  at = end;
+ }
+ if entries.len() as u64 != count {
+  return Err(invalid());
+ }
+ source.seek(SeekFrom::Start(archive_offset))?;
+ Ok(entries)
+}
+
+/// reads up to len bytes from the absolute offset of source on in buffersize steps
+fn read_at<R: Read + Seek>(
+ source: &mut R,
+ offset: u64,
+ len: u64,
+ buffersize: usize,
+) -> Result<Vec<u8>, Error> {
+ source.seek(SeekFrom::Start(offset))?;
+ let mut bwr = BlockWiseReader::new(Box::new(source.by_ref().take(len)));
+ bwr.slurp_loop(buffersize)?;
+ Ok(bwr.get().to_vec())
+}
+
+/// parses a complete local file header, returns the entry and if it has a Zip64 extra field
+fn parse_local_header(header: &[u8], header_offset: u64) -> Result<(ZipEntry, bool), Error> {
+ let name_len = u16_at(header, 26) as usize;
+ let extra = &header[30 + name_len..];
+ let mut values = [u32_at(header, 22) as u64, u32_at(header, 18) as u64];
+ let zip64 = apply_zip64_extra(extra, &mut values)?;
+ let [uncompressed_size, compressed_size] = values;
+ Ok((
+  ZipEntry {
+   name: header[30..30 + name_len].to_vec(),
+   flags: u16_at(header, 6),
+   method: u16_at(header, 8),
+   crc32: u32_at(header, 14),
+   compressed_size,
+   uncompressed_size,
+   dos_time: u16_at(header, 10),
+   dos_date: u16_at(header, 12),
+   header_offset,
+   data_offset: header_offset + header.len() as u64,
+  },
+  zip64,
+ ))
+}
+
+/// Replaces the values which are 0xffffffff by the ones of the Zip64 extra field in the same order.
+/// Returns true if there is a Zip64 extra field.
+fn apply_zip64_extra(mut extra: &[u8], values: &mut [u64]) -> Result<bool, Error> {
+ while extra.len() >= 4 {
+  let id = u16_at(extra, 0);
+  let len = u16_at(extra, 2) as usize;
+  let Some(field) = extra.get(4..4 + len) else {
+   return Err(Error::Msg("invalid zip extra field"));
+  };
+  if 0x0001 == id {
+   let mut field = field;
+   for value in values.iter_mut().filter(|value| **value == 0xffff_ffff) {
+    if field.len() < 8 {
+     return Err(Error::Msg("invalid zip extra field"));
+    }
+    *value = u64_at(field, 0);
+    field = &field[8..];
+   }
+   return Ok(true);
+  }
+  extra = &extra[4 + len..];
+ }
+ Ok(false)
+}
+
+/// sets crc and sizes from a data descriptor without signature
+fn set_descriptor(entry: &mut ZipEntry, descriptor: &[u8], size_len: usize) {
+ entry.crc32 = u32_at(descriptor, 0);
+ entry.compressed_size = read_size(descriptor, 4, size_len);
+ entry.uncompressed_size = read_size(descriptor, 4 + size_len, size_len);
+}
+
+fn read_size(data: &[u8], at: usize, size_len: usize) -> u64 {
+ if 8 == size_len {
+  u64_at(data, at)
+ } else {
+  u32_at(data, at) as u64
+ }
+}
+
+fn u16_at(data: &[u8], at: usize) -> u16 {
+ u16::from_le_bytes([data[at], data[at + 1]])
+}
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+ u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+}
+
+fn u64_at(data: &[u8], at: usize) -> u64 {
+ u64::from_le_bytes(data[at..at + 8].try_into().unwrap())
+}
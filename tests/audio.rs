@@ -0,0 +1,432 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+ use crate::common::{first_msg, msg, Broken, Trickle};
+ use blockwise_reader::audio::*;
+ use blockwise_reader::BlockWiseReader;
+ use blockwise_reader::Error;
+ use std::io::{Cursor, Read};
+
+ fn block(block_type: u8, last: bool, data: &[u8]) -> Vec<u8> {
+  let len = (data.len() as u32).to_be_bytes();
+  [&[block_type | if last { 0x80 } else { 0 }], &len[1..], data].concat()
+ }
+
+ fn be_string(s: &[u8]) -> Vec<u8> {
+  [&(s.len() as u32).to_be_bytes()[..], s].concat()
+ }
+
+ fn le_string(s: &[u8]) -> Vec<u8> {
+  [&(s.len() as u32).to_le_bytes()[..], s].concat()
+ }
+
+ fn flac() -> Vec<u8> {
+  let mut streaminfo = b"\x10\x00\x10\x00\x00\x00\x0e\x00\x30\x00".to_vec();
+  streaminfo
+   .extend_from_slice(&(0x0bb80u64 << 44 | 5 << 41 | 23 << 36 | 0x1_0000_0000).to_be_bytes());
+  streaminfo.extend_from_slice(&[0xaa; 16]);
+  let comment = [
+   le_string(b"reference libFLAC"),
+   2u32.to_le_bytes().to_vec(),
+   le_string(b"ARTIST=someone"),
+   le_string("title=a=b \u{e4}".as_bytes()),
+  ]
+  .concat();
+  let picture = [
+   3u32.to_be_bytes().to_vec(),
+   be_string(b"image/png"),
+   be_string(b"cover"),
+   [640u32, 480, 24, 0]
+    .iter()
+    .flat_map(|x| x.to_be_bytes())
+    .collect(),
+   be_string(&[0x89; 300]),
+  ]
+  .concat();
+  [
+   b"ID3\x04\x00\x00\x00\x00\x01\x05".to_vec(),
+   vec![0; 133],
+   b"fLaC".to_vec(),
+   block(STREAMINFO, false, &streaminfo),
+   block(PADDING, false, &[0; 100]),
+   block(VORBIS_COMMENT, false, &comment),
+   block(APPLICATION, false, b"abcdxyz"),
+   block(PICTURE, true, &picture),
+   b"\xff\xf8frames".to_vec(),
+  ]
+  .concat()
+ }
+
+ #[test]
+ fn test_flac_001() -> Result<(), Error> {
+  let data = flac();
+  for i in [1, 3, 64, 4096] {
+   let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(&data)));
+   let mut flac = FlacReader::new(&mut bwr, i);
+   let mut blocks = vec![];
+   let mut application = vec![];
+   while let Some(b) = flac.next_block()? {
+    blocks.push((b.block_type, b.is_last, b.offset, b.size));
+    match b.block_type {
+     STREAMINFO => {
+      assert!(flac.vorbis_comment()?.is_none());
+      assert_eq!(
+       StreamInfo {
+        min_block_size: 4096,
+        max_block_size: 4096,
+        min_frame_size: 14,
+        max_frame_size: 0x3000,
+        sample_rate: 48000,
+        channels: 6,
+        bits_per_sample: 24,
+        total_samples: 0x1_0000_0000,
+        md5: [0xaa; 16],
+       },
+       flac.stream_info()?.unwrap()
+      );
+     }
+     VORBIS_COMMENT => {
+      let comment = flac.vorbis_comment()?.unwrap();
+      assert_eq!("reference libFLAC", comment.vendor);
+      assert_eq!(
+       vec![
+        ("ARTIST".to_string(), "someone".to_string()),
+        ("title".to_string(), "a=b \u{e4}".to_string())
+       ],
+       comment.comments
+      );
+     }
+     APPLICATION => {
+      flac.read_to_end(&mut application)?;
+      assert!(flac.picture()?.is_none());
+     }
+     PICTURE => {
+      let picture = flac.picture()?.unwrap();
+      assert_eq!(
+       (3, "image/png", "cover"),
+       (picture.picture_type, &*picture.mime_type, &*picture.description)
+      );
+      assert_eq!((640, 480, 24, 0), (picture.width, picture.height, picture.depth, picture.colors));
+      assert_eq!(vec![0x89; 300], picture.data);
+     }
+     _ => {}
+    }
+   }
+   assert_eq!(
+    vec![
+     (STREAMINFO, false, 147, 34),
+     (PADDING, false, 185, 100),
+     (VORBIS_COMMENT, false, 289, 59),
+     (APPLICATION, false, 352, 7),
+     (PICTURE, true, 363, 346),
+    ],
+    blocks
+   );
+   assert_eq!(b"abcdxyz", &application[..]);
+   assert!(flac.next_block()?.is_none());
+   assert_eq!(8, bwr.slurp_exact(100)?);
+   assert_eq!(b"\xff\xf8frames", bwr.get());
+  }
+  Ok(())
+ }
+
+ fn flac_error(data: &[u8]) -> &'static str {
+  let mut bwr = BlockWiseReader::from_slice(data);
+  let mut flac = FlacReader::new(&mut bwr, 4);
+  first_msg(|| {
+   let b = flac.next_block()?;
+   if b.is_some() {
+    flac.vorbis_comment()?;
+   }
+   Ok(b)
+  })
+ }
+
+ #[test]
+ fn test_flac_errors() {
+  let streaminfo = block(STREAMINFO, false, &[0; 34]);
+  assert_eq!("missing flac signature", flac_error(b"RIFF"));
+  assert_eq!("invalid id3v2 tag size", flac_error(b"ID3\x04\x00\x00\x80\x00\x00\x00"));
+  assert_eq!(
+   "unexpected end of file in id3v2 tag",
+   flac_error(b"ID3\x04\x00\x00\x00\x00\x00\x05fLaC")
+  );
+  assert_eq!(
+   "missing flac streaminfo block",
+   flac_error(&[&b"fLaC"[..], &block(PADDING, true, b"")].concat())
+  );
+  assert_eq!(
+   "invalid flac metadata block type",
+   flac_error(&[&b"fLaC"[..], &streaminfo, b"\x7f\0\0\0"].concat())
+  );
+  assert_eq!(
+   "unexpected end of file in flac metadata block",
+   flac_error(&[&b"fLaC"[..], &streaminfo[..20]].concat())
+  );
+  assert_eq!(
+   "invalid flac vorbis comment block",
+   flac_error(
+    &[
+     &b"fLaC"[..],
+     &streaminfo,
+     &block(VORBIS_COMMENT, true, b"\x05\0\0\0ab")
+    ]
+    .concat()
+   )
+  );
+  assert_eq!(
+   "invalid flac vorbis comment block",
+   flac_error(
+    &[
+     &b"fLaC"[..],
+     &streaminfo,
+     &block(VORBIS_COMMENT, true, b"\0\0\0\0\x01\0\0\0\x02\0\0\0ab")
+    ]
+    .concat()
+   )
+  );
+ }
+
+ #[test]
+ fn test_flac_partly_read_picture() -> Result<(), Error> {
+  let data = flac();
+  for (step, buffersize) in [(1, 1), (7, 3), (100, 64)] {
+   let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(&data, step)));
+   let mut flac = FlacReader::new(&mut bwr, buffersize);
+   while PICTURE != flac.next_block()?.unwrap().block_type {}
+   let mut buf = [0; 5];
+   let mut read = vec![];
+   while read.len() < 20 {
+    let n = flac.read(&mut buf)?;
+    assert!(n > 0 && n <= buffersize);
+    read.extend_from_slice(&buf[..n]);
+   }
+   assert_eq!(b"\0\0\0\x03\0\0\0\x09image/png", &read[..17]);
+   // a partly read block is not decoded
+   assert!(flac.picture()?.is_none());
+   assert!(flac.next_block()?.is_none());
+   assert_eq!(8, bwr.slurp_exact(100)?);
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_flac_invalid_vorbis_comment() -> Result<(), Error> {
+  let data = [
+   &b"fLaC"[..],
+   &block(STREAMINFO, false, &[0; 34]),
+   &block(VORBIS_COMMENT, false, b"\x05\0\0\0ab"),
+   &block(PADDING, true, b""),
+  ]
+  .concat();
+  let mut bwr = BlockWiseReader::from_slice(&data);
+  let mut flac = FlacReader::new(&mut bwr, 4);
+  flac.next_block()?.unwrap();
+  flac.next_block()?.unwrap();
+  // an invalid block remains unread and is skipped by the next block
+  assert_eq!("invalid flac vorbis comment block", msg(flac.vorbis_comment()));
+  assert_eq!(b"\x05\0\0\0ab", flac.payload()?);
+  assert_eq!(PADDING, flac.next_block()?.unwrap().block_type);
+  assert!(flac.next_block()?.is_none());
+  Ok(())
+ }
+
+ #[test]
+ fn test_flac_truncated_block() -> Result<(), Error> {
+  let data = [
+   &b"fLaC"[..],
+   &block(STREAMINFO, false, &[0; 34]),
+   &block(VORBIS_COMMENT, false, b"\x05\0\0\0ab"),
+   &block(PADDING, true, b""),
+  ]
+  .concat();
+  let mut bwr = BlockWiseReader::from_slice(&data[..50]);
+  let mut flac = FlacReader::new(&mut bwr, 4);
+  flac.next_block()?.unwrap();
+  flac.next_block()?.unwrap();
+  let mut payload = vec![];
+  assert!(flac.read_to_end(&mut payload).is_err());
+  assert_eq!(b"\x05\0\0\0", &payload[..]);
+  assert_eq!("unexpected end of file in flac metadata block", msg(flac.next_block()));
+  assert_eq!((50, 0), (bwr.pos_absolute(), bwr.available_bytes()));
+  Ok(())
+ }
+
+ fn ogg_crc(data: &[u8]) -> u32 {
+  data.iter().fold(0u32, |mut c, b| {
+   c ^= (*b as u32) << 24;
+   for _ in 0..8 {
+    c = if c & 0x8000_0000 != 0 { 0x04c1_1db7 ^ (c << 1) } else { c << 1 };
+   }
+   c
+  })
+ }
+
+ fn page(
+  header_type: u8,
+  granule: u64,
+  serial: u32,
+  sequence: u32,
+  segments: &[u8],
+  payload: &[u8],
+ ) -> Vec<u8> {
+  let mut page = b"OggS\0".to_vec();
+  page.push(header_type);
+  page.extend_from_slice(&granule.to_le_bytes());
+  page.extend_from_slice(&serial.to_le_bytes());
+  page.extend_from_slice(&sequence.to_le_bytes());
+  page.extend_from_slice(&[0; 4]);
+  page.push(segments.len() as u8);
+  page.extend_from_slice(segments);
+  page.extend_from_slice(payload);
+  let crc = ogg_crc(&page);
+  page[22..26].copy_from_slice(&crc.to_le_bytes());
+  page
+ }
+
+ fn ogg() -> Vec<u8> {
+  [
+   page(BOS, 0, 7, 0, &[19], b"OpusHead...........").as_slice(),
+   &page(0, u64::MAX, 7, 1, &[255, 255], &[1; 510]),
+   &page(CONTINUED, 960, 7, 2, &[10, 0, 3], b"0123456789xyz"),
+   &page(EOS, 1920, 7, 3, &[], b""),
+  ]
+  .concat()
+ }
+
+ #[test]
+ fn test_ogg_001() -> Result<(), Error> {
+  let data = ogg();
+  for i in [1, 3, 64, 4096] {
+   let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(&data)));
+   let mut ogg = OggReader::new(&mut bwr, i, false);
+   let mut pages = vec![];
+   let mut payload = vec![];
+   while let Some(p) = ogg.next_page()? {
+    if 2 == p.sequence {
+     assert_eq!(b"0123456789xyz", ogg.payload()?);
+     ogg.read_to_end(&mut payload)?;
+    }
+    pages.push((
+     p.header_type,
+     p.granule_position,
+     p.serial,
+     p.sequence,
+     p.offset,
+     p.size,
+     p.packet_sizes(),
+    ));
+   }
+   assert_eq!(
+    vec![
+     (BOS, Some(0), 7, 0, 0, 19, vec![19]),
+     (0, None, 7, 1, 47, 510, vec![510]),
+     (CONTINUED, Some(960), 7, 2, 586, 13, vec![10, 0, 3]),
+     (EOS, Some(1920), 7, 3, 629, 0, vec![]),
+    ],
+    pages
+   );
+   assert_eq!(b"0123456789xyz", &payload[..]);
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_ogg_resync() -> Result<(), Error> {
+  let mut broken = page(0, 5, 7, 1, &[3], b"bad");
+  broken[30] = b'B';
+  let data = [
+   &b"garbage OggS"[..],
+   &page(BOS, 0, 7, 0, &[2], b"ok"),
+   &broken,
+   b"Og",
+   &page(EOS, 10, 7, 2, &[4], b"last"),
+   b"trailing Ogg",
+  ]
+  .concat();
+  for i in [1, 5, 64, 4096] {
+   let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(&data)));
+   let mut ogg = OggReader::new(&mut bwr, i, true);
+   let mut pages = vec![];
+   while let Some(p) = ogg.next_page()? {
+    pages.push((p.sequence, p.offset, p.skipped, ogg.payload()?.to_vec()));
+   }
+   assert_eq!(vec![(0, 12, 12, b"ok".to_vec()), (2, 75, 33, b"last".to_vec())], pages);
+   assert_eq!(0, bwr.available_bytes());
+  }
+  let mut bwr = BlockWiseReader::from_slice(&broken);
+  assert_eq!("ogg page crc mismatch", msg(OggReader::new(&mut bwr, 16, false).next_page()));
+  Ok(())
+ }
+
+ #[test]
+ fn test_ogg_resync_read_failure() -> Result<(), Error> {
+  let data = [&page(BOS, 0, 7, 0, &[2], b"ok")[..], b"garbage OggS\0"].concat();
+  let mut bwr = BlockWiseReader::new(Box::new(Broken::new(&data)));
+  let mut ogg = OggReader::new(&mut bwr, 16, true);
+  assert_eq!(0, ogg.next_page()?.unwrap().sequence);
+  match ogg.next_page() {
+   Err(Error::IO(e)) => assert_eq!(std::io::ErrorKind::ConnectionReset, e.kind()),
+   _ => panic!(),
+  };
+  Ok(())
+ }
+
+ fn ogg_error(data: &[u8]) -> &'static str {
+  let mut bwr = BlockWiseReader::from_slice(data);
+  let mut ogg = OggReader::new(&mut bwr, 4, false);
+  first_msg(|| ogg.next_page())
+ }
+
+ #[test]
+ fn test_ogg_errors() {
+  let valid = page(BOS, 0, 1, 0, &[1], b"x");
+  assert_eq!("missing ogg capture pattern", ogg_error(b"RIFF"));
+  assert_eq!("unexpected end of file in ogg page", ogg_error(b"Og"));
+  assert_eq!("unexpected end of file in ogg page", ogg_error(&valid[..28]));
+  assert_eq!("unsupported ogg version", ogg_error(&[&b"OggS\x01"[..], &[0; 30]].concat()));
+  assert_eq!("missing ogg capture pattern", ogg_error(&[&valid[..], b"garbage, no page"].concat()));
+ }
+
+ #[test]
+ fn test_ogg_partly_read_page() -> Result<(), Error> {
+  let data = ogg();
+  for (step, buffersize) in [(1, 1), (5, 2), (200, 64)] {
+   let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(&data, step)));
+   let mut ogg = OggReader::new(&mut bwr, buffersize, false);
+   ogg.next_page()?.unwrap();
+   assert_eq!(1, ogg.next_page()?.unwrap().sequence);
+   let mut buf = [0; 9];
+   let mut read = vec![];
+   while read.len() < 300 {
+    let n = ogg.read(&mut buf)?;
+    assert!(n > 0 && n <= buffersize);
+    read.extend_from_slice(&buf[..n]);
+   }
+   assert_eq!(vec![1; read.len()], read);
+   // the rest of the payload is skipped
+   let p = ogg.next_page()?.unwrap();
+   assert_eq!((2, 586), (p.sequence, p.offset));
+   assert_eq!(b"0123456789xyz", ogg.payload()?);
+   assert_eq!(3, ogg.next_page()?.unwrap().sequence);
+   assert!(ogg.next_page()?.is_none());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_ogg_crc_mismatch_without_resync() -> Result<(), Error> {
+  let mut broken = page(0, 5, 7, 1, &[3], b"bad");
+  broken[30] = b'B';
+  let data = [page(BOS, 0, 7, 0, &[2], b"ok"), broken].concat();
+  let mut bwr = BlockWiseReader::from_slice(&data);
+  let mut ogg = OggReader::new(&mut bwr, 4, false);
+  ogg.next_page()?.unwrap();
+  // without resync the broken page remains at pos
+  assert_eq!("ogg page crc mismatch", msg(ogg.next_page()));
+  assert_eq!("ogg page crc mismatch", msg(ogg.next_page()));
+  assert_eq!((30, 0), (bwr.pos_absolute(), bwr.pos_get()));
+  Ok(())
+ }
+}
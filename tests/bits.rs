@@ -0,0 +1,170 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+ use crate::common::{msg, Trickle};
+ use blockwise_reader::bits::{BitOrder, BitReader};
+ use blockwise_reader::BlockWiseReader;
+ use blockwise_reader::Error;
+ use std::io::Cursor;
+
+ const FIELDS: [(u32, u64); 11] = [
+  (1, 1),
+  (3, 5),
+  (7, 0x55),
+  (8, 0xa7),
+  (13, 0x1abc),
+  (64, 0x0123_4567_89ab_cdef),
+  (5, 0x11),
+  (32, 0xdead_beef),
+  (17, 0x1_0001),
+  (63, 0x7fff_0000_ffff_0000),
+  (2, 2),
+ ];
+
+ fn pack(order: BitOrder, fields: &[(u32, u64)]) -> Vec<u8> {
+  let mut bits = vec![];
+  for (n, value) in fields {
+   for i in 0..*n {
+    match order {
+     BitOrder::MsbFirst => bits.push(value >> (n - 1 - i) & 1),
+     BitOrder::LsbFirst => bits.push(value >> i & 1),
+    }
+   }
+  }
+  bits
+   .chunks(8)
+   .map(|byte| {
+    byte
+     .iter()
+     .enumerate()
+     .fold(0u8, |acc, (i, bit)| match order {
+      BitOrder::MsbFirst => acc | (*bit as u8) << (7 - i),
+      BitOrder::LsbFirst => acc | (*bit as u8) << i,
+     })
+   })
+   .collect()
+ }
+
+ #[test]
+ fn test_bits_001() -> Result<(), Error> {
+  for order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+   let mut data = pack(order, &FIELDS);
+   data.extend_from_slice(b"rest");
+   for i in [1, 2, 5, 4096] {
+    let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(&data)));
+    let mut bits = BitReader::new(&mut bwr, i, order);
+    let mut position = 0;
+    for (n, value) in FIELDS {
+     assert_eq!(position, bits.bit_position());
+     assert_eq!(value, bits.peek_bits(n)?);
+     assert_eq!(value, bits.read_bits(n)?);
+     position += n as u64;
+    }
+    assert_eq!(1, bits.align());
+    assert!(bits.is_aligned());
+    assert_eq!(0, bits.align());
+    bits.finish();
+    assert_eq!(4, bwr.slurp_exact(4)?);
+    assert_eq!(b"rest", bwr.get());
+   }
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_bits_deflate_header() -> Result<(), Error> {
+  let mut bwr = BlockWiseReader::from_slice(b"\x4b\x04\x00");
+  let mut bits = BitReader::new(&mut bwr, 16, BitOrder::LsbFirst);
+  assert!(bits.read_bit()?);
+  assert_eq!(1, bits.read_bits(2)?);
+  bits.skip_bits(13)?;
+  assert_eq!(16, bits.bit_position());
+  assert_eq!(0, bits.read_bits(8)?);
+  Ok(())
+ }
+
+ #[test]
+ fn test_bits_exp_golomb() -> Result<(), Error> {
+  for i in [1, 4096] {
+   let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(b"\xa6\x43\xa6\x48")));
+   let mut bits = BitReader::new(&mut bwr, i, BitOrder::MsbFirst);
+   assert_eq!(
+    vec![0, 1, 2, 3, 6],
+    (0..5)
+     .map(|_| bits.read_ue())
+     .collect::<Result<Vec<_>, _>>()?
+   );
+   assert_eq!(
+    vec![1, -1, 2],
+    (0..3)
+     .map(|_| bits.read_se())
+     .collect::<Result<Vec<_>, _>>()?
+   );
+   assert!(bits.read_bit()?);
+   assert_eq!(3, bits.align());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_bits_errors() {
+  let mut bwr = BlockWiseReader::from_slice(b"\x01\x02");
+  let mut bits = BitReader::new(&mut bwr, 1, BitOrder::MsbFirst);
+  assert_eq!("more than 64 bits requested", msg(bits.read_bits(65)));
+  assert_eq!("unexpected end of file in bit stream", msg(bits.read_bits(17)));
+  assert_eq!("unexpected end of file in bit stream", msg(bits.skip_bits(17)));
+  let mut bwr = BlockWiseReader::from_slice(&[0; 9]);
+  let mut bits = BitReader::new(&mut bwr, 1, BitOrder::MsbFirst);
+  assert_eq!("invalid exp-golomb code", msg(bits.read_ue()));
+ }
+
+ #[test]
+ fn test_bits_lsb_fields_across_reads() -> Result<(), Error> {
+  let data = pack(BitOrder::LsbFirst, &FIELDS);
+  // the fields are split between the reads of the socket
+  for (step, buffersize) in [(1, 1), (3, 2), (5, 16)] {
+   let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(&data, step)));
+   let mut bits = BitReader::new(&mut bwr, buffersize, BitOrder::LsbFirst);
+   for (n, value) in FIELDS {
+    assert_eq!(value, bits.read_bits(n)?);
+   }
+   bits.finish();
+   assert_eq!(data.len() as u64, bwr.pos_absolute());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_bits_failed_read_consumes_nothing() -> Result<(), Error> {
+  let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(b"\xab\xcd\xef", 1)));
+  let mut bits = BitReader::new(&mut bwr, 1, BitOrder::MsbFirst);
+  assert_eq!(0xa, bits.read_bits(4)?);
+  // a failed read does not consume any bits
+  assert_eq!("unexpected end of file in bit stream", msg(bits.read_bits(21)));
+  assert_eq!("more than 64 bits requested", msg(bits.peek_bits(65)));
+  assert_eq!(4, bits.bit_position());
+  assert_eq!(0xbcdef, bits.read_bits(20)?);
+  assert_eq!("unexpected end of file in bit stream", msg(bits.read_bit()));
+  assert_eq!(24, bits.bit_position());
+  Ok(())
+ }
+
+ #[test]
+ fn test_bits_skip_beyond_eof() -> Result<(), Error> {
+  let data = [1, 35, 69, 103, 137, 171, 205];
+  for skip in [57, 60, 64, 1000] {
+   let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(&data, 2)));
+   let mut bits = BitReader::new(&mut bwr, 3, BitOrder::MsbFirst);
+   assert_eq!(0, bits.read_bits(3)?);
+   assert_eq!("unexpected end of file in bit stream", msg(bits.skip_bits(skip)));
+   assert_eq!(56, bits.bit_position());
+   assert!(bits.is_aligned());
+   assert!(bits.read_bit().is_err());
+   bits.finish();
+   assert_eq!(7, bwr.pos_absolute());
+   assert_eq!(0, bwr.available_bytes());
+  }
+  Ok(())
+ }
+}
@@ -0,0 +1,195 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+ use crate::common::{first_msg, msg, Trickle};
+ use blockwise_reader::bmff::{is_container, BoxHeader, BoxReader};
+ use blockwise_reader::BlockWiseReader;
+ use blockwise_reader::Error;
+ use std::io::{Cursor, Read};
+
+ fn bx(box_type: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+  [
+   &(8 + payload.len() as u32).to_be_bytes()[..],
+   box_type,
+   payload,
+  ]
+  .concat()
+ }
+
+ fn mp4() -> Vec<u8> {
+  let hdlr = bx(b"hdlr", &[0; 12]);
+  let moov = [
+   bx(b"mvhd", &[0; 100]),
+   bx(b"trak", &bx(b"tkhd", &[0; 8])),
+   bx(b"meta", &[&[0, 0, 0, 0], &hdlr[..]].concat()),
+   bx(b"udta", &bx(b"meta", &hdlr)),
+  ]
+  .concat();
+  let mut large = b"\0\0\0\x01free".to_vec();
+  large.extend_from_slice(&21u64.to_be_bytes());
+  large.extend_from_slice(b"abcde");
+  let uuid = [b"\0\0\0\x1buuid", &[7u8; 16][..], b"xyz"].concat();
+  let mdat = [b"\0\0\0\0mdat", &[9u8; 1000][..]].concat();
+  [
+   bx(b"ftyp", b"isom\0\0\0\0isomiso2"),
+   bx(b"moov", &moov),
+   large,
+   uuid,
+   mdat,
+  ]
+  .concat()
+ }
+
+ #[test]
+ fn test_bmff_001() -> Result<(), Error> {
+  let data = mp4();
+  for i in [1, 5, 16, 4096] {
+   let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(&data)));
+   let mut boxes = BoxReader::new(&mut bwr, i);
+   let mut found = vec![];
+   let mut uuid = None;
+   let mut mdat = vec![];
+   while let Some(b) = boxes.next_box()? {
+    if is_container(&b.box_type) {
+     boxes.enter()?;
+    }
+    match &b.box_type {
+     b"uuid" => {
+      let mut payload = String::new();
+      boxes.read_to_string(&mut payload)?;
+      uuid = Some((b.clone(), payload));
+     }
+     b"mdat" => {
+      boxes.read_to_end(&mut mdat)?;
+     }
+     _ => {}
+    }
+    found.push((String::from_utf8(b.box_type.to_vec()).unwrap(), b.depth, b.offset, b.size));
+   }
+   let found: Vec<_> = found
+    .iter()
+    .map(|(t, d, o, s)| (t.as_str(), *d, *o, *s))
+    .collect();
+   assert_eq!(
+    vec![
+     ("ftyp", 0, 0, Some(24)),
+     ("moov", 0, 24, Some(208)),
+     ("mvhd", 1, 32, Some(108)),
+     ("trak", 1, 140, Some(24)),
+     ("tkhd", 2, 148, Some(16)),
+     ("meta", 1, 164, Some(32)),
+     ("hdlr", 2, 176, Some(20)),
+     ("udta", 1, 196, Some(36)),
+     ("meta", 2, 204, Some(28)),
+     ("hdlr", 3, 212, Some(20)),
+     ("free", 0, 232, Some(21)),
+     ("uuid", 0, 253, Some(27)),
+     ("mdat", 0, 280, None),
+    ],
+    found
+   );
+   let (uuid, payload) = uuid.unwrap();
+   assert_eq!(
+    BoxHeader {
+     box_type: *b"uuid",
+     user_type: Some([7; 16]),
+     offset: 253,
+     size: Some(27),
+     payload_offset: 277,
+     depth: 0
+    },
+    uuid
+   );
+   assert_eq!("xyz", payload);
+   assert_eq!(vec![9u8; 1000], mdat);
+  }
+  Ok(())
+ }
+
+ fn bmff_error(data: &[u8]) -> &'static str {
+  let mut bwr = BlockWiseReader::from_slice(data);
+  let mut boxes = BoxReader::new(&mut bwr, 4);
+  first_msg(|| {
+   let b = boxes.next_box()?;
+   if let Some(b) = &b {
+    if is_container(&b.box_type) {
+     boxes.enter()?;
+    }
+   }
+   Ok(b)
+  })
+ }
+
+ #[test]
+ fn test_bmff_errors() {
+  assert_eq!("invalid box size", bmff_error(b"\0\0\0\x04free"));
+  assert_eq!("unexpected end of file in box header", bmff_error(b"\0\0\0\x01free\0\0"));
+  assert_eq!("unexpected end of file in box", bmff_error(b"\0\0\0\x10free\0\0"));
+  assert_eq!("box exceeds its container", bmff_error(&bx(b"moov", &bx(b"free", &[0; 8])[..12])));
+  let huge = [&b"\0\0\0\x01free"[..], &[0xff; 8]].concat();
+  assert_eq!("invalid box size", bmff_error(&bx(b"moov", &huge)));
+  assert_eq!("invalid box size", bmff_error(&[bx(b"free", b""), huge].concat()));
+  let large = [&b"\0\0\0\x01free"[..], &(u64::MAX - 8).to_be_bytes()].concat();
+  assert_eq!("box exceeds its container", bmff_error(&bx(b"moov", &large)));
+  assert_eq!(
+   "unexpected end of file in box",
+   bmff_error(&bx(b"moov", &[0, 0, 0, 8, b'f', b'r', b'e', b'e'])[..8])
+  );
+ }
+
+ #[test]
+ fn test_bmff_mdat_up_to_eof() -> Result<(), Error> {
+  let data = mp4();
+  for (step, buffersize) in [(1, 1), (7, 3), (300, 16)] {
+   let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(&data, step)));
+   let mut boxes = BoxReader::new(&mut bwr, buffersize);
+   assert_eq!(b"ftyp", &boxes.next_box()?.unwrap().box_type);
+   let mut buf = [0; 6];
+   boxes.read_exact(&mut buf)?;
+   assert_eq!(b"isom\0\0", &buf);
+   assert_eq!(b"moov", &boxes.next_box()?.unwrap().box_type);
+   while b"mdat" != &boxes.next_box()?.unwrap().box_type {}
+   // the box up to the end of the file is read in pieces of at most buffersize bytes
+   let mut mdat = vec![];
+   loop {
+    let n = boxes.read(&mut buf)?;
+    if 0 == n {
+     break;
+    }
+    assert!(n <= buffersize);
+    mdat.extend_from_slice(&buf[..n]);
+   }
+   assert_eq!(vec![9u8; 1000], mdat);
+   assert!(boxes.next_box()?.is_none());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_bmff_invalid_size_keeps_header() -> Result<(), Error> {
+  let data = [bx(b"free", b"ab"), b"\0\0\0\x04free".to_vec()].concat();
+  let mut bwr = BlockWiseReader::from_slice(&data);
+  let mut boxes = BoxReader::new(&mut bwr, 4);
+  boxes.next_box()?.unwrap();
+  assert_eq!("invalid box size", msg(boxes.next_box()));
+  // the invalid header remains at pos
+  assert_eq!("invalid box size", msg(boxes.next_box()));
+  assert_eq!((10, 0), (bwr.pos_absolute(), bwr.pos_get()));
+  Ok(())
+ }
+
+ #[test]
+ fn test_bmff_truncated_payload() -> Result<(), Error> {
+  let data = bx(b"free", b"abcdef");
+  let mut bwr = BlockWiseReader::from_slice(&data[..11]);
+  let mut boxes = BoxReader::new(&mut bwr, 2);
+  boxes.next_box()?.unwrap();
+  let mut payload = vec![];
+  assert!(boxes.read_to_end(&mut payload).is_err());
+  assert_eq!(b"abc", &payload[..]);
+  assert_eq!("unexpected end of file in box", msg(boxes.next_box()));
+  assert_eq!((11, 0), (bwr.pos_absolute(), bwr.available_bytes()));
+  Ok(())
+ }
+}
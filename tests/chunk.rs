@@ -0,0 +1,208 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+ use crate::common::{msg, Trickle};
+ use blockwise_reader::chunk::{ChunkFormat, ChunkHeader, ChunkReader, Endian, PNG_SIGNATURE};
+ use blockwise_reader::BlockWiseReader;
+ use blockwise_reader::Error;
+ use std::io::{Cursor, Read};
+
+ const PNG: &[u8] = b"\x89\x50\x4e\x47\x0d\x0a\x1a\x0a\x00\x00\x00\x0d\x49\x48\x44\x52\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\xc7\x1a\x68\x35\x00\x00\x00\x0a\x74\x45\x58\x74\x43\x6f\x6d\x6d\x65\x6e\x74\x00\x68\x69\xa2\xa2\x58\x66\x00\x00\x00\x00\x49\x45\x4e\x44\xae\x42\x60\x82";
+
+ fn png_chunks(data: &[u8], buffersize: usize) -> Result<Vec<(ChunkHeader, Vec<u8>)>, Error> {
+  let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(data)));
+  assert!(bwr.slurp_match_repos(PNG_SIGNATURE)?);
+  let format = ChunkFormat {
+   verify_crc: true,
+   ..ChunkFormat::PNG
+  };
+  let mut chunks = ChunkReader::new(&mut bwr, buffersize, format);
+  let mut ret = vec![];
+  while let Some(chunk) = chunks.next_chunk()? {
+   // only the text is read, the other payloads are skipped
+   let mut payload = vec![];
+   if b"tEXt" == &chunk.tag[..] {
+    chunks.read_to_end(&mut payload)?;
+   }
+   ret.push((chunk, payload));
+  }
+  Ok(ret)
+ }
+
+ #[test]
+ fn test_chunk_png() -> Result<(), Error> {
+  for i in 1..20 {
+   let chunks = png_chunks(PNG, i)?;
+   assert_eq!(3, chunks.len());
+   assert_eq!(
+    ChunkHeader {
+     tag: b"IHDR".to_vec(),
+     size: 13,
+     offset: 8,
+     payload_offset: 16,
+     depth: 0
+    },
+    chunks[0].0
+   );
+   assert_eq!((&b"tEXt"[..], 33), (&chunks[1].0.tag[..], chunks[1].0.offset));
+   assert_eq!(b"Comment\0hi", &chunks[1].1[..]);
+   assert_eq!((&b"IEND"[..], 0), (&chunks[2].0.tag[..], chunks[2].0.size));
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_chunk_png_errors() {
+  let mut broken = PNG.to_vec();
+  broken[20] = 0xff;
+  assert_eq!("chunk crc mismatch", msg(png_chunks(&broken, 8)));
+  assert_eq!("unexpected end of file in chunk", msg(png_chunks(&PNG[..PNG.len() - 2], 8)));
+  assert_eq!("unexpected end of file in chunk header", msg(png_chunks(&PNG[..PNG.len() - 8], 8)));
+ }
+
+ fn png_reader<'r, 'a>(
+  bwr: &'r mut BlockWiseReader<'a>,
+  buffersize: usize,
+ ) -> Result<ChunkReader<'r, 'a>, Error> {
+  bwr.slurp_exact(PNG_SIGNATURE.len())?;
+  assert!(bwr.slurp_match_repos(PNG_SIGNATURE)?);
+  let format = ChunkFormat {
+   verify_crc: true,
+   ..ChunkFormat::PNG
+  };
+  Ok(ChunkReader::new(bwr, buffersize, format))
+ }
+
+ #[test]
+ fn test_chunk_png_crc_of_partly_read_payload() -> Result<(), Error> {
+  for (step, buffersize) in [(1, 1), (3, 2), (5, 100)] {
+   let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(PNG, step)));
+   let mut chunks = png_reader(&mut bwr, buffersize)?;
+   chunks.next_chunk()?.unwrap();
+   let mut buf = [0; 3];
+   chunks.read_exact(&mut buf)?;
+   assert_eq!(b"\x00\x01\x02", &buf);
+   // the CRC of the partly read payload is verified by the next chunk
+   assert_eq!(&b"tEXt"[..], &chunks.next_chunk()?.unwrap().tag[..]);
+   let mut payload = vec![];
+   let n = chunks.read(&mut buf)?;
+   assert!(n >= 1 && n <= buffersize.min(3));
+   payload.extend_from_slice(&buf[..n]);
+   chunks.read_to_end(&mut payload)?;
+   assert_eq!(b"Comment\0hi", &payload[..]);
+   assert_eq!(&b"IEND"[..], &chunks.next_chunk()?.unwrap().tag[..]);
+   assert!(chunks.next_chunk()?.is_none());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_chunk_png_crc_mismatch_skips_chunk() -> Result<(), Error> {
+  let mut broken = PNG.to_vec();
+  broken[20] = 0xff;
+  let mut bwr = BlockWiseReader::from_slice(&broken);
+  let mut chunks = png_reader(&mut bwr, 4)?;
+  chunks.next_chunk()?.unwrap();
+  assert_eq!("chunk crc mismatch", msg(chunks.next_chunk()));
+  // the chunk with the wrong CRC is skipped by the next call
+  assert_eq!(&b"tEXt"[..], &chunks.next_chunk()?.unwrap().tag[..]);
+  assert_eq!(41, bwr.pos_absolute());
+  Ok(())
+ }
+
+ #[test]
+ fn test_chunk_png_truncated_payload() -> Result<(), Error> {
+  let mut bwr = BlockWiseReader::from_slice(&PNG[..46]);
+  let mut chunks = png_reader(&mut bwr, 4)?;
+  chunks.next_chunk()?.unwrap();
+  chunks.next_chunk()?.unwrap();
+  let mut payload = vec![];
+  assert!(chunks.read_to_end(&mut payload).is_err());
+  assert_eq!(b"Comme", &payload[..]);
+  // the CRC verification reads the rest of the payload
+  assert_eq!("unexpected end of file in chunk", msg(chunks.next_chunk()));
+  assert_eq!((46, 0), (bwr.pos_absolute(), bwr.available_bytes()));
+  Ok(())
+ }
+
+ const AVI: &[u8] = b"RIFF\x30\0\0\0AVI LIST\x12\0\0\0hdrlavih\x05\0\0\0abcde\0JUNK\x02\0\0\0xyidx1\0\0\0\0tail\0\0\0\0";
+
+ #[test]
+ fn test_chunk_riff() -> Result<(), Error> {
+  for i in 1..20 {
+   let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(AVI)));
+   let mut chunks = ChunkReader::new(&mut bwr, i, ChunkFormat::RIFF);
+   let mut tags = vec![];
+   while let Some(chunk) = chunks.next_chunk()? {
+    tags.push((String::from_utf8(chunk.tag.clone()).unwrap(), chunk.depth, chunk.offset));
+    if b"RIFF" == &chunk.tag[..] || b"LIST" == &chunk.tag[..] {
+     let mut list_type = [0u8; 4];
+     chunks.read_exact(&mut list_type)?;
+     chunks.enter();
+    }
+    if tags.len() == 5 {
+     break;
+    }
+   }
+   let tags: Vec<_> = tags.iter().map(|(t, d, o)| (t.as_str(), *d, *o)).collect();
+   assert_eq!(
+    vec![
+     ("RIFF", 0, 0),
+     ("LIST", 1, 12),
+     ("avih", 2, 24),
+     ("JUNK", 1, 38),
+     ("idx1", 1, 48)
+    ],
+    tags
+   );
+   assert!(chunks.next_chunk()?.is_some());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_chunk_custom() -> Result<(), Error> {
+  let format = ChunkFormat {
+   tag_size: 1,
+   length_size: 2,
+   endian: Endian::Little,
+   length_first: false,
+   length_includes_header: true,
+   alignment: 4,
+   crc: false,
+   verify_crc: false,
+  };
+  let mut bwr = BlockWiseReader::from_slice(b"a\x05\0xx\0\0b\x03\0");
+  let mut chunks = ChunkReader::new(&mut bwr, 4, format);
+  let a = chunks.next_chunk()?.unwrap();
+  assert_eq!((2, 3), (a.size, a.payload_offset));
+  let b = chunks.next_chunk()?.unwrap();
+  assert_eq!((0, 7), (b.size, b.offset));
+  assert!(chunks.next_chunk()?.is_none());
+
+  let mut bwr = BlockWiseReader::from_slice(b"a\x02\0");
+  let mut chunks = ChunkReader::new(&mut bwr, 4, format);
+  assert_eq!("invalid chunk length", msg(chunks.next_chunk()));
+
+  let huge = [&b"a"[..], &(u64::MAX - 2).to_le_bytes()].concat();
+  let mut bwr = BlockWiseReader::from_slice(&huge);
+  let wide = ChunkFormat {
+   length_size: 8,
+   length_includes_header: false,
+   ..format
+  };
+  let mut chunks = ChunkReader::new(&mut bwr, 4, wide);
+  assert_eq!("invalid chunk length", msg(chunks.next_chunk()));
+  let mut chunks = ChunkReader::new(
+   &mut bwr,
+   4,
+   ChunkFormat {
+    length_size: 9,
+    ..format
+   },
+  );
+  assert_eq!("invalid chunk length size", msg(chunks.next_chunk()));
+  Ok(())
+ }
+}
@@ -0,0 +1,81 @@
+// helpers shared by the test crates, not every crate uses all of them
+#![allow(dead_code)]
+
+use blockwise_reader::Error;
+use std::io::Read;
+
+/// Returns the message of an Error::Msg, it panics on success and on io errors.
+pub fn msg<T>(res: Result<T, Error>) -> &'static str {
+ match res {
+  Err(Error::Msg(x)) => x,
+  Err(Error::IO(e)) => panic!("io error instead of a message: {e}"),
+  Ok(_) => panic!("no error"),
+ }
+}
+
+/// Calls next until it fails and returns the message of the error.
+/// It panics if next reaches the end of the stream before or fails with an io error.
+pub fn first_msg<T>(mut next: impl FnMut() -> Result<Option<T>, Error>) -> &'static str {
+ loop {
+  match next() {
+   Ok(Some(_)) => {}
+   Ok(None) => panic!("end of stream instead of an error"),
+   res => return msg(res),
+  }
+ }
+}
+
+/// A reader which returns at most step bytes per read like a slow socket.
+/// It panics if it is read more often than allowed by limit.
+pub struct Trickle<'d> {
+ data: &'d [u8],
+ step: usize,
+ limit: usize,
+}
+
+impl<'d> Trickle<'d> {
+ pub fn new(data: &'d [u8], step: usize) -> Self {
+  Self::limited(data, step, usize::MAX)
+ }
+
+ pub fn limited(data: &'d [u8], step: usize, limit: usize) -> Self {
+  Self { data, step, limit }
+ }
+}
+
+impl Read for Trickle<'_> {
+ fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+  let n = buf.len().min(self.data.len()).min(self.step);
+  if 0 == n {
+   return Ok(0);
+  }
+  assert!(self.limit > 0, "read beyond the limit");
+  self.limit -= 1;
+  buf[..n].copy_from_slice(&self.data[..n]);
+  self.data = &self.data[n..];
+  Ok(n)
+ }
+}
+
+/// A reader which returns the data and fails afterwards like a broken connection.
+pub struct Broken<'d> {
+ data: &'d [u8],
+}
+
+impl<'d> Broken<'d> {
+ pub fn new(data: &'d [u8]) -> Self {
+  Self { data }
+ }
+}
+
+impl Read for Broken<'_> {
+ fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+  if self.data.is_empty() {
+   return Err(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "broken"));
+  }
+  let n = buf.len().min(self.data.len());
+  buf[..n].copy_from_slice(&self.data[..n]);
+  self.data = &self.data[n..];
+  Ok(n)
+ }
+}
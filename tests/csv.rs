@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod tests {
+ use blockwise_reader::csv::{CsvConfig, CsvReader, CsvRecord};
+ use blockwise_reader::BlockWiseReader;
+ use blockwise_reader::Error;
+ use stringreader::StringReader;
+
+ #[test]
+ fn test_csv_001() -> Result<(), Error> {
+  let config = CsvConfig {
+   delimiter: b';',
+   quote: b'\'',
+   fields: true,
+   max_record_size: 100,
+  };
+  for i in 1..30 {
+   let sr = StringReader::new("a;'b;\n''c'''\n\nd;;e\r\n");
+   let mut bwr = BlockWiseReader::new(Box::new(sr));
+   let records = CsvReader::new(&mut bwr, i, config).collect::<Result<Vec<_>, Error>>()?;
+   assert_eq!(
+    vec![
+     CsvRecord {
+      range: 0..12,
+      fields: vec![0..1, 2..12]
+     },
+     CsvRecord {
+      range: 13..13,
+      fields: vec![13..13; 1]
+     },
+     CsvRecord {
+      range: 14..18,
+      fields: vec![14..15, 16..16, 17..18]
+     },
+    ],
+    records
+   );
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_csv_002() -> Result<(), Error> {
+  let sr = StringReader::new("skip\nx,y\n\"z\"");
+  let mut bwr = BlockWiseReader::new(Box::new(sr));
+  assert!(bwr.slurp_find_repos1(100, b'\n')?);
+  let mut csv = CsvReader::new(&mut bwr, 2, CsvConfig::default());
+  let record = csv.next_record()?.unwrap();
+  assert_eq!(5..8, record.range);
+  assert!(record.fields.is_empty());
+  assert_eq!("x,y".as_bytes(), csv.record());
+  assert_eq!(Some(9..12), csv.next_record()?.map(|r| r.range));
+  assert_eq!("\"z\"".as_bytes(), csv.record());
+  assert_eq!(None, csv.next_record()?);
+  assert_eq!(12, bwr.pos_absolute());
+  Ok(())
+ }
+
+ fn csv_error(data: &'static str) -> &'static str {
+  let config = CsvConfig {
+   max_record_size: 8,
+   ..CsvConfig::default()
+  };
+  let sr = StringReader::new(data);
+  let mut bwr = BlockWiseReader::new(Box::new(sr));
+  for record in CsvReader::new(&mut bwr, 3, config) {
+   if let Err(Error::Msg(x)) = record {
+    return x;
+   }
+  }
+  panic!()
+ }
+
+ #[test]
+ fn test_csv_errors() {
+  assert_eq!("csv record exceeds max_record_size", csv_error("1234567\n12345678\n"));
+  assert_eq!("csv record exceeds max_record_size", csv_error("\"12\n345\n678\"\n"));
+  assert_eq!("unterminated quote in csv record", csv_error("a,\"b\n"));
+ }
+}
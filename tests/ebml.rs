@@ -0,0 +1,227 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+ use crate::common::{first_msg, msg, Trickle};
+ use blockwise_reader::ebml::*;
+ use blockwise_reader::BlockWiseReader;
+ use blockwise_reader::Error;
+ use std::io::Cursor;
+
+ const UNKNOWN: &[u8] = b"\x01\xff\xff\xff\xff\xff\xff\xff";
+
+ fn id_bytes(id: u32) -> Vec<u8> {
+  let bytes = id.to_be_bytes();
+  bytes[id.leading_zeros() as usize / 8..].to_vec()
+ }
+
+ fn el(id: u32, data: &[u8]) -> Vec<u8> {
+  let size = if data.len() < 127 {
+   vec![0x80 | data.len() as u8]
+  } else {
+   (0x4000 | data.len() as u16).to_be_bytes().to_vec()
+  };
+  [id_bytes(id), size, data.to_vec()].concat()
+ }
+
+ fn webm() -> Vec<u8> {
+  let info = [
+   el(TIMESTAMP_SCALE, b"\x0f\x42\x40"),
+   el(DURATION, &1234.5f64.to_be_bytes()),
+   el(DATE_UTC, &(-1_000_000_000i64).to_be_bytes()),
+   el(MUXING_APP, b"mux\0\0"),
+   el(VOID, &[0; 200]),
+  ]
+  .concat();
+  let track = [
+   el(TRACK_NUMBER, b"\x01"),
+   el(CODEC_ID, b"V_VP9"),
+   el(VIDEO, &el(0xb0, b"\x02\x80")),
+  ]
+  .concat();
+  let cluster = [el(0xe7, b"\x00"), el(0xa3, &[7; 20])].concat();
+  [
+   el(EBML, &el(DOC_TYPE, b"webm")),
+   id_bytes(SEGMENT),
+   UNKNOWN.to_vec(),
+   el(INFO, &info),
+   el(TRACKS, &el(TRACK_ENTRY, &track)),
+   id_bytes(CLUSTER),
+   UNKNOWN.to_vec(),
+   cluster.clone(),
+   id_bytes(CLUSTER),
+   UNKNOWN.to_vec(),
+   cluster,
+   el(CUES, &el(0xbb, b"")),
+  ]
+  .concat()
+ }
+
+ #[test]
+ fn test_ebml_webm() -> Result<(), Error> {
+  let data = webm();
+  for i in [1, 3, 64, 4096] {
+   let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(&data)));
+   let mut ebml = EbmlReader::new(&mut bwr, i, EbmlConfig::default());
+   let mut tree = vec![];
+   while let Some(e) = ebml.next_element()? {
+    tree.push((e.id, e.depth));
+    match e.id {
+     DOC_TYPE => assert_eq!("webm", ebml.read_string()?),
+     TIMESTAMP_SCALE => assert_eq!(1_000_000, ebml.read_uint()?),
+     DURATION => assert_eq!(1234.5, ebml.read_float()?),
+     DATE_UTC => assert_eq!(-1_000_000_000, ebml.read_date()?),
+     MUXING_APP => assert_eq!("mux", ebml.read_string()?),
+     CODEC_ID => {
+      assert_eq!(vec![SEGMENT, TRACKS, TRACK_ENTRY], ebml.path());
+      assert_eq!("V_VP9", ebml.read_string()?);
+     }
+     0xb0 => assert_eq!(640, ebml.read_int()?),
+     0xa3 => assert_eq!(Some(20), e.size),
+     CLUSTER => assert_eq!(None, e.size),
+     _ => {}
+    }
+   }
+   assert_eq!(
+    vec![
+     (EBML, 0),
+     (DOC_TYPE, 1),
+     (SEGMENT, 0),
+     (INFO, 1),
+     (TIMESTAMP_SCALE, 2),
+     (DURATION, 2),
+     (DATE_UTC, 2),
+     (MUXING_APP, 2),
+     (VOID, 2),
+     (TRACKS, 1),
+     (TRACK_ENTRY, 2),
+     (TRACK_NUMBER, 3),
+     (CODEC_ID, 3),
+     (VIDEO, 3),
+     (0xb0, 4),
+     (CLUSTER, 1),
+     (0xe7, 2),
+     (0xa3, 2),
+     (CLUSTER, 1),
+     (0xe7, 2),
+     (0xa3, 2),
+     (CUES, 1),
+     (0xbb, 2),
+    ],
+    tree
+   );
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_ebml_max_depth() -> Result<(), Error> {
+  let data = webm();
+  let mut bwr = BlockWiseReader::from_slice(&data);
+  let config = EbmlConfig {
+   max_depth: 1,
+   ..EbmlConfig::default()
+  };
+  let mut ebml = EbmlReader::new(&mut bwr, 16, config);
+  let mut tree = vec![];
+  while let Some(e) = ebml.next_element()? {
+   tree.push((e.id, e.offset));
+  }
+  assert_eq!(
+   vec![
+    (EBML, 0),
+    (DOC_TYPE, 5),
+    (SEGMENT, 12),
+    (INFO, 24),
+    (TRACKS, 270),
+    (CLUSTER, 293),
+    (CLUSTER, 330),
+    (CUES, 367)
+   ],
+   tree
+  );
+  Ok(())
+ }
+
+ fn ebml_error(data: &[u8]) -> &'static str {
+  let mut bwr = BlockWiseReader::from_slice(data);
+  let mut ebml = EbmlReader::new(&mut bwr, 4, EbmlConfig::default());
+  first_msg(|| ebml.next_element())
+ }
+
+ #[test]
+ fn test_ebml_errors() {
+  assert_eq!("invalid ebml element id", ebml_error(b"\x08\0\0\0\0\x80"));
+  assert_eq!("invalid ebml element size", ebml_error(b"\xec\x00"));
+  assert_eq!("unexpected end of file in ebml element header", ebml_error(b"\x1a\x45"));
+  assert_eq!("unexpected end of file in ebml element", ebml_error(b"\xec\x85\0"));
+  assert_eq!("unknown size of a non master ebml element", ebml_error(b"\xec\xff"));
+  assert_eq!(
+   "unexpected end of file in ebml element",
+   ebml_error(&el(INFO, &el(VOID, b"12"))[..7])
+  );
+  assert_eq!(
+   "ebml element exceeds its parent",
+   ebml_error(b"\x15\x49\xa9\x66\x82\xec\x82\x31\x32")
+  );
+  let mut bwr = BlockWiseReader::from_slice(b"\xec\x83abc");
+  let mut ebml = EbmlReader::new(&mut bwr, 4, EbmlConfig::default());
+  ebml.next_element().unwrap();
+  assert_eq!("invalid ebml float", msg(ebml.read_float()));
+ }
+
+ #[test]
+ fn test_ebml_values_across_reads() -> Result<(), Error> {
+  let data = webm();
+  // the values and the headers are split between the reads of the socket
+  for (step, buffersize) in [(1, 1), (3, 2), (7, 100)] {
+   let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(&data, step)));
+   let mut ebml = EbmlReader::new(&mut bwr, buffersize, EbmlConfig::default());
+   let mut count = 0;
+   while let Some(e) = ebml.next_element()? {
+    count += 1;
+    match e.id {
+     DURATION => assert_eq!(1234.5, ebml.read_float()?),
+     MUXING_APP => assert_eq!("mux", ebml.read_string()?),
+     0xa3 => assert_eq!(&[7; 20][..], ebml.read_binary()?),
+     _ => {}
+    }
+   }
+   assert_eq!(23, count);
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_ebml_invalid_values() -> Result<(), Error> {
+  let data = [el(0xec, b"abc"), el(0xec, &[1; 20]), el(0xbb, b"")].concat();
+  let config = EbmlConfig {
+   max_value_size: 10,
+   ..EbmlConfig::default()
+  };
+  let mut bwr = BlockWiseReader::from_slice(&data);
+  let mut ebml = EbmlReader::new(&mut bwr, 4, config);
+  ebml.next_element()?.unwrap();
+  // the data of an invalid value are consumed
+  assert_eq!("invalid ebml float", msg(ebml.read_float()));
+  assert_eq!(0.0, ebml.read_float()?);
+  // a value above the limit remains and is skipped by the next element
+  assert_eq!(5, ebml.next_element()?.unwrap().offset);
+  assert_eq!("ebml value exceeds max_value_size", msg(ebml.read_binary()));
+  assert_eq!(27, ebml.next_element()?.unwrap().offset);
+  assert!(ebml.next_element()?.is_none());
+  Ok(())
+ }
+
+ #[test]
+ fn test_ebml_truncated_child() -> Result<(), Error> {
+  let data = el(INFO, &el(VOID, b"12"));
+  let mut bwr = BlockWiseReader::from_slice(&data[..7]);
+  let mut ebml = EbmlReader::new(&mut bwr, 4, EbmlConfig::default());
+  ebml.next_element()?.unwrap();
+  ebml.next_element()?.unwrap();
+  assert_eq!("unexpected end of file in ebml element", msg(ebml.next_element()));
+  assert_eq!((7, 0), (bwr.pos_absolute(), bwr.available_bytes()));
+  Ok(())
+ }
+}
@@ -0,0 +1,185 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+ use crate::common::{first_msg, msg};
+ use blockwise_reader::http::{
+  read_request, read_response, ChunkedReader, HeadLimits, Header, Version,
+ };
+ use blockwise_reader::BlockWiseReader;
+ use blockwise_reader::Error;
+ use std::io::Read;
+ use stringreader::StringReader;
+
+ #[test]
+ fn test_http_request_001() -> Result<(), Error> {
+  for i in 1..20 {
+   let sr = StringReader::new(
+    "\r\nPOST /upload?x=1 HTTP/1.0\r\nHost: example.com\r\nContent-Length:  4 \r\nX-Empty:\r\n\r\nbody",
+   );
+   let mut bwr = BlockWiseReader::new(Box::new(sr));
+   let request = read_request(&mut bwr, i, &HeadLimits::default())?;
+   assert_eq!(b"POST", request.method);
+   assert_eq!(b"/upload?x=1", request.target);
+   assert_eq!(Version::Http10, request.version);
+   assert_eq!(
+    vec![
+     Header {
+      name: b"Host",
+      value: b"example.com"
+     },
+     Header {
+      name: b"Content-Length",
+      value: b"4"
+     },
+     Header {
+      name: b"X-Empty",
+      value: b""
+     },
+    ],
+    request.headers
+   );
+   assert_eq!(Some("4".as_bytes()), request.header(b"content-length"));
+   assert_eq!(None, request.header(b"Content-Type"));
+   assert_eq!(4, bwr.slurp(4)?);
+   assert_eq!("body".as_bytes(), bwr.get());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_http_response_001() -> Result<(), Error> {
+  let mut bwr = BlockWiseReader::from_slice(
+   b"HTTP/1.1 404 Not Found\r\nX-Folded: first\r\n  second\r\n\tthird\r\nServer: x\r\n\r\n",
+  );
+  let response = read_response(&mut bwr, 8, &HeadLimits::default())?;
+  assert_eq!(Version::Http11, response.version);
+  assert_eq!(404, response.status);
+  assert_eq!(b"Not Found", response.reason);
+  assert_eq!(2, response.headers.len());
+  assert_eq!("first second third".as_bytes(), &response.headers[0].unfolded()[..]);
+  assert_eq!(Some("x".as_bytes()), response.header(b"server"));
+  assert_eq!(0, bwr.available_bytes());
+  Ok(())
+ }
+
+ #[test]
+ fn test_http_response_002() -> Result<(), Error> {
+  let mut bwr = BlockWiseReader::from_slice(b"HTTP/1.1 204\r\n\r\n");
+  let response = read_response(&mut bwr, 8, &HeadLimits::default())?;
+  assert_eq!(204, response.status);
+  assert_eq!(b"", response.reason);
+  Ok(())
+ }
+
+ fn request_error(data: &'static str, limits: HeadLimits) -> &'static str {
+  let sr = StringReader::new(data);
+  let mut bwr = BlockWiseReader::new(Box::new(sr));
+  let x = msg(read_request(&mut bwr, 4, &limits));
+  assert_eq!(0, bwr.pos_get());
+  x
+ }
+
+ #[test]
+ fn test_http_errors() {
+  let limits = HeadLimits::default();
+  assert_eq!(
+   "unexpected end of file in http head",
+   request_error("GET / HTTP/1.1\r\nHost: x\r\n", limits)
+  );
+  assert_eq!("invalid http request line", request_error("GET /\r\n\r\n", limits));
+  assert_eq!("unsupported http version", request_error("GET / HTTP/2.0\r\n\r\n", limits));
+  assert_eq!(
+   "invalid header field name",
+   request_error("GET / HTTP/1.1\r\nHost : x\r\n\r\n", limits)
+  );
+  assert_eq!("header field without colon", request_error("GET / HTTP/1.1\r\nHost\r\n\r\n", limits));
+  assert_eq!(
+   "bare CR or LF in http head",
+   request_error("GET / HTTP/1.1\nHost: x\r\n\r\n", limits)
+  );
+  assert_eq!(
+   "obsolete line folding without header field",
+   request_error("GET / HTTP/1.1\r\n x\r\n\r\n", limits)
+  );
+  let small = HeadLimits {
+   max_head_size: 30,
+   max_headers: 1,
+  };
+  assert_eq!(
+   "http head exceeds max_head_size",
+   request_error("GET / HTTP/1.1\r\nHost: example.com\r\n\r\n", small)
+  );
+  assert_eq!(
+   "http head exceeds max_headers",
+   request_error("GET / HTTP/1.1\r\na:\r\nb:\r\n\r\n", small)
+  );
+ }
+
+ const CHUNKED: &str = "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4;name=value\r\nWiki\r\n6 \r\npedia \r\nE\r\nin \r\n\r\nchunks.\r\n0\r\nExpires: never\r\nX-Sum: 1\r\n\r\nNEXT";
+
+ #[test]
+ fn test_http_chunked_001() -> Result<(), Error> {
+  for i in 1..20 {
+   let sr = StringReader::new(CHUNKED);
+   let mut bwr = BlockWiseReader::new(Box::new(sr));
+   let request = read_request(&mut bwr, i, &HeadLimits::default())?;
+   assert_eq!(Some("chunked".as_bytes()), request.header(b"transfer-encoding"));
+   let mut cr = ChunkedReader::new(&mut bwr, i, 1024, HeadLimits::default());
+   let mut body = String::new();
+   cr.read_to_string(&mut body)?;
+   assert_eq!("Wikipedia in \r\n\r\nchunks.", body);
+   assert!(cr.is_done());
+   assert_eq!(
+    vec![
+     Header {
+      name: b"Expires",
+      value: b"never"
+     },
+     Header {
+      name: b"X-Sum",
+      value: b"1"
+     }
+    ],
+    cr.trailers()
+   );
+   assert_eq!(4, bwr.slurp(4)?);
+   assert_eq!("NEXT".as_bytes(), bwr.get());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_http_chunked_002() -> Result<(), Error> {
+  let mut bwr = BlockWiseReader::from_slice(b"3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n");
+  let mut cr = ChunkedReader::new(&mut bwr, 2, 3, HeadLimits::default());
+  let mut buf = [0u8; 2];
+  assert_eq!(2, cr.read(&mut buf)?);
+  assert_eq!(b"ab", &buf);
+  assert_eq!(Some("c".as_bytes()), cr.read_chunk()?);
+  assert_eq!(Some("de".as_bytes()), cr.read_chunk()?);
+  assert_eq!(None, cr.read_chunk()?);
+  assert!(cr.trailers().is_empty());
+  assert_eq!(0, cr.read(&mut buf)?);
+  Ok(())
+ }
+
+ fn chunked_error(data: &'static str) -> &'static str {
+  let sr = StringReader::new(data);
+  let mut bwr = BlockWiseReader::new(Box::new(sr));
+  let mut cr = ChunkedReader::new(&mut bwr, 4, 8, HeadLimits::default());
+  first_msg(|| cr.read_chunk().map(|chunk| chunk.map(|_| ())))
+ }
+
+ #[test]
+ fn test_http_chunked_errors() {
+  assert_eq!("chunk exceeds max_chunk_size", chunked_error("9\r\n123456789\r\n0\r\n\r\n"));
+  assert_eq!("invalid chunk size", chunked_error("x\r\n"));
+  assert_eq!("invalid chunk size", chunked_error("\r\n"));
+  assert_eq!("missing CRLF behind chunk data", chunked_error("2\r\n123\r\n0\r\n\r\n"));
+  assert_eq!("unexpected end of file in chunked body", chunked_error("5\r\n123"));
+  assert_eq!("unexpected end of file in chunked body", chunked_error("2\r\n12\r\n"));
+  assert_eq!("unexpected end of file in trailer section", chunked_error("0\r\nA: b\r\n"));
+  assert_eq!("header field without colon", chunked_error("0\r\nA\r\n\r\n"));
+ }
+}
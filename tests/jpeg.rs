@@ -0,0 +1,170 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+ use crate::common::{first_msg, msg, Trickle};
+ use blockwise_reader::jpeg::{JpegReader, APP0, APP1, COM, DQT, SOF0, SOI, SOS};
+ use blockwise_reader::BlockWiseReader;
+ use blockwise_reader::Error;
+ use std::io::{Cursor, Read};
+
+ const ENTROPY: &[u8] = b"\x12\xff\x00\x34\xff\xd0\xff\xd9";
+
+ fn segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+  [
+   &[0xff, marker][..],
+   &(payload.len() as u16 + 2).to_be_bytes(),
+   payload,
+  ]
+  .concat()
+ }
+
+ fn jpeg() -> Vec<u8> {
+  [
+   &b"\xff\xd8"[..],
+   &segment(APP0, b"JFIF\0\x01\x02\0\0\x01\0\x01\0\0"),
+   b"\xff\xff",
+   &segment(APP1, b"Exif\0\0II*\0\x08\0\0\0"),
+   &segment(APP1, b"http://ns.adobe.com/xap/1.0/\0<x:xmp>"),
+   b"\xff\x01",
+   &segment(DQT, &[1; 65]),
+   &segment(SOF0, &[2; 9]),
+   &segment(SOS, &[3; 6]),
+   ENTROPY,
+  ]
+  .concat()
+ }
+
+ #[test]
+ fn test_jpeg_001() -> Result<(), Error> {
+  let data = jpeg();
+  for i in [1, 3, 16, 4096] {
+   let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(&data)));
+   let mut jpeg = JpegReader::new(&mut bwr, i);
+   let mut segments = vec![];
+   let mut exif = vec![];
+   let mut xmp = vec![];
+   let mut dqt = vec![];
+   while let Some(segment) = jpeg.next_segment()? {
+    if let Some(x) = jpeg.exif()? {
+     exif = x.to_vec();
+    }
+    if let Some(x) = jpeg.xmp()? {
+     xmp = x.to_vec();
+    }
+    if DQT == segment.marker {
+     jpeg.read_to_end(&mut dqt)?;
+    }
+    segments.push((
+     segment.marker,
+     segment.offset,
+     segment.size,
+     segment.is_app(),
+     segment.is_sof(),
+    ));
+   }
+   assert_eq!(
+    vec![
+     (SOI, 0, 0, false, false),
+     (APP0, 2, 14, true, false),
+     (APP1, 22, 14, true, false),
+     (APP1, 40, 36, true, false),
+     (0x01, 80, 0, false, false),
+     (DQT, 82, 65, false, false),
+     (SOF0, 151, 9, false, true),
+     (SOS, 164, 6, false, false),
+    ],
+    segments
+   );
+   assert_eq!(b"II*\0\x08\0\0\0", &exif[..]);
+   assert_eq!(b"<x:xmp>", &xmp[..]);
+   assert_eq!(vec![1; 65], dqt);
+   assert!(jpeg.next_segment()?.is_none());
+   assert_eq!(ENTROPY.len(), bwr.slurp_exact(100)?);
+   assert_eq!(ENTROPY, bwr.get());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_jpeg_eoi() -> Result<(), Error> {
+  let mut bwr = BlockWiseReader::from_slice(b"\xff\xd8\xff\xfe\x00\x04hi\xff\xd9rest");
+  let mut jpeg = JpegReader::new(&mut bwr, 16);
+  assert_eq!(SOI, jpeg.next_segment()?.unwrap().marker);
+  assert_eq!(COM, jpeg.next_segment()?.unwrap().marker);
+  assert_eq!(b"hi", jpeg.payload()?);
+  assert_eq!(0xd9, jpeg.next_segment()?.unwrap().marker);
+  assert!(jpeg.next_segment()?.is_none());
+  assert_eq!(b"rest", bwr.get());
+  Ok(())
+ }
+
+ fn jpeg_error(data: &[u8]) -> &'static str {
+  let mut bwr = BlockWiseReader::from_slice(data);
+  let mut jpeg = JpegReader::new(&mut bwr, 4);
+  first_msg(|| jpeg.next_segment())
+ }
+
+ #[test]
+ fn test_jpeg_errors() {
+  assert_eq!("missing jpeg start of image", jpeg_error(b"\x89PNG"));
+  assert_eq!("invalid jpeg marker", jpeg_error(b"\xff\xd8\x00"));
+  assert_eq!("invalid jpeg marker", jpeg_error(b"\xff\xd8\xff\x00"));
+  assert_eq!("invalid jpeg segment length", jpeg_error(b"\xff\xd8\xff\xe0\x00\x01"));
+  assert_eq!("unexpected end of file in jpeg segment", jpeg_error(b"\xff\xd8\xff\xe0\x00\x10JFIF"));
+  assert_eq!("unexpected end of file in jpeg segment", jpeg_error(b"\xff\xd8\xff\xff"));
+ }
+
+ #[test]
+ fn test_jpeg_dqt_in_pieces() -> Result<(), Error> {
+  let data = jpeg();
+  for (step, buffersize) in [(1, 1), (5, 2), (100, 7)] {
+   let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(&data, step)));
+   let mut jpeg = JpegReader::new(&mut bwr, buffersize);
+   while DQT != jpeg.next_segment()?.unwrap().marker {}
+   let mut buf = [0; 10];
+   let mut dqt = vec![];
+   while dqt.len() < 30 {
+    let n = jpeg.read(&mut buf)?;
+    assert!(n > 0 && n <= buffersize);
+    dqt.extend_from_slice(&buf[..n]);
+   }
+   assert_eq!(vec![1; dqt.len()], dqt);
+   // the rest of the payload is skipped
+   let sof = jpeg.next_segment()?.unwrap();
+   assert_eq!((SOF0, 151), (sof.marker, sof.offset));
+   assert_eq!(&[2; 9], jpeg.payload()?);
+   assert_eq!(SOS, jpeg.next_segment()?.unwrap().marker);
+   assert!(jpeg.next_segment()?.is_none());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_jpeg_invalid_marker_behind_payload() -> Result<(), Error> {
+  let data = [&b"\xff\xd8"[..], &segment(COM, b"hi"), b"\x00\xff\xd9"].concat();
+  let mut bwr = BlockWiseReader::from_slice(&data);
+  let mut jpeg = JpegReader::new(&mut bwr, 4);
+  jpeg.next_segment()?.unwrap();
+  jpeg.next_segment()?.unwrap();
+  assert_eq!("invalid jpeg marker", msg(jpeg.next_segment()));
+  // the payload in front of the invalid marker is skipped and dropped
+  assert_eq!("invalid jpeg marker", msg(jpeg.next_segment()));
+  assert_eq!((8, 0), (bwr.pos_absolute(), bwr.pos_get()));
+  Ok(())
+ }
+
+ #[test]
+ fn test_jpeg_truncated_segment() -> Result<(), Error> {
+  let data = [&b"\xff\xd8"[..], &segment(COM, b"comment")[..7]].concat();
+  let mut bwr = BlockWiseReader::from_slice(&data);
+  let mut jpeg = JpegReader::new(&mut bwr, 2);
+  jpeg.next_segment()?.unwrap();
+  jpeg.next_segment()?.unwrap();
+  let mut payload = vec![];
+  assert!(jpeg.read_to_end(&mut payload).is_err());
+  assert_eq!(b"com", &payload[..]);
+  assert_eq!("unexpected end of file in jpeg segment", msg(jpeg.next_segment()));
+  Ok(())
+ }
+}
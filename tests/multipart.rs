@@ -0,0 +1,105 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+ use crate::common::msg;
+ use blockwise_reader::http::{HeadLimits, Header};
+ use blockwise_reader::multipart::{boundary, Multipart};
+ use blockwise_reader::BlockWiseReader;
+ use blockwise_reader::Error;
+ use std::io::Read;
+ use stringreader::StringReader;
+
+ const MAIL: &str = "This is the preamble.\r\n--frontier\r\nContent-Type: text/plain\r\n\r\nThis is the body.\r\n--front\r\n--frontier \t\r\n\r\nno header\r\n--frontier\r\nContent-Type: application/octet-stream\r\nContent-Transfer-Encoding: base64\r\n\r\nPGh0bWw+\r\n--frontier--\r\nThis is the epilogue.";
+
+ #[test]
+ fn test_multipart_001() -> Result<(), Error> {
+  for i in 15..40 {
+   let sr = StringReader::new(MAIL);
+   let mut bwr = BlockWiseReader::new(Box::new(sr));
+   let mut multipart = Multipart::new(&mut bwr, b"frontier", i, HeadLimits::default())?;
+
+   let mut part = multipart.next_part()?.unwrap();
+   assert_eq!(
+    vec![Header {
+     name: b"Content-Type",
+     value: b"text/plain"
+    }],
+    part.headers()
+   );
+   let mut body = String::new();
+   part.read_to_string(&mut body)?;
+   assert_eq!("This is the body.\r\n--front", body);
+
+   let mut part = multipart.next_part()?.unwrap();
+   assert!(part.headers().is_empty());
+   let mut body = String::new();
+   part.read_to_string(&mut body)?;
+   assert_eq!("no header", body);
+
+   // the body of this part is skipped
+   let part = multipart.next_part()?.unwrap();
+   assert_eq!(Some("base64".as_bytes()), part.header(b"content-transfer-encoding"));
+
+   assert!(multipart.next_part()?.is_none());
+   assert!(multipart.next_part()?.is_none());
+   assert_eq!(21, bwr.slurp_exact(100)?);
+   assert_eq!("This is the epilogue.".as_bytes(), bwr.get());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_multipart_002() -> Result<(), Error> {
+  let mut bwr = BlockWiseReader::from_slice(b"--b\r\n\r\n\r\n--b--");
+  let mut multipart = Multipart::new(&mut bwr, b"b", 10, HeadLimits::default())?;
+  let mut part = multipart.next_part()?.unwrap();
+  let mut body = vec![];
+  part.read_to_end(&mut body)?;
+  assert!(body.is_empty());
+  assert!(multipart.next_part()?.is_none());
+  assert_eq!(0, bwr.available_bytes());
+  Ok(())
+ }
+
+ #[test]
+ fn test_multipart_boundary() {
+  assert_eq!(Some("abc".as_bytes()), boundary(b"multipart/form-data; boundary=abc"));
+  assert_eq!(
+   Some("a b".as_bytes()),
+   boundary(b"multipart/mixed;charset=utf-8; Boundary = \"a b\"")
+  );
+  assert_eq!(None, boundary(b"multipart/mixed; charset=utf-8"));
+ }
+
+ fn multipart_error(data: &'static str) -> String {
+  let sr = StringReader::new(data);
+  let mut bwr = BlockWiseReader::new(Box::new(sr));
+  let mut multipart = Multipart::new(&mut bwr, b"b", 10, HeadLimits::default()).unwrap();
+  loop {
+   match multipart.next_part() {
+    Ok(Some(mut part)) => {
+     let mut body = vec![];
+     if let Err(e) = part.read_to_end(&mut body) {
+      return e.to_string();
+     }
+    }
+    Ok(None) => panic!(),
+    Err(Error::Msg(x)) => return x.to_string(),
+    Err(Error::IO(_)) => panic!(),
+   }
+  }
+ }
+
+ #[test]
+ fn test_multipart_errors() {
+  assert_eq!("missing multipart delimiter", multipart_error("no delimiter at all"));
+  assert_eq!("invalid multipart delimiter line", multipart_error("--bx\r\n\r\n"));
+  assert_eq!("unexpected end of file in multipart body", multipart_error("--b\r\n\r\nbody"));
+  assert_eq!("header field without colon", multipart_error("--b\r\nX\r\n\r\n"));
+  assert_eq!(
+   "invalid multipart boundary",
+   msg(Multipart::new(&mut BlockWiseReader::from_slice(b""), b"", 10, HeadLimits::default()))
+  );
+ }
+}
@@ -0,0 +1,210 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+ use crate::common::{msg, Trickle};
+ use blockwise_reader::proxy_protocol::*;
+ use blockwise_reader::BlockWiseReader;
+ use blockwise_reader::Error;
+ use std::io::Cursor;
+
+ fn v2(ver_cmd: u8, family: u8, body: &[u8]) -> Vec<u8> {
+  [
+   V2_SIGNATURE,
+   &[ver_cmd, family],
+   &(body.len() as u16).to_be_bytes(),
+   body,
+  ]
+  .concat()
+ }
+
+ #[test]
+ fn test_proxy_v1() -> Result<(), Error> {
+  let data = "PROXY TCP6 2001:db8::1 ::1 65535 8080\r\nhello";
+  let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(data)));
+  let header = read_proxy_header(&mut bwr)?.unwrap();
+  assert_eq!(
+   (1, Command::Proxy, Transport::Stream),
+   (header.version, header.command, header.transport)
+  );
+  assert_eq!(
+   ProxyAddresses::Inet {
+    source: "[2001:db8::1]:65535".parse().unwrap(),
+    destination: "[::1]:8080".parse().unwrap(),
+   },
+   header.addresses
+  );
+  assert_eq!(5, bwr.slurp_exact(5)?);
+  assert_eq!(b"hello", bwr.get());
+
+  let mut bwr = BlockWiseReader::from_slice(b"PROXY UNKNOWN ffff::1 ::1 1 2\r\nhello");
+  let header = read_proxy_header(&mut bwr)?.unwrap();
+  assert_eq!((Transport::Unspec, ProxyAddresses::Unknown), (header.transport, header.addresses));
+  assert_eq!(b"hello", bwr.get());
+  Ok(())
+ }
+
+ #[test]
+ fn test_proxy_v2() -> Result<(), Error> {
+  let body = [
+   &[192, 0, 2, 1, 10, 0, 0, 1, 0x1f, 0x90, 0x01, 0xbb][..],
+   &[PP2_TYPE_ALPN, 0, 2],
+   b"h2",
+   &[PP2_TYPE_AUTHORITY, 0, 11],
+   b"example.com",
+   &[PP2_TYPE_NOOP, 0, 0],
+  ]
+  .concat();
+  let data = [v2(0x21, 0x11, &body), b"payload".to_vec()].concat();
+  let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(&data)));
+  let header = read_proxy_header(&mut bwr)?.unwrap();
+  assert_eq!(
+   (2, Command::Proxy, Transport::Stream),
+   (header.version, header.command, header.transport)
+  );
+  assert_eq!(
+   ProxyAddresses::Inet {
+    source: "192.0.2.1:8080".parse().unwrap(),
+    destination: "10.0.0.1:443".parse().unwrap(),
+   },
+   header.addresses
+  );
+  assert_eq!(3, header.tlvs.len());
+  assert_eq!(Some(&b"h2"[..]), header.tlv(PP2_TYPE_ALPN));
+  assert_eq!(Some(&b"example.com"[..]), header.tlv(PP2_TYPE_AUTHORITY));
+  assert_eq!(None, header.tlv(PP2_TYPE_SSL));
+  assert_eq!(7, bwr.slurp_exact(7)?);
+  assert_eq!(b"payload", bwr.get());
+
+  let mut body = vec![0; 36];
+  body[15] = 1;
+  body[31] = 2;
+  body[32..].copy_from_slice(&[0, 1, 0, 2]);
+  let data = v2(0x21, 0x22, &body);
+  let mut bwr = BlockWiseReader::from_slice(&data);
+  let header = read_proxy_header(&mut bwr)?.unwrap();
+  assert_eq!(Transport::Datagram, header.transport);
+  assert_eq!(
+   ProxyAddresses::Inet {
+    source: "[::1]:1".parse().unwrap(),
+    destination: "[::2]:2".parse().unwrap(),
+   },
+   header.addresses
+  );
+
+  let mut body = vec![0; 216];
+  body[..8].copy_from_slice(b"/run/src");
+  body[108..113].copy_from_slice(b"/dest");
+  let data = v2(0x21, 0x31, &body);
+  let mut bwr = BlockWiseReader::from_slice(&data);
+  assert_eq!(
+   ProxyAddresses::Unix {
+    source: b"/run/src".to_vec(),
+    destination: b"/dest".to_vec(),
+   },
+   read_proxy_header(&mut bwr)?.unwrap().addresses
+  );
+
+  let data = v2(0x20, 0x11, &[1; 12]);
+  let mut bwr = BlockWiseReader::from_slice(&data);
+  let header = read_proxy_header(&mut bwr)?.unwrap();
+  assert_eq!((Command::Local, ProxyAddresses::Unknown), (header.command, header.addresses));
+  assert_eq!(0, bwr.available_bytes());
+  Ok(())
+ }
+
+ #[test]
+ fn test_proxy_none() -> Result<(), Error> {
+  for (data, limit) in [
+   (&b"GET / HTTP/1.1\r\n"[..], 1),
+   (b"PROXX", 5),
+   (b"\r\n\r\nGET", 5),
+   (b"PRO", 3),
+   (b"", 0),
+  ] {
+   let mut bwr = BlockWiseReader::new(Box::new(Trickle::limited(data, 1, limit)));
+   assert!(read_proxy_header(&mut bwr)?.is_none());
+   assert_eq!(0, bwr.pos_get());
+   assert_eq!(&data[..limit], bwr.get());
+  }
+  Ok(())
+ }
+
+ fn proxy_error(data: &[u8]) -> &'static str {
+  let mut bwr = BlockWiseReader::from_slice(data);
+  let x = msg(read_proxy_header(&mut bwr));
+  assert_eq!(0, bwr.pos_get());
+  x
+ }
+
+ #[test]
+ fn test_proxy_errors() {
+  assert_eq!("invalid proxy protocol v1 header", proxy_error(b"PROXY TCP4 1.2.3.4\r\n"));
+  assert_eq!("invalid proxy protocol v1 header", proxy_error(b"PROXY TCP4 1.2.3.4 5.6.7.8 1 2"));
+  assert_eq!(
+   "invalid proxy protocol v1 header",
+   proxy_error(&[&b"PROXY UNKNOWN"[..], &[b' '; 100]].concat())
+  );
+  assert_eq!("invalid proxy protocol v1 address", proxy_error(b"PROXY TCP4 ::1 5.6.7.8 1 2\r\n"));
+  assert_eq!("invalid proxy protocol v1 port", proxy_error(b"PROXY TCP4 1.2.3.4 5.6.7.8 01 2\r\n"));
+  assert_eq!(
+   "invalid proxy protocol v1 port",
+   proxy_error(b"PROXY TCP4 1.2.3.4 5.6.7.8 1 65536\r\n")
+  );
+  assert_eq!("unsupported proxy protocol version", proxy_error(&v2(0x11, 0x11, &[0; 12])));
+  assert_eq!("invalid proxy protocol v2 command", proxy_error(&v2(0x22, 0x11, &[0; 12])));
+  assert_eq!("invalid proxy protocol v2 transport", proxy_error(&v2(0x21, 0x13, &[0; 12])));
+  assert_eq!("invalid proxy protocol v2 address family", proxy_error(&v2(0x21, 0x41, &[0; 12])));
+  assert_eq!("invalid proxy protocol v2 address length", proxy_error(&v2(0x21, 0x21, &[0; 12])));
+  assert_eq!("invalid proxy protocol v2 tlv", proxy_error(&v2(0x21, 0x11, &[0; 14])));
+  assert_eq!(
+   "invalid proxy protocol v2 tlv",
+   proxy_error(&v2(0x21, 0x11, &[&[0; 12][..], &[1, 0, 5, 0]].concat()))
+  );
+  assert_eq!(
+   "unexpected end of file in proxy protocol v2 header",
+   proxy_error(&v2(0x21, 0x11, &[0; 12])[..20])
+  );
+ }
+
+ #[test]
+ fn test_proxy_headers_across_reads() -> Result<(), Error> {
+  let v1 = b"PROXY TCP4 192.0.2.1 10.0.0.1 8080 443\r\npayload".to_vec();
+  let body = [
+   &[192, 0, 2, 1, 10, 0, 0, 1, 0x1f, 0x90, 0x01, 0xbb][..],
+   &[PP2_TYPE_ALPN, 0, 2],
+   b"h2",
+  ]
+  .concat();
+  let v2 = [v2(0x21, 0x11, &body), b"payload".to_vec()].concat();
+  // the headers are split between the reads of the socket
+  for data in [v1, v2] {
+   for step in [1, 3, 17] {
+    let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(&data, step)));
+    let header = read_proxy_header(&mut bwr)?.unwrap();
+    assert_eq!(
+     ProxyAddresses::Inet {
+      source: "192.0.2.1:8080".parse().unwrap(),
+      destination: "10.0.0.1:443".parse().unwrap(),
+     },
+     header.addresses
+    );
+    assert_eq!(7, bwr.slurp_exact(7)?);
+    assert_eq!(b"payload", &bwr.get()[..7]);
+   }
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_proxy_invalid_v2_header_unconsumed() -> Result<(), Error> {
+  let data = v2(0x21, 0x21, &[0; 12]);
+  let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(&data, 5)));
+  assert_eq!("invalid proxy protocol v2 address length", msg(read_proxy_header(&mut bwr)));
+  // the header remains unconsumed, a second attempt fails the same way
+  assert_eq!((0, &data[..]), (bwr.pos_get(), bwr.get()));
+  assert_eq!("invalid proxy protocol v2 address length", msg(read_proxy_header(&mut bwr)));
+  assert_eq!(0, bwr.pos_absolute());
+  Ok(())
+ }
+}
@@ -0,0 +1,216 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+ use crate::common::{msg, Trickle};
+ use blockwise_reader::resp::*;
+ use blockwise_reader::BlockWiseReader;
+ use blockwise_reader::Error;
+ use std::io::Cursor;
+
+ #[test]
+ fn test_resp2() -> Result<(), Error> {
+  let data = [
+   &b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$12\r\nline1\r\nline2\r\n"[..],
+   b"+OK\r\n",
+   b"-ERR unknown command\r\n",
+   b":-42\r\n",
+   b"$-1\r\n*-1\r\n$0\r\n\r\n*0\r\n",
+   b"*2\r\n*1\r\n:1\r\n*2\r\n+a\r\n$-1\r\n",
+  ]
+  .concat();
+  for buffersize in [1, 2, 5, 100] {
+   let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(&data)));
+   let limits = RespLimits::default();
+   assert_eq!(
+    Some(RespValue::Array(vec![
+     RespValue::BulkString(b"SET"),
+     RespValue::BulkString(b"key"),
+     RespValue::BulkString(b"line1\r\nline2"),
+    ])),
+    read_value(&mut bwr, buffersize, &limits)?
+   );
+   assert_eq!(Some(RespValue::SimpleString(b"OK")), read_value(&mut bwr, buffersize, &limits)?);
+   assert_eq!(
+    Some(RespValue::Error(b"ERR unknown command")),
+    read_value(&mut bwr, buffersize, &limits)?
+   );
+   assert_eq!(Some(RespValue::Integer(-42)), read_value(&mut bwr, buffersize, &limits)?);
+   assert_eq!(Some(RespValue::Null), read_value(&mut bwr, buffersize, &limits)?);
+   assert_eq!(Some(RespValue::Null), read_value(&mut bwr, buffersize, &limits)?);
+   assert_eq!(Some(RespValue::BulkString(b"")), read_value(&mut bwr, buffersize, &limits)?);
+   assert_eq!(Some(RespValue::Array(vec![])), read_value(&mut bwr, buffersize, &limits)?);
+   assert_eq!(
+    Some(&b"*2\r\n*1\r\n:1\r\n*2\r\n+a\r\n$-1\r\n"[..]),
+    read_frame(&mut bwr, buffersize, &limits)?
+   );
+   assert_eq!(None, read_frame(&mut bwr, buffersize, &limits)?);
+   assert_eq!(data.len(), bwr.pos_get());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_resp3() -> Result<(), Error> {
+  let data = [
+   &b"%2\r\n+first\r\n:1\r\n$6\r\nsecond\r\n#f\r\n"[..],
+   b"_\r\n,3.25\r\n,-inf\r\n(-3492890328409238509324850943850943825024385\r\n",
+   b"!21\r\nSYNTAX invalid syntax\r\n=15\r\ntxt:Some string\r\n",
+   b"~2\r\n#t\r\n:2\r\n>3\r\n+message\r\n+channel\r\n$5\r\nhello\r\n",
+   b"|1\r\n+key-popularity\r\n%1\r\n$1\r\na\r\n,0.19\r\n*1\r\n:2039123\r\n",
+  ]
+  .concat();
+  for buffersize in [1, 3, 1000] {
+   let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(&data)));
+   let limits = RespLimits::default();
+   assert_eq!(
+    Some(RespValue::Map(vec![
+     (RespValue::SimpleString(b"first"), RespValue::Integer(1)),
+     (RespValue::BulkString(b"second"), RespValue::Boolean(false)),
+    ])),
+    read_value(&mut bwr, buffersize, &limits)?
+   );
+   assert_eq!(Some(RespValue::Null), read_value(&mut bwr, buffersize, &limits)?);
+   assert_eq!(Some(RespValue::Double(3.25)), read_value(&mut bwr, buffersize, &limits)?);
+   assert_eq!(
+    Some(RespValue::Double(f64::NEG_INFINITY)),
+    read_value(&mut bwr, buffersize, &limits)?
+   );
+   assert_eq!(
+    Some(RespValue::BigNumber(b"-3492890328409238509324850943850943825024385")),
+    read_value(&mut bwr, buffersize, &limits)?
+   );
+   assert_eq!(
+    Some(RespValue::BulkError(b"SYNTAX invalid syntax")),
+    read_value(&mut bwr, buffersize, &limits)?
+   );
+   assert_eq!(
+    Some(RespValue::VerbatimString {
+     format: b"txt",
+     data: b"Some string"
+    }),
+    read_value(&mut bwr, buffersize, &limits)?
+   );
+   assert_eq!(
+    Some(RespValue::Set(vec![RespValue::Boolean(true), RespValue::Integer(2)])),
+    read_value(&mut bwr, buffersize, &limits)?
+   );
+   assert_eq!(
+    Some(RespValue::Push(vec![
+     RespValue::SimpleString(b"message"),
+     RespValue::SimpleString(b"channel"),
+     RespValue::BulkString(b"hello"),
+    ])),
+    read_value(&mut bwr, buffersize, &limits)?
+   );
+   assert_eq!(
+    Some(RespValue::Attribute {
+     attributes: vec![(
+      RespValue::SimpleString(b"key-popularity"),
+      RespValue::Map(vec![(RespValue::BulkString(b"a"), RespValue::Double(0.19))]),
+     )],
+     value: Box::new(RespValue::Array(vec![RespValue::Integer(2039123)])),
+    }),
+    read_value(&mut bwr, buffersize, &limits)?
+   );
+   assert_eq!(None, read_value(&mut bwr, buffersize, &limits)?);
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_resp_inline() -> Result<(), Error> {
+  let mut bwr = BlockWiseReader::from_slice(b"SET  key\tvalue\r\n\r\nPING\r\n*1\r\n$4\r\nPING\r\n");
+  let limits = RespLimits::default();
+  assert_eq!(
+   Some(RespValue::Inline(vec![b"SET", b"key", b"value"])),
+   read_value(&mut bwr, 10, &limits)?
+  );
+  assert_eq!(Some(RespValue::Inline(vec![])), read_value(&mut bwr, 10, &limits)?);
+  assert_eq!(Some(&b"PING\r\n"[..]), read_frame(&mut bwr, 10, &limits)?);
+  assert_eq!(
+   Some(RespValue::Array(vec![RespValue::BulkString(b"PING")])),
+   read_value(&mut bwr, 10, &limits)?
+  );
+  Ok(())
+ }
+
+ fn resp_error(data: &[u8], limits: &RespLimits) -> &'static str {
+  let mut bwr = BlockWiseReader::from_slice(data);
+  let x = msg(read_value(&mut bwr, 4, limits));
+  assert_eq!(0, bwr.pos_get());
+  x
+ }
+
+ #[test]
+ fn test_resp_errors() {
+  let limits = RespLimits {
+   max_line_size: 16,
+   max_bulk_size: 8,
+   max_depth: 2,
+  };
+  let e = |data: &[u8]| resp_error(data, &limits);
+  assert_eq!("unexpected end of file in resp frame", e(b"*2\r\n:1\r\n"));
+  assert_eq!("unexpected end of file in resp frame", e(b"$5\r\nabc"));
+  assert_eq!("unexpected end of file in resp frame", e(b"+OK"));
+  assert_eq!("unexpected end of file in resp frame", e(b"|1\r\n+a\r\n+b\r\n"));
+  assert_eq!("resp line exceeds max_line_size", e(b"+0123456789abcdef\r\n"));
+  assert_eq!("resp line exceeds max_line_size", e(b"GET 0123456789abcdef"));
+  assert_eq!("resp bulk string exceeds max_bulk_size", e(b"$9\r\n123456789\r\n"));
+  assert_eq!("resp frame exceeds max_depth", e(b"*1\r\n*1\r\n*0\r\n"));
+  assert_eq!("resp frame exceeds max_depth", e(b"*1\r\n|1\r\n*1\r\n:1\r\n+b\r\n:1\r\n"));
+  assert_eq!("missing CRLF behind resp bulk string", e(b"$3\r\nabcd\r\n"));
+  assert_eq!("invalid resp type", e(b"*1\r\nPING\r\n"));
+  assert_eq!("invalid resp type", e(b"*1\r\n\r\n"));
+  assert_eq!("invalid resp integer", e(b":1.5\r\n"));
+  assert_eq!("invalid resp null", e(b"_0\r\n"));
+  assert_eq!("invalid resp boolean", e(b"#x\r\n"));
+  assert_eq!("invalid resp double", e(b",1,5\r\n"));
+  assert_eq!("invalid resp big number", e(b"(12a\r\n"));
+  assert_eq!("invalid resp length", e(b"$-2\r\n"));
+  assert_eq!("invalid resp length", e(b"*?\r\n"));
+  assert_eq!("invalid resp length", e(b"%\r\n"));
+  assert_eq!("invalid resp verbatim string", e(b"=3\r\ntxt\r\n"));
+  assert_eq!("invalid resp verbatim string", e(b"=5\r\ntxt!a\r\n"));
+
+  let limits = RespLimits {
+   max_depth: 0,
+   ..RespLimits::default()
+  };
+  assert_eq!("resp frame exceeds max_depth", resp_error(b"*0\r\n", &limits));
+ }
+
+ #[test]
+ fn test_resp_nested_frames_across_reads() -> Result<(), Error> {
+  let data = b"*2\r\n$5\r\nhello\r\n%1\r\n+k\r\n:12345\r\n+OK\r\n";
+  // the lines and the bulk strings are split between the reads of the socket
+  for (step, buffersize) in [(1, 1), (3, 2), (7, 100)] {
+   let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(data, step)));
+   let limits = RespLimits::default();
+   assert_eq!(
+    Some(RespValue::Array(vec![
+     RespValue::BulkString(b"hello"),
+     RespValue::Map(vec![(RespValue::SimpleString(b"k"), RespValue::Integer(12345))]),
+    ])),
+    read_value(&mut bwr, buffersize, &limits)?
+   );
+   assert_eq!(Some(&b"+OK\r\n"[..]), read_frame(&mut bwr, buffersize, &limits)?);
+   assert_eq!(None, read_frame(&mut bwr, buffersize, &limits)?);
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_resp_invalid_integer_in_array() -> Result<(), Error> {
+  let data = b"+OK\r\n*2\r\n:1\r\n:x\r\n";
+  let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(data, 2)));
+  let limits = RespLimits::default();
+  assert_eq!(Some(RespValue::SimpleString(b"OK")), read_value(&mut bwr, 2, &limits)?);
+  // the invalid frame remains at pos, a second attempt fails the same way
+  assert_eq!("invalid resp integer", msg(read_value(&mut bwr, 2, &limits)));
+  assert_eq!(5, bwr.pos_get());
+  assert_eq!("invalid resp integer", msg(read_value(&mut bwr, 2, &limits)));
+  assert_eq!(&data[5..], bwr.get());
+  Ok(())
+ }
+}
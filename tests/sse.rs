@@ -0,0 +1,93 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+ use crate::common::{first_msg, msg, Trickle};
+ use blockwise_reader::sse::*;
+ use blockwise_reader::BlockWiseReader;
+ use blockwise_reader::Error;
+ use stringreader::StringReader;
+
+ fn event(event: &str, data: &str, id: &str, retry: Option<u64>) -> Option<Event> {
+  Some(Event {
+   event: event.to_string(),
+   data: data.to_string(),
+   id: id.to_string(),
+   retry,
+  })
+ }
+
+ const STREAM: &str = "\u{feff}: comment\n\ndata: first\ndata:  second\r\ndata\rdata:\n\n\
+event: add\rid: 1\rretry: 1500\rfoo: bar\rdata: x:y\r\r\
+event: ignored\nid: 2\n\n\
+id: 3\0\nretry: 10s\ndata\r\n\r\n\
+id\nevent:\ndata: grüße\n\n\
+data: unfinished";
+
+ #[test]
+ fn test_sse_001() -> Result<(), Error> {
+  for buffersize in [1, 2, 7, 1000] {
+   let mut bwr = BlockWiseReader::new(Box::new(StringReader::new(STREAM)));
+   let mut sse = SseReader::new(&mut bwr, buffersize, 1024);
+   assert_eq!(event("message", "first\n second\n\n", "", None), sse.next_event()?);
+   assert_eq!(event("add", "x:y", "1", Some(1500)), sse.next_event()?);
+   assert_eq!(event("message", "", "2", Some(1500)), sse.next_event()?);
+   assert_eq!(event("message", "grüße", "", Some(1500)), sse.next_event()?);
+   assert_eq!(None, sse.next_event()?);
+   assert_eq!(None, sse.next_event()?);
+   assert_eq!((Some(1500), ""), (sse.retry(), sse.last_event_id()));
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_sse_trickle() -> Result<(), Error> {
+  let data = b"data: a\r\n\r\ndata: b\r\rdata: c\r";
+  let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(data, 1)));
+  let mut sse = SseReader::new(&mut bwr, 1, 1024);
+  assert_eq!(event("message", "a", "", None), sse.next_event()?);
+  assert_eq!(event("message", "b", "", None), sse.next_event()?);
+  assert_eq!(None, sse.next_event()?);
+
+  let mut bwr = BlockWiseReader::from_slice(b"data: a\r\r");
+  let mut sse = SseReader::new(&mut bwr, 4, 1024);
+  assert_eq!(event("message", "a", "", None), sse.next_event()?);
+  assert_eq!(None, sse.next_event()?);
+  Ok(())
+ }
+
+ fn sse_error(data: &str, max_event_size: usize) -> &'static str {
+  let mut bwr = BlockWiseReader::new(Box::new(StringReader::new(data)));
+  let mut sse = SseReader::new(&mut bwr, 3, max_event_size);
+  first_msg(|| sse.next_event())
+ }
+
+ #[test]
+ fn test_sse_limits() -> Result<(), Error> {
+  let mut bwr =
+   BlockWiseReader::new(Box::new(StringReader::new("data: 12\n\n:4567890\r\ndata: 1\r\n\r\n")));
+  let mut sse = SseReader::new(&mut bwr, 3, 10);
+  assert_eq!(event("message", "12", "", None), sse.next_event()?);
+  assert!(sse.next_event().is_err());
+
+  assert_eq!("sse event exceeds max_event_size", sse_error("data: 123\n\n", 10));
+  assert_eq!("sse event exceeds max_event_size", sse_error("data: 1\n\ndata: 123456", 10));
+  assert_eq!("sse event exceeds max_event_size", sse_error("data: 1\ndata: 2\n\n", 15));
+  assert_eq!("sse event exceeds max_event_size", sse_error(&":\n".repeat(100), 50));
+  Ok(())
+ }
+
+ #[test]
+ fn test_sse_long_line_behind_fields() -> Result<(), Error> {
+  let data = b"data: 1\n\nid: 5\ndata: 0123456789\n\n";
+  let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(data, 3)));
+  let mut sse = SseReader::new(&mut bwr, 2, 20);
+  assert_eq!(event("message", "1", "", None), sse.next_event()?);
+  // the consumed lines of the failing event are applied, the long line remains at pos
+  assert_eq!("sse event exceeds max_event_size", msg(sse.next_event()));
+  assert_eq!("5", sse.last_event_id());
+  assert_eq!("sse event exceeds max_event_size", msg(sse.next_event()));
+  assert_eq!((15, 0), (bwr.pos_absolute(), bwr.pos_get()));
+  Ok(())
+ }
+}
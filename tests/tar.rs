@@ -0,0 +1,207 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+ use crate::common::{first_msg, msg, Trickle};
+ use blockwise_reader::tar::{EntryType, TarReader};
+ use blockwise_reader::BlockWiseReader;
+ use blockwise_reader::Error;
+ use std::io::{Cursor, ErrorKind, Read};
+
+ fn header(name: &str, size: usize, flag: u8, prefix: &str) -> Vec<u8> {
+  let mut h = vec![0u8; 512];
+  h[..name.len()].copy_from_slice(name.as_bytes());
+  h[100..107].copy_from_slice(b"0000644");
+  h[108..115].copy_from_slice(b"0001750");
+  h[116..123].copy_from_slice(b"0001750");
+  h[124..135].copy_from_slice(format!("{:011o}", size).as_bytes());
+  h[136..147].copy_from_slice(b"14230157640");
+  h[156] = flag;
+  h[257..263].copy_from_slice(b"ustar\0");
+  h[263..265].copy_from_slice(b"00");
+  h[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+  set_checksum(&mut h);
+  h
+ }
+
+ fn set_checksum(h: &mut [u8]) {
+  h[148..156].fill(0);
+  let sum: u32 = h.iter().map(|b| *b as u32).sum::<u32>() + 8 * b' ' as u32;
+  h[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+ }
+
+ fn entry(name: &str, data: &[u8], flag: u8, prefix: &str) -> Vec<u8> {
+  let mut e = header(name, data.len(), flag, prefix);
+  e.extend_from_slice(data);
+  e.resize(e.len().div_ceil(512) * 512, 0);
+  e
+ }
+
+ fn archive() -> Vec<u8> {
+  let long = "d/".repeat(80) + "long.txt";
+  let pax = "29 path=pax/renamed/file.txt\n10 size=3\n20 mtime=123.000001\n";
+  [
+   entry("hello.txt", b"Hello World", b'0', ""),
+   entry("dir/", b"", b'5', "some/prefix"),
+   entry("././@LongLink", format!("{}\0", long).as_bytes(), b'L', ""),
+   entry("short", &[b'x'; 1000], 0, ""),
+   entry("PaxHeader", pax.as_bytes(), b'x', ""),
+   entry("file.txt", b"abc", b'0', ""),
+   entry("link", b"", b'2', ""),
+   vec![0u8; 1024],
+   b"trailing".to_vec(),
+  ]
+  .concat()
+ }
+
+ #[test]
+ fn test_tar_001() -> Result<(), Error> {
+  let data = archive();
+  for i in [1, 7, 512, 4096] {
+   let mut bwr = BlockWiseReader::from_slice(&data);
+   let mut tar = TarReader::new(&mut bwr, i, 1024);
+
+   let e = tar.next_entry()?.unwrap();
+   assert_eq!(b"hello.txt", &e.name[..]);
+   assert_eq!(EntryType::Regular, e.entry_type);
+   assert_eq!((11, 0o644, 1000, 1000), (e.size, e.mode, e.uid, e.gid));
+   assert_eq!(0o14230157640, e.mtime);
+   assert_eq!((0, 512), (e.header_offset, e.data_offset));
+   let mut s = String::new();
+   tar.read_to_string(&mut s)?;
+   assert_eq!("Hello World", s);
+
+   let e = tar.next_entry()?.unwrap();
+   assert_eq!(b"some/prefix/dir/", &e.name[..]);
+   assert_eq!(EntryType::Directory, e.entry_type);
+
+   let e = tar.next_entry()?.unwrap();
+   assert_eq!(("d/".repeat(80) + "long.txt").as_bytes(), &e.name[..]);
+   assert_eq!(1000, e.size);
+   assert_eq!(2560, e.header_offset);
+
+   // the data of this entry are skipped
+   let e = tar.next_entry()?.unwrap();
+   assert_eq!(b"pax/renamed/file.txt", &e.name[..]);
+   assert_eq!((3, 123), (e.size, e.mtime));
+   let mut s = String::new();
+   tar.read_to_string(&mut s)?;
+   assert_eq!("abc", s);
+
+   let e = tar.next_entry()?.unwrap();
+   assert_eq!(EntryType::Symlink, e.entry_type);
+
+   assert!(tar.next_entry()?.is_none());
+   assert!(tar.next_entry()?.is_none());
+   assert_eq!(b"trailing", bwr.get());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_tar_002() -> Result<(), Error> {
+  let data = [entry("a", b"1", b'0', ""), header("b", 0, b'0', "")].concat();
+  let mut bwr = BlockWiseReader::from_slice(&data);
+  let mut tar = TarReader::new(&mut bwr, 100, 1024);
+  assert_eq!(b"a", &tar.next_entry()?.unwrap().name[..]);
+  assert_eq!(b"b", &tar.next_entry()?.unwrap().name[..]);
+  assert!(tar.next_entry()?.is_none());
+  Ok(())
+ }
+
+ fn tar_error(data: Vec<u8>) -> &'static str {
+  let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(data)));
+  let mut tar = TarReader::new(&mut bwr, 100, 100);
+  first_msg(|| tar.next_entry())
+ }
+
+ #[test]
+ fn test_tar_errors() {
+  let mut broken = header("a", 0, b'0', "");
+  broken[0] = b'b';
+  assert_eq!("invalid tar header checksum", tar_error(broken));
+  assert_eq!("unexpected end of file in tar header", tar_error(vec![b'x'; 100]));
+  assert_eq!(
+   "unexpected end of file in tar entry data",
+   tar_error([header("a", 10, b'0', ""), vec![0xff; 5]].concat())
+  );
+  assert_eq!(
+   "tar extension header exceeds max_extension_size",
+   tar_error(entry("L", &[b'x'; 200], b'L', ""))
+  );
+  assert_eq!("invalid pax extended header", tar_error(entry("x", b"8 path=x\n", b'x', "")));
+  let mut octal = header("a", 0, b'0', "");
+  octal[101] = b'9';
+  set_checksum(&mut octal);
+  assert_eq!("invalid tar number field", tar_error(octal));
+  let mut huge = header("a", 0, b'0', "");
+  huge[124..136].copy_from_slice(&[
+   0x80, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+  ]);
+  set_checksum(&mut huge);
+  assert_eq!("invalid tar entry size", tar_error(huge));
+  let pax = entry("x", b"29 size=18446744073709551615\n", b'x', "");
+  assert_eq!("invalid tar entry size", tar_error([pax, header("a", 0, b'0', "")].concat()));
+ }
+
+ #[test]
+ fn test_tar_data_across_blocks() -> Result<(), Error> {
+  let data: Vec<u8> = (0..1300u32).map(|i| (i % 251) as u8).collect();
+  let archive = [
+   entry("a", &data, b'0', ""),
+   entry("b", b"xyz", b'0', ""),
+   vec![0; 1024],
+  ]
+  .concat();
+  for (step, buffersize) in [(1, 1), (100, 64), (700, 3)] {
+   let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(&archive, step)));
+   let mut tar = TarReader::new(&mut bwr, buffersize, 100);
+   assert_eq!(1300, tar.next_entry()?.unwrap().size);
+   // the reads cross the block boundaries of the data
+   let mut read = vec![];
+   let mut buf = [0; 7];
+   while read.len() < 1100 {
+    let n = tar.read(&mut buf)?;
+    assert!(n > 0 && n <= buffersize);
+    read.extend_from_slice(&buf[..n]);
+   }
+   assert_eq!(&data[..read.len()], &read[..]);
+   let e = tar.next_entry()?.unwrap();
+   assert_eq!((&b"b"[..], 2048), (&e.name[..], e.header_offset));
+   let mut s = String::new();
+   tar.read_to_string(&mut s)?;
+   assert_eq!("xyz", s);
+   assert!(tar.next_entry()?.is_none());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_tar_checksum_error_keeps_header() -> Result<(), Error> {
+  let mut broken = header("b", 0, b'0', "");
+  broken[0] = b'c';
+  let data = [entry("a", b"12345", b'0', ""), broken].concat();
+  let mut bwr = BlockWiseReader::from_slice(&data);
+  let mut tar = TarReader::new(&mut bwr, 2, 100);
+  tar.next_entry()?.unwrap();
+  // the failing header remains at pos, the skipped data are dropped
+  assert_eq!("invalid tar header checksum", msg(tar.next_entry()));
+  assert_eq!((1024, 0), (bwr.pos_absolute(), bwr.pos_get()));
+  assert_eq!(b"c", &bwr.get()[..1]);
+  Ok(())
+ }
+
+ #[test]
+ fn test_tar_truncated_data() -> Result<(), Error> {
+  let data = [header("a", 10, b'0', ""), b"12345".to_vec()].concat();
+  let mut bwr = BlockWiseReader::from_slice(&data);
+  let mut tar = TarReader::new(&mut bwr, 2, 100);
+  tar.next_entry()?.unwrap();
+  let mut buf = vec![];
+  let e = tar.read_to_end(&mut buf).unwrap_err();
+  assert_eq!(ErrorKind::InvalidData, e.kind());
+  assert_eq!(b"12345", &buf[..]);
+  assert_eq!((517, 0), (bwr.pos_absolute(), bwr.available_bytes()));
+  Ok(())
+ }
+}
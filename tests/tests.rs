@@ -1,7 +1,11 @@
 #[cfg(test)]
+// the original tests are kept as they are written
+#[allow(clippy::byte_char_slices, clippy::needless_borrow)]
 mod tests {
  use blockwise_reader::FindPos;
  use blockwise_reader::PatternIdx;
+ use blockwise_reader::Quoting;
+ use blockwise_reader::{SyncInterval, SyncPattern};
  use stringreader::StringReader;
 
  use blockwise_reader::BlockWiseReader;
@@ -733,4 +737,481 @@ nameserver 8.8.8.8
    assert_eq!(0, bwr.pos_get());
   }
  }
+
+ #[cfg(feature = "mmap")]
+ fn mmap_file(name: &str, content: &[u8]) -> Result<std::fs::File, std::io::Error> {
+  let path = std::env::temp_dir().join(format!("blockwise_reader_{}_{}", std::process::id(), name));
+  std::fs::write(&path, content)?;
+  let file = std::fs::File::open(&path)?;
+  std::fs::remove_file(&path)?;
+  Ok(file)
+ }
+
+ #[cfg(feature = "mmap")]
+ #[test]
+ fn test_mmap_001() -> Result<(), Error> {
+  let mut bwr = BlockWiseReader::new_mmap(mmap_file("mmap_001", b"123456789")?);
+  assert_eq!(0, bwr.available_bytes());
+  assert_eq!(3, bwr.slurp(3)?);
+  assert_eq!("123".as_bytes(), bwr.get());
+  assert!(bwr.slurp_search_repos_loop(3, "67".as_bytes(), FindPos::Begin)?);
+  assert_eq!(5, bwr.pos_get());
+  assert_eq!("12345".as_bytes(), bwr.pos_cut());
+  assert_eq!(4, bwr.slurp(100)?);
+  assert_eq!("6789".as_bytes(), bwr.get());
+  Ok(())
+ }
+
+ #[cfg(feature = "mmap")]
+ #[test]
+ fn test_mmap_002() -> Result<(), Error> {
+  let mut bwr = BlockWiseReader::new_mmap(mmap_file("mmap_002", b"123456789")?);
+  assert_eq!(4, bwr.slurp(4)?);
+  bwr.pos_set(2);
+  bwr.pos_inject("abc".as_bytes());
+  bwr.pos_set(0);
+  assert_eq!("12abc34".as_bytes(), bwr.get());
+  assert_eq!(12, bwr.slurp_loop(2)?);
+  assert_eq!("12abc3456789".as_bytes(), bwr.get());
+  Ok(())
+ }
+
+ #[cfg(feature = "mmap")]
+ #[test]
+ fn test_mmap_003() -> Result<(), Error> {
+  // not a regular file, falls back to buffered reading
+  let mut bwr = BlockWiseReader::new_mmap(std::fs::File::open("/dev/null")?);
+  assert_eq!(0, bwr.slurp(10)?);
+  assert!(!bwr.slurp_find_repos_loop(10, b'x', FindPos::Begin)?);
+  Ok(())
+ }
+
+ #[test]
+ fn test_from_slice_001() -> Result<(), Error> {
+  let data = "123456789".as_bytes();
+  let mut bwr = BlockWiseReader::from_slice(data);
+  assert_eq!(9, bwr.available_bytes());
+  assert_eq!(9, bwr.slurp(3)?);
+  assert!(bwr.slurp_search_repos_loop(3, "67".as_bytes(), FindPos::End)?);
+  assert_eq!("89".as_bytes(), bwr.get());
+  assert_eq!("1234567".as_bytes(), bwr.pos_cut());
+  bwr.pos_inject("ab".as_bytes());
+  assert_eq!("ab89".as_bytes(), bwr.get());
+  assert_eq!(4, bwr.slurp_loop(10)?);
+  assert_eq!("123456789".as_bytes(), data);
+  Ok(())
+ }
+
+ #[test]
+ fn test_from_slice_002() -> Result<(), Error> {
+  let mut bwr = BlockWiseReader::from_slice(b"# Generated by NetworkManager\nnameserver 8.8.8.8\n");
+  assert!(bwr.slurp_match_repos("# Generated by NetworkManager\n".as_bytes())?);
+  assert!(bwr.slurp_match_repos("nameserver ".as_bytes())?);
+  let pos = bwr.pos_get();
+  assert!(bwr.slurp_find_repos0(1024, b'\n')?);
+  assert_eq!("8.8.8.8".as_bytes(), bwr.get_from_to_current(pos));
+  assert!(!bwr.slurp_find_repos_loop(4, b'x', FindPos::Begin)?);
+  Ok(())
+ }
+
+ #[cfg(feature = "bytes")]
+ #[test]
+ fn test_from_bytes_001() -> Result<(), Error> {
+  let mut bwr = BlockWiseReader::from_bytes(bytes::Bytes::from_static(b"123456"));
+  assert!(bwr.slurp_find_repos_loop(2, b'5', FindPos::Begin)?);
+  assert_eq!("56".as_bytes(), bwr.get());
+  Ok(())
+ }
+
+ #[cfg(feature = "nom")]
+ fn nom_record(i: &[u8]) -> nom::IResult<&[u8], &[u8]> {
+  use nom::Parser;
+  nom::multi::length_data(nom::number::streaming::be_u8).parse(i)
+ }
+
+ #[cfg(feature = "nom")]
+ fn nom_line(i: &[u8]) -> nom::IResult<&[u8], &[u8]> {
+  use nom::Parser;
+  nom::sequence::terminated(nom::bytes::take_until("\n"), nom::bytes::tag("\n")).parse(i)
+ }
+
+ #[cfg(feature = "nom")]
+ #[test]
+ fn test_slurp_nom_001() -> Result<(), Error> {
+  let sr = StringReader::new("\x03abc\x02de\x05f");
+  let mut bwr = BlockWiseReader::new(Box::new(sr));
+  assert_eq!(Ok("abc".as_bytes()), bwr.slurp_nom(1, nom_record)?);
+  assert_eq!(4, bwr.pos_get());
+  assert_eq!(Ok("de".as_bytes()), bwr.slurp_nom(1, nom_record)?);
+  assert_eq!(7, bwr.pos_get());
+  match bwr.slurp_nom(1, nom_record)? {
+   Err(nom::Err::Error(e)) => assert_eq!(nom::error::ErrorKind::Complete, e.code),
+   _ => panic!(),
+  }
+  assert_eq!(7, bwr.pos_get());
+  Ok(())
+ }
+
+ #[cfg(feature = "nom")]
+ #[test]
+ fn test_slurp_nom_002() -> Result<(), Error> {
+  for i in 1..10 {
+   let sr = StringReader::new("first line\nsecond line\nrest");
+   let mut bwr = BlockWiseReader::new(Box::new(sr));
+   assert_eq!(Ok("first line".as_bytes()), bwr.slurp_nom(i, nom_line)?);
+   assert_eq!(Ok("second line".as_bytes()), bwr.slurp_nom(i, nom_line)?);
+   assert!(bwr.slurp_nom(i, nom_line)?.is_err());
+   assert_eq!("rest".as_bytes(), bwr.get());
+  }
+  Ok(())
+ }
+
+ #[cfg(feature = "nom")]
+ #[test]
+ fn test_slurp_nom_003() {
+  let mut bwr = BlockWiseReader::from_slice(b"\x01a");
+  match bwr.slurp_nom(0, nom_record) {
+   Err(Error::Msg(x)) => assert_eq!(x, "buffersize 0 leads to an infinite loop"),
+   _ => panic!(),
+  }
+  assert_eq!(Ok(Ok("a".as_bytes())), bwr.slurp_nom(1, nom_record).map_err(|_| ()));
+ }
+
+ #[cfg(feature = "winnow")]
+ fn winnow_record<'i>(i: &mut winnow::Partial<&'i [u8]>) -> winnow::ModalResult<&'i [u8]> {
+  use winnow::Parser;
+  winnow::binary::length_take(winnow::binary::be_u8).parse_next(i)
+ }
+
+ #[cfg(feature = "winnow")]
+ fn winnow_line<'i>(i: &mut winnow::Partial<&'i [u8]>) -> winnow::ModalResult<&'i [u8]> {
+  use winnow::Parser;
+  winnow::combinator::terminated(winnow::token::take_until(0.., "\n"), "\n").parse_next(i)
+ }
+
+ #[cfg(feature = "winnow")]
+ fn winnow_alt<'i>(i: &mut winnow::Partial<&'i [u8]>) -> winnow::ModalResult<&'i [u8]> {
+  use winnow::Parser;
+  winnow::combinator::alt(("abcdef", "abcxyz")).parse_next(i)
+ }
+
+ #[cfg(feature = "winnow")]
+ #[test]
+ fn test_slurp_winnow_001() -> Result<(), Error> {
+  let sr = StringReader::new("\x03abc\x02de\x05f");
+  let mut bwr = BlockWiseReader::new(Box::new(sr));
+  assert_eq!(Ok("abc".as_bytes()), bwr.slurp_winnow(1, winnow_record)?);
+  assert_eq!(4, bwr.pos_get());
+  assert_eq!(Ok("de".as_bytes()), bwr.slurp_winnow(1, winnow_record)?);
+  assert_eq!(7, bwr.pos_get());
+  assert!(bwr.slurp_winnow(1, winnow_record)?.is_err());
+  assert_eq!(7, bwr.pos_get());
+  Ok(())
+ }
+
+ #[cfg(feature = "winnow")]
+ #[test]
+ fn test_slurp_winnow_002() -> Result<(), Error> {
+  for i in 1..10 {
+   let sr = StringReader::new("first line\nsecond line\nrest");
+   let mut bwr = BlockWiseReader::new(Box::new(sr));
+   assert_eq!(Ok("first line".as_bytes()), bwr.slurp_winnow(i, winnow_line)?);
+   assert_eq!(Ok("second line".as_bytes()), bwr.slurp_winnow(i, winnow_line)?);
+   assert!(bwr.slurp_winnow(i, winnow_line)?.is_err());
+   assert_eq!("rest".as_bytes(), bwr.get());
+  }
+  Ok(())
+ }
+
+ #[cfg(feature = "winnow")]
+ #[test]
+ fn test_slurp_winnow_003() -> Result<(), Error> {
+  // alt backtracks to its checkpoint within the buffered data
+  for i in 1..8 {
+   let sr = StringReader::new("abcxyz!");
+   let mut bwr = BlockWiseReader::new(Box::new(sr));
+   assert_eq!(Ok("abcxyz".as_bytes()), bwr.slurp_winnow(i, winnow_alt)?);
+   assert_eq!(6, bwr.pos_get());
+  }
+  let mut bwr = BlockWiseReader::from_slice(b"abcx");
+  assert!(bwr.slurp_winnow(1, winnow_alt)?.is_err());
+  assert_eq!(0, bwr.pos_get());
+  Ok(())
+ }
+
+ struct Trickle<'a>(&'a [u8]);
+
+ impl std::io::Read for Trickle<'_> {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+   let n = buf.len().min(self.0.len()).min(1);
+   buf[..n].copy_from_slice(&self.0[..n]);
+   self.0 = &self.0[n..];
+   Ok(n)
+  }
+ }
+
+ #[test]
+ fn test_slurp_exact() -> Result<(), std::io::Error> {
+  let mut bwr = BlockWiseReader::new(Box::new(Trickle(b"123456")));
+  assert_eq!(1, bwr.slurp(4)?);
+  assert_eq!(4, bwr.slurp_exact(4)?);
+  assert_eq!("1234".as_bytes(), bwr.get());
+  assert_eq!(6, bwr.slurp_exact(10)?);
+  assert_eq!("123456".as_bytes(), bwr.get());
+  Ok(())
+ }
+
+ #[test]
+ fn test_pos_drop() -> Result<(), std::io::Error> {
+  let sr = StringReader::new("123456");
+  let mut bwr = BlockWiseReader::new(Box::new(sr));
+  assert_eq!(6, bwr.slurp(6)?);
+  bwr.pos_set(4);
+  bwr.pos_drop();
+  assert_eq!(0, bwr.pos_get());
+  assert_eq!(2, bwr.size());
+  assert_eq!("56".as_bytes(), bwr.get());
+  let mut bwr = BlockWiseReader::from_slice(b"123456");
+  bwr.pos_set(2);
+  bwr.pos_drop();
+  assert_eq!("3456".as_bytes(), bwr.get());
+  bwr.pos_set(1);
+  assert_eq!(3, bwr.pos_absolute());
+  assert_eq!("3".as_bytes(), bwr.pos_cut());
+  assert_eq!(3, bwr.pos_absolute());
+  Ok(())
+ }
+
+ const JSON_PAIRS: &[(u8, u8)] = &[(b'{', b'}'), (b'[', b']')];
+ const JSON_QUOTING: Quoting = Quoting {
+  quotes: &[(b'"', b'"')],
+  escape: Some(b'\\'),
+ };
+
+ #[test]
+ fn test_slurp_balanced_001() -> Result<(), Error> {
+  let json = r#"{"a": [1, {"b": "}]\"{"}], "c": "x"}[2, [3]]x"#;
+  for i in 1..50 {
+   let sr = StringReader::new(json);
+   let mut bwr = BlockWiseReader::new(Box::new(sr));
+   assert!(bwr.slurp_balanced(i, JSON_PAIRS, &JSON_QUOTING, 10)?);
+   assert_eq!(36, bwr.pos_get());
+   assert!(bwr.slurp_balanced(i, JSON_PAIRS, &JSON_QUOTING, 10)?);
+   assert_eq!(44, bwr.pos_get());
+   assert!(!bwr.slurp_balanced(i, JSON_PAIRS, &JSON_QUOTING, 10)?);
+   assert_eq!(44, bwr.pos_get());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_slurp_balanced_002() {
+  for (json, msg) in [
+   ("[[[1]]]", "balanced region exceeds max_depth"),
+   ("[1, 2}", "unbalanced: unexpected closing byte"),
+   ("{\"a\": [1}", "unbalanced: unexpected closing byte"),
+   ("[\"]\"", "unbalanced: end of file inside of the region"),
+  ] {
+   let mut bwr = BlockWiseReader::from_slice(json.as_bytes());
+   match bwr.slurp_balanced(4, JSON_PAIRS, &JSON_QUOTING, 2) {
+    Err(Error::Msg(x)) => assert_eq!(msg, x),
+    _ => panic!(),
+   }
+   assert_eq!(0, bwr.pos_get());
+  }
+ }
+
+ const KV_QUOTING: Quoting = Quoting {
+  quotes: &[(b'"', b'"'), (b'(', b')')],
+  escape: Some(b'\\'),
+ };
+
+ #[test]
+ fn test_slurp_find_quoted_repos() -> Result<(), std::io::Error> {
+  let sr = StringReader::new(r#"key="a,b",x\,y,(c,d),z"#);
+  let mut bwr = BlockWiseReader::new(Box::new(sr));
+  assert_eq!(None, bwr.find_quoted(b',', &KV_QUOTING));
+  assert!(bwr.slurp_find_quoted_repos(100, b',', &KV_QUOTING, FindPos::End)?);
+  assert_eq!(10, bwr.pos_get());
+  assert_eq!(Some(4), bwr.find_quoted(b',', &KV_QUOTING));
+  assert!(bwr.slurp_find_quoted_repos(100, b',', &KV_QUOTING, FindPos::Begin)?);
+  assert_eq!(14, bwr.pos_get());
+  assert!(bwr.slurp_search_quoted_repos(100, b",z", &KV_QUOTING, FindPos::End)?);
+  assert_eq!(22, bwr.pos_get());
+  assert!(!bwr.slurp_find_quoted_repos(100, b',', &KV_QUOTING, FindPos::End)?);
+  assert_eq!(22, bwr.pos_get());
+  Ok(())
+ }
+
+ #[test]
+ fn test_slurp_search_quoted_repos_loop() -> Result<(), Error> {
+  let data = r#"a="x;;y" \;; b=1;;"#;
+  for i in 1..20 {
+   let sr = StringReader::new(data);
+   let mut bwr = BlockWiseReader::new(Box::new(sr));
+   assert!(bwr.slurp_search_quoted_repos_loop(i, 100, b";;", &KV_QUOTING, FindPos::Begin)?);
+   assert_eq!(16, bwr.pos_get());
+   let sr = StringReader::new(data);
+   let mut bwr = BlockWiseReader::new(Box::new(sr));
+   assert!(bwr.slurp_find_quoted_repos_loop(i, 100, b';', &KV_QUOTING, FindPos::End)?);
+   assert_eq!(12, bwr.pos_get());
+   assert!(!bwr.slurp_search_quoted_repos_loop(i, 100, b"x", &KV_QUOTING, FindPos::End)?);
+   assert_eq!(12, bwr.pos_get());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_slurp_search_quoted_repos_loop_max_len() -> Result<(), Error> {
+  let data = r#"a="x;;y" \;; b=1;;"#;
+  for i in 1..20 {
+   let sr = StringReader::new(data);
+   let mut bwr = BlockWiseReader::new(Box::new(sr));
+   // the found byte slice has to end within max_len bytes
+   assert!(bwr.slurp_search_quoted_repos_loop(i, 18, b";;", &KV_QUOTING, FindPos::End)?);
+   assert_eq!(18, bwr.pos_get());
+   let sr = StringReader::new(data);
+   let mut bwr = BlockWiseReader::new(Box::new(sr));
+   assert!(matches!(
+    bwr.slurp_search_quoted_repos_loop(i, 17, b";;", &KV_QUOTING, FindPos::Begin),
+    Err(Error::Msg("quoted search exceeds max_len"))
+   ));
+   assert_eq!(0, bwr.pos_get());
+   assert!(bwr.available_bytes() <= 17);
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_pos_skip() -> Result<(), std::io::Error> {
+  let sr = StringReader::new("0123456789");
+  let mut bwr = BlockWiseReader::new(Box::new(sr));
+  assert_eq!(2, bwr.slurp(2)?);
+  bwr.pos_add(1);
+  assert_eq!(6, bwr.pos_skip(6, 4)?);
+  assert_eq!(0, bwr.pos_get());
+  assert_eq!(7, bwr.pos_absolute());
+  assert_eq!(1, bwr.slurp(1)?);
+  assert_eq!(b"7", bwr.get());
+  assert_eq!(3, bwr.pos_skip(100, 4)?);
+  assert_eq!(10, bwr.pos_absolute());
+  assert_eq!(0, bwr.size());
+  Ok(())
+ }
+
+ #[test]
+ fn test_resync_fixed() -> Result<(), Error> {
+  let sync = SyncPattern {
+   pattern: b"#",
+   interval: SyncInterval::Fixed(4),
+   confirmations: 3,
+  };
+  for i in [1, 2, 5, 100] {
+   let sr = StringReader::new("ga#bc#d#abc#def#ghi#jk");
+   let mut bwr = BlockWiseReader::new(Box::new(sr));
+   assert_eq!(Some(7), bwr.slurp_resync_repos_loop(i, &sync)?);
+   assert_eq!((0, 7), (bwr.pos_get(), bwr.pos_absolute()));
+   assert_eq!(b"#abc", &bwr.get()[..4]);
+   assert!(bwr.slurp_exact(12)? >= 12);
+   bwr.pos_add(12);
+   assert_eq!(None, bwr.slurp_resync_repos_loop(i, &sync)?);
+   assert_eq!((22, 0), (bwr.pos_absolute(), bwr.available_bytes()));
+  }
+  let sync = SyncPattern {
+   confirmations: 5,
+   ..sync
+  };
+  // the records up to end of file are not enough to confirm the boundary
+  let sr = StringReader::new("x#abc#def");
+  let mut bwr = BlockWiseReader::new(Box::new(sr));
+  assert_eq!(None, bwr.slurp_resync_repos_loop(3, &sync)?);
+  Ok(())
+ }
+
+ #[test]
+ fn test_resync_stray_pattern_near_eof() -> Result<(), Error> {
+  let sync = SyncPattern {
+   pattern: &[0x47],
+   interval: SyncInterval::Fixed(188),
+   confirmations: 5,
+  };
+  let mut data = vec![0; 1000];
+  data[812] = 0x47;
+  for buffersize in [1, 188, 4096] {
+   let mut bwr = BlockWiseReader::from_slice(&data);
+   assert_eq!(None, bwr.slurp_resync_repos_loop(buffersize, &sync)?);
+   assert_eq!((1000, 0), (bwr.pos_absolute(), bwr.available_bytes()));
+   let mut bwr = BlockWiseReader::new(Box::new(std::io::Cursor::new(&data)));
+   assert_eq!(None, bwr.slurp_resync_repos_loop(buffersize, &sync)?);
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_resync_length() -> Result<(), Error> {
+  let length = |header: &[u8]| (header[1] as char).to_digit(10).map(|d| d as usize + 2);
+  let sync = SyncPattern {
+   pattern: b"$",
+   interval: SyncInterval::Length {
+    header_size: 2,
+    length: &length,
+   },
+   confirmations: 3,
+  };
+  for i in [1, 2, 5, 100] {
+   let sr = StringReader::new("x$9$3abc$1z$0$2hi");
+   let mut bwr = BlockWiseReader::new(Box::new(sr));
+   assert_eq!(Some(3), bwr.slurp_resync_repos_loop(i, &sync)?);
+   assert_eq!(b"$3abc", &bwr.get()[..5]);
+   let sr = StringReader::new("$$$x$1");
+   let mut bwr = BlockWiseReader::new(Box::new(sr));
+   assert_eq!(None, bwr.slurp_resync_repos_loop(i, &sync)?);
+   assert_eq!((6, 0), (bwr.pos_absolute(), bwr.available_bytes()));
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_resync_drops_garbage() -> Result<(), Error> {
+  let sync = SyncPattern {
+   pattern: b"#!",
+   interval: SyncInterval::Fixed(4),
+   confirmations: 2,
+  };
+  let mut data = b"#!x".repeat(10000);
+  data.extend_from_slice(b"#!ab#!cd");
+  for buffersize in [1, 3, 64] {
+   let mut bwr = BlockWiseReader::new(Box::new(std::io::Cursor::new(&data)));
+   assert_eq!(Some(30000), bwr.slurp_resync_repos_loop(buffersize, &sync)?);
+   // the garbage is not buffered
+   assert_eq!((0, 30000), (bwr.pos_get(), bwr.pos_absolute()));
+   assert!(bwr.available_bytes() <= 8 + buffersize);
+   assert_eq!(b"#!ab", &bwr.get()[..4]);
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_resync_errors() {
+  let mut bwr = BlockWiseReader::from_slice(b"#abc");
+  let mut sync = SyncPattern {
+   pattern: b"#",
+   interval: SyncInterval::Fixed(4),
+   confirmations: 3,
+  };
+  match bwr.slurp_resync_repos_loop(0, &sync) {
+   Err(Error::Msg(x)) => assert_eq!("buffersize 0 leads to an infinite loop", x),
+   _ => panic!(),
+  };
+  sync.interval = SyncInterval::Fixed(0);
+  match bwr.slurp_resync_repos_loop(4, &sync) {
+   Err(Error::Msg(x)) => assert_eq!("sync interval 0 leads to an infinite loop", x),
+   _ => panic!(),
+  };
+  sync.pattern = b"";
+  match bwr.slurp_resync_repos_loop(4, &sync) {
+   Err(Error::Msg(x)) => assert_eq!("empty sync pattern", x),
+   _ => panic!(),
+  };
+ }
 }
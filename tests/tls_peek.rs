@@ -0,0 +1,164 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+ use crate::common::{msg, Trickle};
+ use blockwise_reader::tls_peek::*;
+ use blockwise_reader::BlockWiseReader;
+ use blockwise_reader::Error;
+ use std::io::Cursor;
+
+ fn vec16(data: &[u8]) -> Vec<u8> {
+  [&(data.len() as u16).to_be_bytes()[..], data].concat()
+ }
+
+ fn extension(extension_type: u16, data: &[u8]) -> Vec<u8> {
+  [&extension_type.to_be_bytes()[..], &vec16(data)].concat()
+ }
+
+ fn client_hello(extensions: &[u8]) -> Vec<u8> {
+  let body = [
+   &[3, 3][..],
+   &[7; 32],
+   &[4, 1, 2, 3, 4],
+   &vec16(&[0x13, 0x01, 0x13, 0x02, 0xc0, 0x2f]),
+   &[1, 0],
+   &vec16(extensions),
+  ]
+  .concat();
+  [
+   &[CLIENT_HELLO][..],
+   &(body.len() as u32).to_be_bytes()[1..],
+   &body,
+  ]
+  .concat()
+ }
+
+ fn records(message: &[u8], fragment: usize) -> Vec<u8> {
+  message
+   .chunks(fragment)
+   .flat_map(|chunk| [&[HANDSHAKE, 3, 1][..], &vec16(chunk)].concat())
+   .collect()
+ }
+
+ fn extensions() -> Vec<u8> {
+  [
+   extension(EXTENSION_SERVER_NAME, &vec16(&[&[0][..], &vec16(b"backend.example")].concat())),
+   extension(0x000a, &[0, 2, 0, 0x1d]),
+   extension(EXTENSION_ALPN, &vec16(b"\x02h2\x08http/1.1")),
+   extension(EXTENSION_SUPPORTED_VERSIONS, b"\x04\x03\x04\x03\x03"),
+   extension(0xff01, &[0]),
+  ]
+  .concat()
+ }
+
+ #[test]
+ fn test_tls_peek_001() -> Result<(), Error> {
+  let message = client_hello(&extensions());
+  for fragment in [1, 3, 50, 16384] {
+   let data = [records(&message, fragment), b"application data".to_vec()].concat();
+   let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(&data)));
+   let hello = peek_client_hello(&mut bwr, 16 * 1024)?.unwrap();
+   assert_eq!((0x0301, 0x0303), (hello.record_version, hello.version));
+   assert_eq!([7; 32], hello.random);
+   assert_eq!(vec![1, 2, 3, 4], hello.session_id);
+   assert_eq!(vec![0x1301, 0x1302, 0xc02f], hello.cipher_suites);
+   assert_eq!(vec![0], hello.compression_methods);
+   assert_eq!(Some("backend.example"), hello.server_name.as_deref());
+   assert_eq!(vec![b"h2".to_vec(), b"http/1.1".to_vec()], hello.alpn);
+   assert_eq!(vec![0x0304, 0x0303], hello.supported_versions);
+   assert_eq!(
+    vec![0, 0x0a, 16, 43, 0xff01],
+    hello
+     .extensions
+     .iter()
+     .map(|e| e.extension_type)
+     .collect::<Vec<_>>()
+   );
+   assert_eq!(Some(&[0, 2, 0, 0x1d][..]), hello.extension(0x000a));
+   assert_eq!(data.len() - 16, hello.size);
+   assert_eq!(0, bwr.pos_get());
+   assert_eq!(&data[..hello.size], &bwr.get()[..hello.size]);
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_tls_peek_minimal() -> Result<(), Error> {
+  let mut message = client_hello(b"");
+  let len = message.len() - 2;
+  message.truncate(len);
+  message[3] -= 2;
+  let data = records(&message, 100);
+  let mut bwr = BlockWiseReader::from_slice(&data);
+  let hello = peek_client_hello(&mut bwr, 1024)?.unwrap();
+  assert!(hello.extensions.is_empty());
+  assert_eq!(None, hello.server_name);
+
+  let mut bwr = BlockWiseReader::from_slice(b"GET / HTTP/1.1\r\n");
+  assert!(peek_client_hello(&mut bwr, 1024)?.is_none());
+  let mut bwr = BlockWiseReader::from_slice(b"");
+  assert!(peek_client_hello(&mut bwr, 1024)?.is_none());
+  Ok(())
+ }
+
+ fn tls_error(data: &[u8]) -> &'static str {
+  let mut bwr = BlockWiseReader::from_slice(data);
+  let x = msg(peek_client_hello(&mut bwr, 1024));
+  assert_eq!(0, bwr.pos_get());
+  x
+ }
+
+ #[test]
+ fn test_tls_peek_errors() {
+  let message = client_hello(&extensions());
+  let data = records(&message, 40);
+  assert_eq!("unexpected end of file in tls record", tls_error(&data[..100]));
+  assert_eq!("unexpected end of file in tls record", tls_error(&data[..47]));
+  let mut alert = data.clone();
+  alert[45] = 21;
+  assert_eq!("unexpected tls record type", tls_error(&alert));
+  assert_eq!("unsupported tls record version", tls_error(b"\x16\x02\x00\x00\x01\x01"));
+  assert_eq!("invalid tls record length", tls_error(b"\x16\x03\x01\x00\x00"));
+  assert_eq!("invalid tls record length", tls_error(b"\x16\x03\x01\x40\x01"));
+  assert_eq!("not a tls client hello", tls_error(&records(b"\x02\x00\x00\x01\x00", 10)));
+  assert_eq!(
+   "tls client hello exceeds max_hello_size",
+   tls_error(&records(b"\x01\x00\x04\x01", 10))
+  );
+  assert_eq!("invalid tls client hello", tls_error(&records(b"\x01\x00\x00\x02\x03\x03", 10)));
+  let bad_sni = client_hello(&extension(EXTENSION_SERVER_NAME, &vec16(&[0, 0, 9, b'x'])));
+  assert_eq!("invalid tls client hello", tls_error(&records(&bad_sni, 1000)));
+  let bad_alpn = client_hello(&extension(EXTENSION_ALPN, &vec16(b"\x02h2x")));
+  assert_eq!("invalid tls client hello", tls_error(&records(&bad_alpn, 1000)));
+ }
+
+ #[test]
+ fn test_tls_peek_fragmented_records() -> Result<(), Error> {
+  let message = client_hello(&extensions());
+  // the records and the message are split between the reads of the socket
+  for (fragment, step) in [(1, 1), (7, 3), (50, 64), (16384, 5)] {
+   let data = [records(&message, fragment), b"application data".to_vec()].concat();
+   let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(&data, step)));
+   let hello = peek_client_hello(&mut bwr, 16 * 1024)?.unwrap();
+   assert_eq!(Some("backend.example"), hello.server_name.as_deref());
+   assert_eq!(data.len() - 16, hello.size);
+   assert_eq!(0, bwr.pos_get());
+   assert_eq!(&data[..hello.size], &bwr.get()[..hello.size]);
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_tls_peek_alert_record_unconsumed() -> Result<(), Error> {
+  let mut data = records(&client_hello(&extensions()), 40);
+  data[45] = 21;
+  let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(&data, 7)));
+  assert_eq!("unexpected tls record type", msg(peek_client_hello(&mut bwr, 1024)));
+  // the peeked bytes remain unconsumed, a second attempt fails the same way
+  assert_eq!((0, 0), (bwr.pos_get(), bwr.pos_absolute()));
+  assert_eq!(&data[..bwr.available_bytes()], bwr.get());
+  assert_eq!("unexpected tls record type", msg(peek_client_hello(&mut bwr, 1024)));
+  Ok(())
+ }
+}
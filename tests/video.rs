@@ -0,0 +1,289 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+ use crate::common::{msg, Broken, Trickle};
+ use blockwise_reader::video::*;
+ use blockwise_reader::BlockWiseReader;
+ use blockwise_reader::Error;
+ use std::io::{Cursor, Read};
+
+ fn ts(pid: u16, cc: u8, start: bool, adaptation: Option<u8>, fill: u8) -> Vec<u8> {
+  let mut packet = vec![
+   SYNC_BYTE,
+   (pid >> 8) as u8 | if start { 0x40 } else { 0 },
+   pid as u8,
+  ];
+  match adaptation {
+   Some(len) => {
+    packet.push(0x30 | cc);
+    packet.push(len);
+    packet.extend(vec![0xff; len as usize]);
+   }
+   None => packet.push(0x10 | cc),
+  }
+  packet.resize(TS_PACKET_SIZE, fill);
+  packet
+ }
+
+ #[test]
+ fn test_ts_001() -> Result<(), Error> {
+  let mut data = vec![0xaa; 50];
+  for cc in 0..5 {
+   data.extend(ts(0x100, cc, 0 == cc, if 2 == cc { Some(10) } else { None }, cc));
+  }
+  data.extend_from_slice(b"\xaa\xaa\xaa");
+  for cc in 5..9 {
+   data.extend(ts(0x1fff, cc, false, None, 0));
+  }
+  for i in [1, 7, 188, 4096] {
+   let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(&data)));
+   let mut tsr = TsReader::new(&mut bwr, i, 3);
+   let mut packets = vec![];
+   while let Some(p) = tsr.next_packet()? {
+    assert_eq!(188, p.packet_size);
+    let payload = tsr.payload()?;
+    assert_eq!(if 2 == p.continuity_counter { 173 } else { 184 }, payload.len());
+    if p.continuity_counter < 5 {
+     assert_eq!(p.continuity_counter, payload[0]);
+    }
+    packets.push((p.offset, p.pid, p.continuity_counter, p.payload_unit_start, p.skipped));
+   }
+   assert_eq!(
+    vec![
+     (50, 0x100, 0, true, 50),
+     (238, 0x100, 1, false, 0),
+     (426, 0x100, 2, false, 0),
+     (614, 0x100, 3, false, 0),
+     (802, 0x100, 4, false, 0),
+     (993, 0x1fff, 5, false, 3),
+     (1181, 0x1fff, 6, false, 0),
+     (1369, 0x1fff, 7, false, 0),
+     (1557, 0x1fff, 8, false, 0),
+    ],
+    packets
+   );
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_ts_packet_sizes() -> Result<(), Error> {
+  for size in [192, 204] {
+   let mut data = vec![0; 7];
+   for cc in 0..4u8 {
+    let mut packet = ts(0x20, cc, false, None, 0);
+    if 192 == size {
+     packet.splice(0..0, [0, 0, 1, cc]);
+    } else {
+     packet.extend([0x55; 16]);
+    }
+    data.extend(packet);
+   }
+   let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(&data)));
+   let mut tsr = TsReader::new(&mut bwr, 16, 4);
+   let mut packets = vec![];
+   while let Some(p) = tsr.next_packet()? {
+    assert_eq!(TS_PACKET_SIZE, tsr.packet().unwrap().len());
+    packets.push((p.offset, p.packet_size, p.timecode, p.continuity_counter));
+   }
+   let timecode = |cc: u32| (192 == size).then_some(0x100 + cc);
+   assert_eq!(
+    (0..4)
+     .map(|cc: u32| (7 + cc as u64 * size as u64, size, timecode(cc), cc as u8))
+     .collect::<Vec<_>>(),
+    packets
+   );
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_ts_errors() {
+  let mut data = ts(0x20, 0, false, None, 0);
+  data.extend_from_slice(&ts(0x20, 1, false, Some(200), 0)[..100]);
+  let mut bwr = BlockWiseReader::from_slice(&data);
+  let mut tsr = TsReader::new(&mut bwr, 16, 2);
+  assert!(tsr.next_packet().unwrap().is_some());
+  assert_eq!("unexpected end of file in ts packet", msg(tsr.next_packet()));
+  let data = ts(0x20, 0, false, Some(200), 0);
+  let mut bwr = BlockWiseReader::from_slice(&data);
+  let mut tsr = TsReader::new(&mut bwr, 16, 2);
+  assert!(tsr.next_packet().unwrap().is_some());
+  assert_eq!("invalid ts adaptation field length", msg(tsr.payload()));
+  let mut bwr = BlockWiseReader::from_slice(b"no transport stream");
+  assert!(TsReader::new(&mut bwr, 4, 2)
+   .next_packet()
+   .unwrap()
+   .is_none());
+ }
+
+ #[test]
+ fn test_ts_stray_sync_byte_in_tail() -> Result<(), Error> {
+  let mut tail = vec![0; 1000];
+  tail[812] = SYNC_BYTE;
+  let mut data = vec![];
+  for cc in 0..4 {
+   data.extend(ts(0x20, cc, false, None, 0));
+  }
+  data.extend(&tail);
+  for i in [1, 188, 4096] {
+   let mut bwr = BlockWiseReader::from_slice(&tail);
+   assert!(TsReader::new(&mut bwr, i, 5).next_packet()?.is_none());
+   assert_eq!((1000, 0), (bwr.pos_absolute(), bwr.available_bytes()));
+   let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(&data)));
+   let mut tsr = TsReader::new(&mut bwr, i, 3);
+   for cc in 0..4 {
+    assert_eq!(cc, tsr.next_packet()?.unwrap().continuity_counter);
+   }
+   assert!(tsr.next_packet()?.is_none());
+   assert_eq!(data.len() as u64, bwr.pos_absolute());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_ts_invalid_adaptation_field() -> Result<(), Error> {
+  let mut data = ts(0x20, 0, false, Some(200), 0);
+  data.extend(ts(0x20, 1, false, None, 7));
+  data.extend_from_slice(&ts(0x20, 2, false, None, 0)[..100]);
+  for step in [1, 50, 1000] {
+   let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(&data, step)));
+   let mut tsr = TsReader::new(&mut bwr, 16, 2);
+   tsr.next_packet()?.unwrap();
+   // the packet with the invalid payload remains the current packet
+   assert_eq!("invalid ts adaptation field length", msg(tsr.payload()));
+   assert_eq!(0, tsr.packet().unwrap()[3] & 0x0f);
+   assert_eq!(1, tsr.next_packet()?.unwrap().continuity_counter);
+   assert_eq!(&[7; 184], tsr.payload()?);
+   // the incomplete packet remains at pos
+   assert_eq!("unexpected end of file in ts packet", msg(tsr.next_packet()));
+   assert!(tsr.packet().is_none());
+   assert_eq!((376, 100), (bwr.pos_absolute(), bwr.available_bytes()));
+  }
+  Ok(())
+ }
+
+ fn annex_b() -> Vec<u8> {
+  let mut idr = vec![0x65];
+  for i in 0..300u32 {
+   idr.extend_from_slice(&[i as u8 | 1, 0, 0, 3, 1]);
+  }
+  [
+   &b"junk\0\0"[..],
+   b"\0\0\0\x01\x67\x64\0\0\x03\0\x1f\0\0\x03\x03",
+   b"\0\0\x01\x68\xee",
+   b"\0\0\0\0\x01",
+   &idr,
+   b"\0\0\x01\x06\x05",
+   b"\0\0\x01\x0c\xff\xff\x80\0\0",
+  ]
+  .concat()
+ }
+
+ #[test]
+ fn test_nal_001() -> Result<(), Error> {
+  let data = annex_b();
+  let mut expected_idr = vec![0x65];
+  for i in 0..300u32 {
+   expected_idr.extend_from_slice(&[i as u8 | 1, 0, 0, 1]);
+  }
+  for i in [1, 3, 5, 64, 4096] {
+   let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(&data)));
+   let mut nal = NalReader::new(&mut bwr, i);
+   let mut units = vec![];
+   while let Some(unit) = nal.next_nal()? {
+    let mut rbsp = vec![];
+    if 6 != unit.h264_type() {
+     if 0 == unit.offset % 2 {
+      nal.read_to_end(&mut rbsp)?;
+     } else {
+      let mut byte = [0];
+      while 1 == nal.read(&mut byte)? {
+       rbsp.push(byte[0]);
+      }
+     }
+    }
+    units.push((unit.offset, unit.start_code_len, unit.h264_type(), rbsp));
+   }
+   assert_eq!(
+    vec![
+     (6, 4, 7, b"\x67\x64\0\0\0\x1f\0\0\x03".to_vec()),
+     (21, 3, 8, b"\x68\xee".to_vec()),
+     (27, 4, 5, expected_idr.clone()),
+     (1532, 3, 6, vec![]),
+     (1537, 3, 12, b"\x0c\xff\xff\x80".to_vec()),
+    ],
+    units
+   );
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_nal_skip_partly_read_unit() -> Result<(), Error> {
+  let data = annex_b();
+  for i in [1, 3, 64, 4096] {
+   let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(&data, 5)));
+   let mut nal = NalReader::new(&mut bwr, i);
+   while let Some(unit) = nal.next_nal()? {
+    if 5 == unit.h264_type() {
+     let mut rbsp = [0; 3];
+     nal.read_exact(&mut rbsp)?;
+     assert_eq!(b"\x65\x01\0", &rbsp);
+     assert_eq!(1532, nal.next_nal()?.unwrap().offset);
+    }
+   }
+  }
+  let mut bwr = BlockWiseReader::new(Box::new(Broken::new(b"\0\0\x01\x65\x01\0\0\x03\x01")));
+  let mut nal = NalReader::new(&mut bwr, 4);
+  nal.next_nal()?.unwrap();
+  nal.read_exact(&mut [0; 2])?;
+  assert!(
+   matches!(nal.next_nal(), Err(Error::IO(e)) if std::io::ErrorKind::ConnectionReset == e.kind())
+  );
+  Ok(())
+ }
+
+ #[test]
+ fn test_nal_h265() -> Result<(), Error> {
+  let mut bwr = BlockWiseReader::from_slice(b"\0\0\x01\x40\x01\x0c\0\0\x01\x42\x01\x01\0\0\x01");
+  let mut nal = NalReader::new(&mut bwr, 4);
+  assert_eq!(32, nal.next_nal()?.unwrap().h265_type());
+  assert_eq!(33, nal.next_nal()?.unwrap().h265_type());
+  assert!(nal.next_nal()?.is_none());
+  Ok(())
+ }
+
+ /// the offsets and the RBSP of the NAL units, read in pieces of 2 bytes
+ fn nal_units(bwr: &mut BlockWiseReader, buffersize: usize) -> Result<Vec<(u64, Vec<u8>)>, Error> {
+  let mut nal = NalReader::new(bwr, buffersize);
+  let mut units = vec![];
+  while let Some(unit) = nal.next_nal()? {
+   let mut rbsp = vec![];
+   let mut buf = [0; 2];
+   loop {
+    let n = nal.read(&mut buf)?;
+    if 0 == n {
+     break;
+    }
+    rbsp.extend_from_slice(&buf[..n]);
+   }
+   units.push((unit.offset, rbsp));
+  }
+  Ok(units)
+ }
+
+ #[test]
+ fn test_nal_start_codes_across_reads() -> Result<(), Error> {
+  let data = annex_b();
+  let expected = nal_units(&mut BlockWiseReader::from_slice(&data), 4096)?;
+  assert_eq!(5, expected.len());
+  // start codes and emulation prevention bytes are split between the reads of the socket
+  for (step, buffersize) in [(1, 3), (2, 4), (5, 7), (64, 3)] {
+   let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(&data, step)));
+   assert_eq!(expected, nal_units(&mut bwr, buffersize)?);
+  }
+  Ok(())
+ }
+}
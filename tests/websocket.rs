@@ -0,0 +1,233 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+ use crate::common::{msg, Trickle};
+ use blockwise_reader::websocket::*;
+ use blockwise_reader::BlockWiseReader;
+ use blockwise_reader::Error;
+ use std::io::{Cursor, ErrorKind, Read};
+
+ fn frame(b0: u8, mask: Option<[u8; 4]>, payload: &[u8]) -> Vec<u8> {
+  let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+  let mut data = vec![b0];
+  match payload.len() {
+   len @ 0..=125 => data.push(mask_bit | len as u8),
+   len @ 126..=0xffff => {
+    data.push(mask_bit | 126);
+    data.extend_from_slice(&(len as u16).to_be_bytes());
+   }
+   len => {
+    data.push(mask_bit | 127);
+    data.extend_from_slice(&(len as u64).to_be_bytes());
+   }
+  }
+  match mask {
+   Some(mask) => {
+    data.extend_from_slice(&mask);
+    data.extend(payload.iter().enumerate().map(|(idx, b)| b ^ mask[idx % 4]));
+   }
+   None => data.extend_from_slice(payload),
+  }
+  data
+ }
+
+ #[test]
+ fn test_websocket_frames() -> Result<(), Error> {
+  let mask = Some([0x12, 0x34, 0x56, 0x78]);
+  let big: Vec<u8> = (0..70000u32).map(|i| (i % 251) as u8).collect();
+  let data = [
+   frame(0x82, mask, &big[..300]),
+   frame(0x82, None, &big),
+   frame(0xc1, mask, b"compressed"),
+   frame(0x8a, None, b""),
+  ]
+  .concat();
+  for buffersize in [1, 7, 4096] {
+   let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(&data)));
+   let mut ws = WebSocketReader::new(&mut bwr, buffersize, 100000);
+   let header = ws.next_frame()?.unwrap();
+   assert_eq!(
+    FrameHeader {
+     fin: true,
+     rsv: 0,
+     opcode: Opcode::Binary,
+     mask,
+     payload_len: 300,
+     offset: 0,
+     payload_offset: 8,
+    },
+    header
+   );
+   let mut buf = [0; 5];
+   ws.read_exact(&mut buf)?;
+   assert_eq!(&big[..5], &buf);
+   assert_eq!(&big[5..300], &ws.read_payload()?[..]);
+
+   let header = ws.next_frame()?.unwrap();
+   assert_eq!(
+    (None, 70000, 308, 318),
+    (header.mask, header.payload_len, header.offset, header.payload_offset)
+   );
+   ws.read_exact(&mut buf)?;
+   assert_eq!(&big[..5], &buf);
+
+   let header = ws.next_frame()?.unwrap();
+   assert_eq!((4, Opcode::Text), (header.rsv, header.opcode));
+   assert_eq!(70318, header.offset);
+   assert_eq!(b"compressed", &ws.read_payload()?[..]);
+   let header = ws.next_frame()?.unwrap();
+   assert_eq!((Opcode::Pong, 0), (header.opcode, header.payload_len));
+   assert!(ws.next_frame()?.is_none());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_websocket_messages() -> Result<(), Error> {
+  let mask = Some([1, 2, 3, 4]);
+  let data = [
+   frame(0x02, mask, b"\x00\x01"),
+   frame(0x00, mask, b""),
+   frame(0x89, mask, b"ping"),
+   frame(0x80, mask, b"\x02\x03\x04"),
+   frame(0x81, None, "grüße".as_bytes()),
+   frame(0x88, mask, b"\x03\xe9bye"),
+  ]
+  .concat();
+  for buffersize in [1, 3, 100] {
+   let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(&data)));
+   let mut ws = WebSocketReader::new(&mut bwr, buffersize, 1024);
+   let message = |opcode, data: &[u8]| {
+    Some(Message {
+     opcode,
+     data: data.to_vec(),
+    })
+   };
+   assert_eq!(message(Opcode::Ping, b"ping"), ws.read_message(5)?);
+   assert_eq!(message(Opcode::Binary, b"\x00\x01\x02\x03\x04"), ws.read_message(5)?);
+   assert_eq!(message(Opcode::Text, "grüße".as_bytes()), ws.read_message(7)?);
+   let close = ws.read_message(5)?.unwrap();
+   assert_eq!(Some((1001, &b"bye"[..])), close.close_status());
+   assert_eq!(None, ws.read_message(5)?);
+  }
+  Ok(())
+ }
+
+ fn websocket_error(data: &[u8], frames: usize) -> &'static str {
+  let mut bwr = BlockWiseReader::from_slice(data);
+  let mut ws = WebSocketReader::new(&mut bwr, 16, 1000);
+  for _ in 0..frames {
+   ws.next_frame().unwrap().unwrap();
+  }
+  msg(ws.next_frame())
+ }
+
+ fn message_error(data: &[u8], max_message_size: u64) -> &'static str {
+  let mut bwr = BlockWiseReader::from_slice(data);
+  let mut ws = WebSocketReader::new(&mut bwr, 16, 1000);
+  msg(ws.read_message(max_message_size))
+ }
+
+ #[test]
+ fn test_websocket_errors() {
+  assert_eq!("unexpected end of file in websocket frame header", websocket_error(b"\x81", 0));
+  assert_eq!(
+   "unexpected end of file in websocket frame header",
+   websocket_error(b"\x81\xfe\x00", 0)
+  );
+  assert_eq!("unexpected end of file in websocket payload", websocket_error(b"\x81\x05abc", 1));
+  assert_eq!(
+   "unexpected end of file in fragmented websocket message",
+   websocket_error(&frame(0x01, None, b"abc"), 1)
+  );
+  assert_eq!("reserved websocket opcode", websocket_error(b"\x83\x00", 0));
+  assert_eq!("reserved websocket opcode", websocket_error(b"\x8b\x00", 0));
+  assert_eq!("invalid websocket payload length", websocket_error(b"\x82\x7e\x00\x7d", 0));
+  assert_eq!(
+   "invalid websocket payload length",
+   websocket_error(b"\x82\x7f\x00\x00\x00\x00\x00\x00\xff\xff", 0)
+  );
+  assert_eq!(
+   "invalid websocket payload length",
+   websocket_error(b"\x82\x7f\x80\x00\x00\x00\x00\x00\x00\x00", 0)
+  );
+  assert_eq!("fragmented websocket control frame", websocket_error(b"\x09\x00", 0));
+  assert_eq!("websocket control frame exceeds 125 bytes", websocket_error(b"\x89\x7e\x00\x7e", 0));
+  assert_eq!("websocket frame exceeds max_frame_size", websocket_error(b"\x82\x7e\x03\xe9", 0));
+  assert_eq!("unexpected websocket continuation frame", websocket_error(b"\x80\x00", 0));
+  assert_eq!(
+   "unfinished fragmented websocket message",
+   websocket_error(&[frame(0x01, None, b"a"), frame(0x81, None, b"b")].concat(), 1)
+  );
+  assert_eq!(
+   "websocket message exceeds max_message_size",
+   message_error(&[frame(0x01, None, b"abc"), frame(0x80, None, b"d")].concat(), 3)
+  );
+  assert_eq!(
+   "invalid utf-8 in websocket text message",
+   message_error(&frame(0x81, None, b"\xff"), 3)
+  );
+
+  let data = b"\x82\x01a\x83\x00";
+  let mut bwr = BlockWiseReader::from_slice(data);
+  let mut ws = WebSocketReader::new(&mut bwr, 16, 1000);
+  ws.next_frame().unwrap();
+  assert!(ws.next_frame().is_err());
+  drop(ws);
+  assert_eq!(b"\x83\x00", bwr.get());
+ }
+
+ #[test]
+ fn test_websocket_masked_payload_in_pieces() -> Result<(), Error> {
+  let mask = Some([0x12, 0x34, 0x56, 0x78]);
+  let payload: Vec<u8> = (0..300u32).map(|i| (i % 251) as u8).collect();
+  let data = [frame(0x82, mask, &payload), frame(0x89, None, b"ping")].concat();
+  for (step, buffersize) in [(1, 1), (3, 5), (64, 7)] {
+   let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(&data, step)));
+   let mut ws = WebSocketReader::new(&mut bwr, buffersize, 1000);
+   ws.next_frame()?.unwrap();
+   // the masking key continues across the reads
+   let mut buf = [0; 3];
+   let mut read = vec![];
+   while read.len() < 100 {
+    let n = ws.read(&mut buf)?;
+    assert!(n > 0 && n <= buffersize);
+    read.extend_from_slice(&buf[..n]);
+   }
+   assert_eq!(&payload[..read.len()], &read[..]);
+   assert_eq!(&payload[read.len()..], &ws.read_payload()?[..]);
+   assert_eq!(Opcode::Ping, ws.next_frame()?.unwrap().opcode);
+   assert_eq!(b"ping", &ws.read_payload()?[..]);
+   assert!(ws.next_frame()?.is_none());
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_websocket_truncated_payload() -> Result<(), Error> {
+  let data = frame(0x82, Some([1, 2, 3, 4]), b"abcdef");
+  let mut bwr = BlockWiseReader::from_slice(&data[..9]);
+  let mut ws = WebSocketReader::new(&mut bwr, 2, 1000);
+  ws.next_frame()?.unwrap();
+  let mut payload = vec![];
+  let e = ws.read_to_end(&mut payload).unwrap_err();
+  assert_eq!(ErrorKind::InvalidData, e.kind());
+  assert_eq!(b"abc", &payload[..]);
+  assert_eq!("unexpected end of file in websocket payload", msg(ws.next_frame()));
+  assert_eq!((9, 0), (bwr.pos_absolute(), bwr.available_bytes()));
+  Ok(())
+ }
+
+ #[test]
+ fn test_websocket_unfinished_message_keeps_header() -> Result<(), Error> {
+  let data = [frame(0x01, None, b"ab"), frame(0x82, None, b"c")].concat();
+  let mut bwr = BlockWiseReader::from_slice(&data);
+  let mut ws = WebSocketReader::new(&mut bwr, 2, 1000);
+  assert_eq!("unfinished fragmented websocket message", msg(ws.read_message(10)));
+  // the frame header remains at pos
+  assert_eq!("unfinished fragmented websocket message", msg(ws.next_frame()));
+  assert_eq!((4, 0), (bwr.pos_absolute(), bwr.pos_get()));
+  Ok(())
+ }
+}
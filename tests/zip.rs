@@ -0,0 +1,307 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+ use crate::common::{first_msg, msg, Trickle};
+ use blockwise_reader::zip::{read_central_directory, ZipEntry, ZipStreamReader};
+ use blockwise_reader::BlockWiseReader;
+ use blockwise_reader::Error;
+ use std::io::{Cursor, Read, Seek, SeekFrom};
+
+ struct Member {
+  name: &'static str,
+  data: &'static [u8],
+  flags: u16,
+  /// Some(signed) if a data descriptor is written
+  descriptor: Option<bool>,
+  crc32: u32,
+ }
+
+ const MEMBERS: [Member; 3] = [
+  Member {
+   name: "a.txt",
+   data: b"hello",
+   flags: 0,
+   descriptor: None,
+   crc32: 0x3610a686,
+  },
+  Member {
+   name: "dir/b.bin",
+   data: b"xPK\x07\x08\x01\0\0\0\x02\0\0\0\x03\0\0\0PK\x03\x04y",
+   flags: 8,
+   descriptor: Some(true),
+   crc32: 0x11111111,
+  },
+  Member {
+   name: "c",
+   data: b"PK\x01\x02",
+   flags: 8,
+   descriptor: Some(false),
+   crc32: 0x22222222,
+  },
+ ];
+
+ fn le16(v: &mut Vec<u8>, x: u16) {
+  v.extend_from_slice(&x.to_le_bytes());
+ }
+
+ fn le32(v: &mut Vec<u8>, x: u32) {
+  v.extend_from_slice(&x.to_le_bytes());
+ }
+
+ /// an archive of MEMBERS with the central directory behind prefix bytes
+ fn archive(prefix: &[u8]) -> Vec<u8> {
+  let mut v = prefix.to_vec();
+  let mut offsets = vec![];
+  for m in MEMBERS.iter() {
+   offsets.push((v.len() - prefix.len()) as u32);
+   let (crc, size) = match m.descriptor {
+    None => (m.crc32, m.data.len() as u32),
+    Some(_) => (0, 0),
+   };
+   v.extend_from_slice(b"PK\x03\x04\x14\0");
+   le16(&mut v, m.flags);
+   le16(&mut v, 0);
+   le32(&mut v, 0x5a2b_6000);
+   le32(&mut v, crc);
+   le32(&mut v, size);
+   le32(&mut v, size);
+   le16(&mut v, m.name.len() as u16);
+   le16(&mut v, 4);
+   v.extend_from_slice(m.name.as_bytes());
+   v.extend_from_slice(b"\xfe\xca\0\0");
+   v.extend_from_slice(m.data);
+   if let Some(signed) = m.descriptor {
+    if signed {
+     v.extend_from_slice(b"PK\x07\x08");
+    }
+    le32(&mut v, m.crc32);
+    le32(&mut v, m.data.len() as u32);
+    le32(&mut v, m.data.len() as u32);
+   }
+  }
+  let cd_offset = (v.len() - prefix.len()) as u32;
+  for (m, offset) in MEMBERS.iter().zip(offsets) {
+   v.extend_from_slice(b"PK\x01\x02\x14\0\x14\0");
+   le16(&mut v, m.flags);
+   le16(&mut v, 0);
+   le32(&mut v, 0x5a2b_6000);
+   le32(&mut v, m.crc32);
+   le32(&mut v, m.data.len() as u32);
+   le32(&mut v, m.data.len() as u32);
+   le16(&mut v, m.name.len() as u16);
+   le16(&mut v, 0);
+   le16(&mut v, 0);
+   v.extend_from_slice(&[0; 8]);
+   le32(&mut v, offset);
+   v.extend_from_slice(m.name.as_bytes());
+  }
+  let cd_size = (v.len() - prefix.len()) as u32 - cd_offset;
+  v.extend_from_slice(b"PK\x05\x06\0\0\0\0\x03\0\x03\0");
+  le32(&mut v, cd_size);
+  le32(&mut v, cd_offset);
+  le16(&mut v, 7);
+  v.extend_from_slice(b"comment");
+  v
+ }
+
+ fn check(entries: &[ZipEntry], base: u64) {
+  assert_eq!(3, entries.len());
+  for (e, m) in entries.iter().zip(MEMBERS.iter()) {
+   assert_eq!(m.name.as_bytes(), &e.name[..]);
+   assert_eq!(m.flags, e.flags);
+   assert_eq!(0, e.method);
+   assert_eq!(m.crc32, e.crc32);
+   assert_eq!(m.data.len() as u64, e.compressed_size);
+   assert_eq!(m.data.len() as u64, e.uncompressed_size);
+   assert_eq!((0x6000, 0x5a2b), (e.dos_time, e.dos_date));
+   assert_eq!(e.header_offset + 34 + m.name.len() as u64, e.data_offset);
+  }
+  assert_eq!(
+   vec![base, base + 44, base + 44 + 65 + 16],
+   entries.iter().map(|e| e.header_offset).collect::<Vec<_>>()
+  );
+ }
+
+ #[test]
+ fn test_zip_stream() -> Result<(), Error> {
+  let data = archive(b"");
+  for i in 1..40 {
+   let mut bwr = BlockWiseReader::new(Box::new(Cursor::new(&data)));
+   let mut zip = ZipStreamReader::new(&mut bwr, i);
+   let mut entries = vec![];
+   while let Some(entry) = zip.next_entry()? {
+    entries.push(entry);
+   }
+   check(&entries, 0);
+   assert!(zip.next_entry()?.is_none());
+   assert!(bwr.slurp_exact(4)? >= 4);
+   assert_eq!(b"PK\x01\x02", &bwr.get()[..4]);
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_zip_central_directory() -> Result<(), Error> {
+  let data = archive(b"#!/bin/sh\n");
+  for i in [1, 7, 100] {
+   let mut cursor = Cursor::new(&data);
+   cursor.set_position(10);
+   let entries = read_central_directory(&mut cursor, i)?;
+   check(&entries, 10);
+   assert_eq!(10, cursor.position());
+  }
+  Ok(())
+ }
+
+ /// a seekable source which counts the bytes read
+ struct Counting<'d> {
+  cursor: Cursor<&'d [u8]>,
+  read: usize,
+ }
+
+ impl Read for Counting<'_> {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+   let n = self.cursor.read(buf)?;
+   self.read += n;
+   Ok(n)
+  }
+ }
+
+ impl Seek for Counting<'_> {
+  fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+   self.cursor.seek(pos)
+  }
+ }
+
+ #[test]
+ fn test_zip_central_directory_reads_only_the_end() -> Result<(), Error> {
+  // removed data between the members and the central directory is never read
+  let mut data = archive(b"");
+  let len = data.len();
+  let cd_offset = u32::from_le_bytes(data[len - 13..len - 9].try_into().unwrap());
+  data.splice(cd_offset as usize..cd_offset as usize, vec![0; 1 << 20]);
+  let len = data.len();
+  data[len - 13..len - 9].copy_from_slice(&(cd_offset + (1 << 20)).to_le_bytes());
+  let mut source = Counting {
+   cursor: Cursor::new(&data),
+   read: 0,
+  };
+  check(&read_central_directory(&mut source, 4096)?, 0);
+  assert!(source.read < 70_000);
+  Ok(())
+ }
+
+ #[test]
+ fn test_zip64_central_directory() -> Result<(), Error> {
+  let mut v = b"PK\x03\x04\x2d\0\0\0\0\0\0\0\0\0\0\0\0\0".to_vec();
+  v.extend_from_slice(&[0xff; 8]);
+  v.extend_from_slice(b"\x01\0\x14\0x\x01\0\x10\0");
+  v.extend_from_slice(&3u64.to_le_bytes());
+  v.extend_from_slice(&3u64.to_le_bytes());
+  v.extend_from_slice(b"abc");
+  let cd_offset = v.len() as u64;
+  v.extend_from_slice(b"PK\x01\x02\x2d\0\x2d\0\0\0\0\0\0\0\0\0\0\0\0\0");
+  v.extend_from_slice(&[0xff; 8]);
+  v.extend_from_slice(b"\x01\0\x1c\0\0\0\0\0\0\0\0\0\0\0\xff\xff\xff\xffx\x01\0\x18\0");
+  v.extend_from_slice(&5_000_000_000u64.to_le_bytes());
+  v.extend_from_slice(&3u64.to_le_bytes());
+  v.extend_from_slice(&0u64.to_le_bytes());
+  let cd_size = v.len() as u64 - cd_offset;
+  let record = v.len() as u64;
+  v.extend_from_slice(b"PK\x06\x06");
+  v.extend_from_slice(&44u64.to_le_bytes());
+  v.extend_from_slice(b"\x2d\0\x2d\0\0\0\0\0\0\0\0\0");
+  v.extend_from_slice(&1u64.to_le_bytes());
+  v.extend_from_slice(&1u64.to_le_bytes());
+  v.extend_from_slice(&cd_size.to_le_bytes());
+  v.extend_from_slice(&cd_offset.to_le_bytes());
+  v.extend_from_slice(b"PK\x06\x07\0\0\0\0");
+  v.extend_from_slice(&record.to_le_bytes());
+  v.extend_from_slice(b"\x01\0\0\0");
+  v.extend_from_slice(b"PK\x05\x06\0\0\0\0\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\0\0");
+
+  let entries = read_central_directory(&mut Cursor::new(&v), 100)?;
+  assert_eq!(1, entries.len());
+  assert_eq!(b"x", &entries[0].name[..]);
+  assert_eq!((3, 5_000_000_000), (entries[0].compressed_size, entries[0].uncompressed_size));
+  assert_eq!((0, 51), (entries[0].header_offset, entries[0].data_offset));
+
+  let mut bwr = BlockWiseReader::from_slice(&v);
+  let mut zip = ZipStreamReader::new(&mut bwr, 100);
+  let entry = zip.next_entry()?.unwrap();
+  assert_eq!((3, 3), (entry.compressed_size, entry.uncompressed_size));
+  assert!(zip.next_entry()?.is_none());
+  Ok(())
+ }
+
+ fn stream_error(data: &[u8]) -> &'static str {
+  let mut bwr = BlockWiseReader::from_slice(data);
+  let mut zip = ZipStreamReader::new(&mut bwr, 4);
+  first_msg(|| zip.next_entry())
+ }
+
+ #[test]
+ fn test_zip_errors() {
+  let data = archive(b"");
+  assert_eq!("invalid zip local header signature", stream_error(b"PK\x03\x05"));
+  assert_eq!("unexpected end of file in zip local header", stream_error(&data[..35]));
+  assert_eq!("unexpected end of file in zip entry data", stream_error(&data[..42]));
+  assert_eq!("missing zip data descriptor", stream_error(&data[..100]));
+  assert_eq!(
+   "missing zip end of central directory record",
+   msg(read_central_directory(&mut Cursor::new(&data[..data.len() - 1]), 10))
+  );
+  let mut broken = data.clone();
+  let len = broken.len();
+  broken[len - 10] = 0xff;
+  assert_eq!(
+   "invalid zip central directory",
+   msg(read_central_directory(&mut Cursor::new(&broken), 10))
+  );
+ }
+
+ #[test]
+ fn test_zip_data_descriptor_across_reads() -> Result<(), Error> {
+  let data = archive(b"");
+  // the data descriptors are searched across the reads of the socket
+  for (step, buffersize) in [(1, 1), (3, 5), (17, 2), (50, 1000)] {
+   let mut bwr = BlockWiseReader::new(Box::new(Trickle::new(&data, step)));
+   let mut zip = ZipStreamReader::new(&mut bwr, buffersize);
+   let mut entries = vec![];
+   while let Some(entry) = zip.next_entry()? {
+    entries.push(entry);
+   }
+   check(&entries, 0);
+  }
+  Ok(())
+ }
+
+ #[test]
+ fn test_zip_invalid_local_header_keeps_pos() -> Result<(), Error> {
+  let data = archive(b"");
+  let mut broken = data[..44].to_vec();
+  broken.extend_from_slice(b"PK\x03\x05 and more");
+  let mut bwr = BlockWiseReader::from_slice(&broken);
+  let mut zip = ZipStreamReader::new(&mut bwr, 4);
+  assert_eq!(b"a.txt", &zip.next_entry()?.unwrap().name[..]);
+  assert_eq!("invalid zip local header signature", msg(zip.next_entry()));
+  // the invalid header remains at pos
+  assert_eq!("invalid zip local header signature", msg(zip.next_entry()));
+  assert_eq!((44, 0), (bwr.pos_absolute(), bwr.pos_get()));
+  Ok(())
+ }
+
+ #[test]
+ fn test_zip_missing_descriptor_drops_data() -> Result<(), Error> {
+  let data = archive(b"");
+  let mut bwr = BlockWiseReader::from_slice(&data[..100]);
+  let mut zip = ZipStreamReader::new(&mut bwr, 4);
+  zip.next_entry()?.unwrap();
+  assert_eq!("missing zip data descriptor", msg(zip.next_entry()));
+  // the searched data are dropped except for the bytes of a possible descriptor
+  assert!(bwr.pos_absolute() > 44 + 30);
+  assert!(bwr.available_bytes() <= 16);
+  Ok(())
+ }
+}